@@ -0,0 +1,64 @@
+// Compares closest object queries that scan every object against ones that go through
+// the scene's bounding volume hierarchy, for growing numbers of objects.
+//
+//   cargo bench --bench scene_queries
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ray_marching::ray_marcher::{
+    scene::Scene,
+    scene_objects::objects::{AxisAlignedBox, Sphere},
+    threed_data_types::Point,
+};
+
+// Objects spread through a cube that grows with their number, so the density stays the same
+fn scattered_scene(count: usize) -> Scene {
+    let extent = (count as f64).cbrt() * 4.0;
+    let mut scene = Scene::new();
+    for i in 0..count {
+        let f = i as f64;
+        let center = Point::new((f * 7.3).sin() * extent, (f * 3.1).cos() * extent, (f * 1.7).sin() * extent);
+        if i % 2 == 0 {
+            scene.add_scene_object(Sphere::new(center, 0.5, None));
+        } else {
+            scene.add_scene_object(AxisAlignedBox::new(center, Point::new(0.4, 0.6, 0.5), None));
+        }
+    }
+    scene
+}
+
+fn query_points(count: usize) -> Vec<Point> {
+    let extent = (count as f64).cbrt() * 4.0;
+    (0..256)
+        .map(|i| {
+            let f = i as f64;
+            Point::new((f * 2.3).cos() * extent, (f * 0.7).sin() * extent, (f * 1.1).cos() * extent)
+        })
+        .collect()
+}
+
+fn closest_object(c: &mut Criterion) {
+    let mut group = c.benchmark_group("closest_object");
+    for count in [100, 1_000, 10_000] {
+        let mut scene = scattered_scene(count);
+        scene.build();
+        let points = query_points(count);
+        group.bench_with_input(BenchmarkId::new("linear", count), &points, |b, points| {
+            b.iter(|| {
+                for p in points.iter() {
+                    black_box(scene.get_closest_object_linear(p).map(|c| c.step_distance));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("bvh", count), &points, |b, points| {
+            b.iter(|| {
+                for p in points.iter() {
+                    black_box(scene.get_closest_object(p).map(|c| c.step_distance));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, closest_object);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+// Renders the two spheres from `main` with a label in front of them to label.png.
+//
+//   cargo run --release --example label [text]
+
+use std::f64::consts::PI;
+
+use image::{self, RgbImage};
+use ray_marching::ray_marcher::{
+    camera::Camera,
+    color_data_types::{f64_to_u8, Color},
+    marcher::{self, MarcherHandler},
+    scene_objects::{objects::Sphere, text::Text, transformed::Transformed, SurfaceMaterial},
+    screen::Displayable,
+    threed_data_types::{transform::Transform, Axis, Direction as Vector3D, Point},
+};
+
+fn main() {
+    let (width_res, height_res) = (300, 300);
+    let camera = Camera::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vector3D::new(1.0, 0.0, 0.0).get_norm(),
+        1.0,
+        60_f64.to_radians(),
+        (width_res, height_res),
+    );
+    let mut march_handler = MarcherHandler::new(400, marcher::MAX_DISTANCE, 20, camera);
+
+    march_handler.add_scene_object(Sphere::new(
+        Point::new(30.0, -10.0, 0.0),
+        10.0,
+        Some(SurfaceMaterial::mirror(Color::new(1.0, 0.0, 0.0))),
+    ));
+    march_handler.add_scene_object(Sphere::new(
+        Point::new(30.0, 10.0, 0.0),
+        10.0,
+        Some(SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0))),
+    ));
+    let label = std::env::args().nth(1).unwrap_or_else(|| "RAY MARCHED".to_string());
+    let text = Text::new(
+        &label,
+        Point::new(0.0, 0.0, 0.0),
+        Axis::X,
+        3.0,
+        0.5,
+        Some(SurfaceMaterial::diffuse(Color::new(1.0, 1.0, 1.0))),
+    );
+    // Turned half way around the view direction so it reads the right way up on screen
+    let placement = Transform::from_rotation(Vector3D::new(1.0, 0.0, 0.0), PI).then(&Transform::from_translation(Point::new(18.0, 0.0, -12.0)));
+    march_handler.add_scene_object(Transformed::new(text, placement));
+
+    let screen = march_handler.march();
+    let mut image_buf: RgbImage = image::ImageBuffer::new(width_res, height_res);
+    for (x, y, pixel) in image_buf.enumerate_pixels_mut() {
+        let (r, g, b) = screen.get_color_components((x, y));
+        *pixel = image::Rgb([f64_to_u8(r), f64_to_u8(g), f64_to_u8(b)]);
+    }
+    image_buf.save("label.png").unwrap();
+}
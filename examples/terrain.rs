@@ -0,0 +1,60 @@
+// Renders a heightfield terrain to terrain.png.
+//
+//   cargo run --release --example terrain [heightmap.png]
+//
+// Without a heightmap image the terrain is generated from fractal Perlin noise.
+
+use image::{self, RgbImage};
+use ray_marching::ray_marcher::{
+    camera::Camera,
+    color_data_types::{f64_to_u8, Color},
+    marcher::{self, MarcherHandler},
+    noise::{FractalKind, FractalNoise, Perlin},
+    scene_objects::{
+        heightfield::Heightfield,
+        objects::Sphere,
+        SurfaceMaterial,
+    },
+    screen::Displayable,
+    threed_data_types::{Direction as Vector3D, Point},
+};
+
+fn main() {
+    let (width_res, height_res) = (300, 300);
+    let camera = Camera::new(
+        Point::new(0.0, 0.0, 0.0),
+        Vector3D::new(1.0, -0.35, 0.0).get_norm(),
+        1.0,
+        60_f64.to_radians(),
+        (width_res, height_res),
+    );
+    let mut march_handler = MarcherHandler::new(400, marcher::MAX_DISTANCE, 20, camera);
+
+    let ground = Some(SurfaceMaterial::diffuse(Color::new(0.35, 0.55, 0.25)));
+    let position = Point::new(45.0, -20.0, 0.0);
+    let extents = (40.0, 40.0);
+    let terrain = match std::env::args().nth(1) {
+        Some(path) => Heightfield::load(position, extents, 12.0, &path, ground).unwrap_or_else(|e| {
+            eprintln!("could not load {}: {}", path, e);
+            std::process::exit(1);
+        }),
+        None => {
+            let noise = FractalNoise::new(Perlin::new(2024), FractalKind::Fbm, 5).with_frequency(0.04);
+            Heightfield::from_noise(position, extents, 12.0, noise, ground)
+        }
+    };
+    march_handler.add_scene_object(terrain);
+    march_handler.add_scene_object(Sphere::new(
+        Point::new(60.0, 5.0, -8.0),
+        6.0,
+        Some(SurfaceMaterial::mirror(Color::new(0.9, 0.9, 1.0))),
+    ));
+
+    let screen = march_handler.march();
+    let mut image_buf: RgbImage = image::ImageBuffer::new(width_res, height_res);
+    for (x, y, pixel) in image_buf.enumerate_pixels_mut() {
+        let (r, g, b) = screen.get_color_components((x, y));
+        *pixel = image::Rgb([f64_to_u8(r), f64_to_u8(g), f64_to_u8(b)]);
+    }
+    image_buf.save("terrain.png").unwrap();
+}
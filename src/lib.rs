@@ -0,0 +1 @@
+pub mod ray_marcher;
@@ -1,18 +1,46 @@
-pub mod ray_marcher;
-
 use image::{self, RgbImage};
-use ray_marcher::{
+use ray_marching::ray_marcher::{
     color_data_types::{f64_to_u8, Color},
     marcher,
+    scene_file::SceneFile,
     scene_objects::{objects::Sphere, SurfaceMaterial},
     screen::Displayable,
     threed_data_types::{Direction as Vector3D, Point},
 };
 
 fn main() {
+    // A scene file can be given instead of the built in scene
+    let (mut march_handler, output) = match std::env::args().nth(1) {
+        Some(path) => match SceneFile::load(&path) {
+            Ok(file) => {
+                let output = file.render.output.clone();
+                (file.into_marcher(), output)
+            }
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => (default_scene(), "ray_marched.png".to_string()),
+    };
+    let screen = march_handler.march();
+
+    let mut image_buf: RgbImage = image::ImageBuffer::new(
+        march_handler.get_camera().resolution.0,
+        march_handler.get_camera().resolution.1,
+    );
+
+    for (x, y, pixel) in image_buf.enumerate_pixels_mut() {
+        let (r, g, b) = screen.get_color_components((x, y));
+        *pixel = image::Rgb([f64_to_u8(r), f64_to_u8(g), f64_to_u8(b)]);
+    }
+    image_buf.save(output).unwrap();
+}
+
+fn default_scene() -> marcher::MarcherHandler {
     let height_res = 600;
     let width_res = 600;
-    let camera = ray_marcher::camera::Camera::new(
+    let camera = ray_marching::ray_marcher::camera::Camera::new(
         Point::new(0.0, 0.0, 0.0),
         Vector3D::new(1.0, 0.0, 0.0).get_norm(),
         1.0,
@@ -20,34 +48,17 @@ fn main() {
         (width_res, height_res),
     );
     let mut march_handler =
-        ray_marcher::marcher::MarcherHandler::new(2000, marcher::MAX_DISTANCE, 100, camera);
+        ray_marching::ray_marcher::marcher::MarcherHandler::new(2000, marcher::MAX_DISTANCE, 100, camera);
     // march_handler.debug = true;
     march_handler.add_scene_object(Sphere::new(
         Point::new(30.0, -10.0, 0.0),
         10.0,
-        Some(SurfaceMaterial {
-            color: Color::new(1.0, 0.0, 0.0),
-            reflectivity: 1.0,
-        }),
+        Some(SurfaceMaterial::mirror(Color::new(1.0, 0.0, 0.0))),
     ));
     march_handler.add_scene_object(Sphere::new(
         Point::new(30.0, 10.0, 0.0),
         10.0,
-        Some(SurfaceMaterial {
-            color: Color::new(0.0, 0.0, 1.0),
-            reflectivity: 0.0,
-        }),
+        Some(SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0))),
     ));
-    let screen = march_handler.march();
-
-    let mut image_buf: RgbImage = image::ImageBuffer::new(
-        march_handler.get_camera().resolution.0,
-        march_handler.get_camera().resolution.1,
-    );
-
-    for (x, y, pixel) in image_buf.enumerate_pixels_mut() {
-        let (r, g, b) = screen.get_color_components((x, y));
-        *pixel = image::Rgb([f64_to_u8(r), f64_to_u8(g), f64_to_u8(b)]);
-    }
-    image_buf.save("ray_marched.png").unwrap();
+    march_handler
 }
@@ -2,10 +2,15 @@ pub mod threed_data_types;
 pub mod color_data_types;
 pub mod scene_objects;
 pub mod scene;
+pub mod scene_file;
 pub mod ray;
+pub mod bsdf;
+pub mod lights;
 pub mod marcher;
 pub mod camera;
 pub mod screen;
+pub mod bvh;
+pub mod noise;
 
 
 #[allow(unused_imports)]
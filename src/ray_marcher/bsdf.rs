@@ -0,0 +1,444 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+
+use super::color_data_types::spectrum::Spectrum;
+use super::scene_objects::SurfaceMaterial;
+use super::Vector3D;
+
+// How surfaces scatter light. Directions all point away from the surface: `wo` back
+// along the incoming ray and `wi` toward the next bounce, with `n` the surface's outward
+// unit normal. Only dielectrics scatter light arriving from inside.
+
+pub struct BsdfSample {
+    pub direction: Vector3D,
+    pub weight: Spectrum, // f * cos / pdf, what the path's throughput is multiplied by
+    pub pdf: f64,         // Infinite for the single direction off a perfect mirror or through glass
+    pub specular: bool,   // Whether the direction came from a perfect mirror or a refraction
+}
+
+pub trait Bsdf {
+    fn eval(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> Spectrum; // Reflectance, without the cosine term
+    fn pdf(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> f64; // Density over solid angle of `sample` picking wi
+    fn sample(&self, wo: &Vector3D, n: &Vector3D, rng: &mut dyn RngCore) -> Option<BsdfSample>;
+}
+
+fn dot(a: &Vector3D, b: &Vector3D) -> f64 {
+    Vector3D::get_dot(a, b)
+}
+
+// Mirrors v about the axis a
+fn reflect(v: &Vector3D, a: &Vector3D) -> Vector3D {
+    *a * (2.0 * dot(v, a)) - *v
+}
+
+// Turns a direction given with z along n into world space
+pub fn to_world(x: f64, y: f64, z: f64, n: &Vector3D) -> Vector3D {
+    // Duff et al., "Building an Orthonormal Basis, Revisited"
+    let sign = 1_f64.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    let t = Vector3D::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let bt = Vector3D::new(b, sign + n.y * n.y * a, -n.y);
+    t * x + bt * y + *n * z
+}
+
+// Perfectly matte, scattering the same amount in every direction
+pub struct Lambertian {
+    pub albedo: Spectrum,
+}
+
+impl Bsdf for Lambertian {
+    fn eval(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> Spectrum {
+        if dot(wo, n) <= 0.0 || dot(wi, n) <= 0.0 {
+            return Spectrum::BLACK;
+        }
+        self.albedo / PI
+    }
+
+    fn pdf(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> f64 {
+        if dot(wo, n) <= 0.0 {
+            return 0.0;
+        }
+        dot(wi, n).max(0.0) / PI
+    }
+
+    // Cosine weighted, so every sample carries exactly the albedo
+    fn sample(&self, wo: &Vector3D, n: &Vector3D, rng: &mut dyn RngCore) -> Option<BsdfSample> {
+        if dot(wo, n) <= 0.0 {
+            return None;
+        }
+        let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+        let r = u.sqrt();
+        let phi = 2.0 * PI * v;
+        let cos = (1.0 - u).sqrt();
+        let direction = to_world(r * phi.cos(), r * phi.sin(), cos, n);
+        Some(BsdfSample { direction, weight: self.albedo, pdf: cos / PI, specular: false })
+    }
+}
+
+// Below this alpha a surface is treated as a perfect mirror
+const SMOOTH_ALPHA: f64 = 1e-4;
+
+// Microfacet reflection with the GGX distribution, Smith shadowing and Schlick's Fresnel
+pub struct Ggx {
+    pub f0: Spectrum, // Reflectance head on
+    alpha: f64,
+}
+
+impl Ggx {
+    pub fn new(f0: Spectrum, roughness: f64) -> Self {
+        let r = roughness.clamp(0.0, 1.0);
+        Ggx { f0, alpha: r * r }
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha < SMOOTH_ALPHA
+    }
+
+    fn distribution(&self, cos_h: f64) -> f64 {
+        let a2 = self.alpha * self.alpha;
+        let t = cos_h * cos_h * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    fn shadowing(&self, cos: f64) -> f64 {
+        let a2 = self.alpha * self.alpha;
+        2.0 * cos / (cos + (a2 + (1.0 - a2) * cos * cos).sqrt())
+    }
+
+    fn fresnel(&self, cos: f64) -> Spectrum {
+        self.f0 + (Spectrum::WHITE - self.f0) * (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+    }
+}
+
+impl Bsdf for Ggx {
+    fn eval(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> Spectrum {
+        let (cos_o, cos_i) = (dot(wo, n), dot(wi, n));
+        if self.is_smooth() || cos_o <= 0.0 || cos_i <= 0.0 {
+            return Spectrum::BLACK;
+        }
+        let h = (*wo + *wi).get_norm();
+        let d = self.distribution(dot(&h, n));
+        let g = self.shadowing(cos_o) * self.shadowing(cos_i);
+        self.fresnel(dot(wo, &h)) * (d * g / (4.0 * cos_o * cos_i))
+    }
+
+    fn pdf(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> f64 {
+        if self.is_smooth() || dot(wo, n) <= 0.0 || dot(wi, n) <= 0.0 {
+            return 0.0;
+        }
+        let h = (*wo + *wi).get_norm();
+        let cos_h = dot(&h, n);
+        self.distribution(cos_h) * cos_h / (4.0 * dot(wo, &h))
+    }
+
+    // Picks a microfacet normal in proportion to D(h) cos(h) and reflects about it
+    fn sample(&self, wo: &Vector3D, n: &Vector3D, rng: &mut dyn RngCore) -> Option<BsdfSample> {
+        let cos_o = dot(wo, n);
+        if cos_o <= 0.0 {
+            return None;
+        }
+        if self.is_smooth() {
+            let direction = reflect(wo, n);
+            return Some(BsdfSample { direction, weight: self.fresnel(cos_o), pdf: f64::INFINITY, specular: true });
+        }
+        let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+        let a2 = self.alpha * self.alpha;
+        let cos_h = ((1.0 - u) / (1.0 + (a2 - 1.0) * u)).sqrt();
+        let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let h = to_world(sin_h * phi.cos(), sin_h * phi.sin(), cos_h, n);
+        let direction = reflect(wo, &h);
+        let cos_i = dot(&direction, n);
+        if cos_i <= 0.0 {
+            return None;
+        }
+        let pdf = self.pdf(wo, &direction, n);
+        let weight = self.eval(wo, &direction, n) * (cos_i / pdf);
+        Some(BsdfSample { direction, weight, pdf, specular: false })
+    }
+}
+
+// Share of light reflected off a smooth boundary between two dielectrics, from the cosines
+// on either side and eta, the index of refraction of the incoming side over the other
+pub fn fresnel_dielectric(cos_i: f64, cos_t: f64, eta: f64) -> f64 {
+    let s = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let p = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (s * s + p * p) / 2.0
+}
+
+// A smooth boundary into glass, water or the like, which reflects or refracts by the
+// exact Fresnel equations. Past the critical angle, light inside is totally reflected.
+// Radiance isn't rescaled by eta^2 on crossing, since paths that enter also leave.
+pub struct Dielectric {
+    pub ior: f64, // Of the inside, with the outside taken as air
+}
+
+impl Bsdf for Dielectric {
+    fn eval(&self, _wo: &Vector3D, _wi: &Vector3D, _n: &Vector3D) -> Spectrum {
+        Spectrum::BLACK
+    }
+
+    fn pdf(&self, _wo: &Vector3D, _wi: &Vector3D, _n: &Vector3D) -> f64 {
+        0.0
+    }
+
+    fn sample(&self, wo: &Vector3D, n: &Vector3D, rng: &mut dyn RngCore) -> Option<BsdfSample> {
+        let cos_o = dot(wo, n);
+        // Flipped so the normal faces the side the ray came from
+        let (eta, normal, cos_i) = if cos_o >= 0.0 { (1.0 / self.ior, *n, cos_o) } else { (self.ior, *n * -1.0, -cos_o) };
+        let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+        let reflected = BsdfSample { direction: reflect(wo, &normal), weight: Spectrum::WHITE, pdf: f64::INFINITY, specular: true };
+        if sin2_t >= 1.0 {
+            return Some(reflected);
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        if rng.gen::<f64>() < fresnel_dielectric(cos_i, cos_t, eta) {
+            return Some(reflected);
+        }
+        let direction = *wo * -eta + normal * (eta * cos_i - cos_t);
+        Some(BsdfSample { direction, weight: Spectrum::WHITE, pdf: f64::INFINITY, specular: true })
+    }
+}
+
+// Reflectance of dielectrics like plastic or stone head on
+const DIELECTRIC_F0: f64 = 0.04;
+
+// The metallic/roughness model: a matte base under a glossy coat for dielectrics,
+// fading to bare tinted reflection as the surface becomes metal. Transmission swaps that
+// share of the surface for a clear dielectric.
+pub struct Principled {
+    pub diffuse: Lambertian,
+    pub specular: Ggx,
+    pub transmission: f64,
+    pub dielectric: Dielectric,
+}
+
+impl Principled {
+    pub fn new(albedo: Spectrum, metallic: f64, roughness: f64) -> Self {
+        let metallic = metallic.clamp(0.0, 1.0);
+        let f0 = Spectrum::splat(DIELECTRIC_F0) * (1.0 - metallic) + albedo * metallic;
+        Principled {
+            diffuse: Lambertian { albedo: albedo * (1.0 - metallic) },
+            specular: Ggx::new(f0, roughness),
+            transmission: 0.0,
+            dielectric: Dielectric { ior: 1.5 },
+        }
+    }
+
+    pub fn with_transmission(mut self, transmission: f64, ior: f64) -> Self {
+        self.transmission = transmission.clamp(0.0, 1.0);
+        self.dielectric = Dielectric { ior };
+        self
+    }
+
+    pub fn from_material(sm: &SurfaceMaterial) -> Self {
+        Principled::new(sm.albedo.into(), sm.metallic, sm.roughness).with_transmission(sm.transmission, sm.ior)
+    }
+
+    // How often the glossy lobe is sampled rather than the matte one
+    fn specular_chance(&self) -> f64 {
+        let s = self.specular.f0.max_component();
+        let d = self.diffuse.albedo.max_component();
+        if d <= 0.0 {
+            1.0
+        } else if s <= 0.0 {
+            0.0
+        } else {
+            (s / (s + d)).clamp(0.1, 0.9)
+        }
+    }
+}
+
+impl Bsdf for Principled {
+    // The dielectric only scatters in single directions, so it adds nothing here
+    fn eval(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> Spectrum {
+        (self.diffuse.eval(wo, wi, n) + self.specular.eval(wo, wi, n)) * (1.0 - self.transmission)
+    }
+
+    fn pdf(&self, wo: &Vector3D, wi: &Vector3D, n: &Vector3D) -> f64 {
+        let p = self.specular_chance();
+        (p * self.specular.pdf(wo, wi, n) + (1.0 - p) * self.diffuse.pdf(wo, wi, n)) * (1.0 - self.transmission)
+    }
+
+    // Picks one lobe, then weights the direction by the opaque lobes' combined density
+    fn sample(&self, wo: &Vector3D, n: &Vector3D, rng: &mut dyn RngCore) -> Option<BsdfSample> {
+        if self.transmission > 0.0 && rng.gen::<f64>() < self.transmission {
+            return self.dielectric.sample(wo, n, rng);
+        }
+        let p = self.specular_chance();
+        let direction = if rng.gen::<f64>() < p {
+            let s = self.specular.sample(wo, n, rng)?;
+            if s.specular {
+                return Some(BsdfSample { weight: s.weight / p, ..s });
+            }
+            s.direction
+        } else {
+            self.diffuse.sample(wo, n, rng)?.direction
+        };
+        let pdf = self.pdf(wo, &direction, n);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.eval(wo, &direction, n) * (dot(&direction, n) / pdf);
+        Some(BsdfSample { direction, weight, pdf, specular: false })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_close, assert_within};
+
+    fn up() -> Vector3D {
+        Vector3D::new(0.0, 0.0, 1.0)
+    }
+
+    #[test]
+    fn test_lambertian_sampling() {
+        let bsdf = Lambertian { albedo: Spectrum::new(0.5, 0.25, 1.0) };
+        let wo = Vector3D::new(0.6, 0.0, 0.8);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let s = bsdf.sample(&wo, &up(), &mut rng).unwrap();
+            assert_within(s.direction.length(), 1.0, 1e-12);
+            assert!(s.direction.z > 0.0);
+            assert_within(s.pdf, bsdf.pdf(&wo, &s.direction, &up()), 1e-12);
+            assert_eq!(s.weight, bsdf.albedo);
+        }
+        assert!(bsdf.sample(&(wo * -1.0), &up(), &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_pdf_matches_sampling() {
+        // GGX reflections that would go under the surface are dropped, so the pdf
+        // integrates to the share of samples that succeed rather than to 1
+        let mut rng = StdRng::seed_from_u64(11);
+        let wo = Vector3D::new(0.3, 0.1, 1.0).get_norm();
+        for (metallic, roughness) in [(0.0, 0.6), (1.0, 0.5), (0.5, 0.8)] {
+            let bsdf = Principled::new(Spectrum::new(0.8, 0.6, 0.4), metallic, roughness);
+            let count = 200_000;
+            // Uniformly sampled directions over the sphere, each weighted by 4 pi
+            let total: f64 = (0..count)
+                .map(|_| {
+                    let z: f64 = rng.gen::<f64>() * 2.0 - 1.0;
+                    let phi = rng.gen::<f64>() * 2.0 * PI;
+                    let r = (1.0 - z * z).sqrt();
+                    bsdf.pdf(&wo, &Vector3D::new(r * phi.cos(), r * phi.sin(), z), &up()) * 4.0 * PI
+                })
+                .sum();
+            let integral = total / count as f64;
+            let succeeded = (0..count).filter(|_| bsdf.sample(&wo, &up(), &mut rng).is_some()).count();
+            let expected = succeeded as f64 / count as f64;
+            assert!((integral - expected).abs() < 0.02, "{} vs {} for metallic {}, roughness {}", integral, expected, metallic, roughness);
+        }
+    }
+
+    #[test]
+    fn test_sample_matches_eval_and_pdf() {
+        let bsdf = Principled::new(Spectrum::new(0.9, 0.2, 0.2), 0.3, 0.4);
+        let wo = Vector3D::new(-0.5, 0.2, 0.7).get_norm();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            if let Some(s) = bsdf.sample(&wo, &up(), &mut rng) {
+                assert_close(s.pdf, bsdf.pdf(&wo, &s.direction, &up()));
+                let expected = bsdf.eval(&wo, &s.direction, &up()) * (s.direction.z / s.pdf);
+                assert_close(s.weight.r, expected.r);
+                assert_close(s.weight.g, expected.g);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reciprocity() {
+        let bsdf = Principled::new(Spectrum::new(0.7, 0.7, 0.2), 0.6, 0.35);
+        let a = Vector3D::new(0.2, -0.4, 0.9).get_norm();
+        let b = Vector3D::new(-0.7, 0.1, 0.3).get_norm();
+        let (ab, ba) = (bsdf.eval(&a, &b, &up()), bsdf.eval(&b, &a, &up()));
+        assert_within(ab.r, ba.r, 1e-12);
+        assert_within(ab.b, ba.b, 1e-12);
+    }
+
+    #[test]
+    fn test_rough_metal_conserves_energy() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let wo = Vector3D::new(0.0, 0.0, 1.0);
+        for roughness in [0.2, 0.5, 0.9] {
+            let bsdf = Ggx::new(Spectrum::WHITE, roughness);
+            let count = 20_000;
+            let total: f64 = (0..count).filter_map(|_| bsdf.sample(&wo, &up(), &mut rng)).map(|s| s.weight.g).sum();
+            let albedo = total / count as f64;
+            // Light that would scatter between microfacets more than once is lost, which
+            // only becomes noticeable on rough surfaces
+            assert!(albedo <= 1.0, "{} at roughness {}", albedo, roughness);
+            if roughness < 0.3 {
+                assert!(albedo > 0.95, "{} at roughness {}", albedo, roughness);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fresnel_dielectric() {
+        // Head on from air into glass, ((1 - 1.5) / (1 + 1.5))^2
+        assert_within(fresnel_dielectric(1.0, 1.0, 1.0 / 1.5), 0.04, 1e-12);
+        // At Brewster's angle the p polarized part vanishes
+        let brewster = 1.5_f64.atan();
+        let cos_t = (1.0 - (brewster.sin() / 1.5).powi(2)).sqrt();
+        let s = (brewster.cos() / 1.5 - cos_t) / (brewster.cos() / 1.5 + cos_t);
+        assert_within(fresnel_dielectric(brewster.cos(), cos_t, 1.0 / 1.5), s * s / 2.0, 1e-12);
+    }
+
+    #[test]
+    fn test_dielectric_refraction() {
+        let glass = Dielectric { ior: 1.5 };
+        let mut rng = StdRng::seed_from_u64(9);
+        let wo = Vector3D::new(0.6, 0.0, 0.8);
+        let (mut reflected, mut refracted) = (0, 0);
+        for _ in 0..2000 {
+            let s = glass.sample(&wo, &up(), &mut rng).unwrap();
+            assert_within(s.direction.length(), 1.0, 1e-12);
+            if s.direction.z > 0.0 {
+                reflected += 1;
+                assert_within((s.direction - Vector3D::new(-0.6, 0.0, 0.8)).length(), 0.0, 1e-12);
+            } else {
+                refracted += 1;
+                // Snell's law, sin(t) = sin(i) / 1.5
+                assert_within(s.direction.x, -0.4, 1e-12);
+            }
+        }
+        let share = reflected as f64 / (reflected + refracted) as f64;
+        let cos_t = (1.0 - 0.4_f64 * 0.4).sqrt();
+        assert_within(share, fresnel_dielectric(0.8, cos_t, 1.0 / 1.5), 0.015);
+    }
+
+    #[test]
+    fn test_total_internal_reflection() {
+        let glass = Dielectric { ior: 1.5 };
+        let mut rng = StdRng::seed_from_u64(2);
+        // Leaving the glass at 60 degrees, beyond the critical angle of about 41.8
+        let wo = Vector3D::new(3_f64.sqrt() / 2.0, 0.0, -0.5);
+        for _ in 0..100 {
+            let s = glass.sample(&wo, &up(), &mut rng).unwrap();
+            assert_within((s.direction - Vector3D::new(-(3_f64.sqrt()) / 2.0, 0.0, -0.5)).length(), 0.0, 1e-12);
+        }
+        // Below it, light gets out and bends away from the normal
+        let wo = Vector3D::new(0.5, 0.0, -(3_f64.sqrt()) / 2.0);
+        let out = (0..100).filter_map(|_| glass.sample(&wo, &up(), &mut rng)).find(|s| s.direction.z > 0.0).unwrap();
+        assert_within(out.direction.x, -0.75, 1e-12);
+    }
+
+    #[test]
+    fn test_smooth_metal_is_a_mirror() {
+        let bsdf = Principled::new(Spectrum::new(1.0, 0.0, 0.0), 1.0, 0.0);
+        let wo = Vector3D::new(0.6, 0.0, 0.8);
+        let s = bsdf.sample(&wo, &up(), &mut StdRng::seed_from_u64(1)).unwrap();
+        assert!(s.specular);
+        assert_within((s.direction - Vector3D::new(-0.6, 0.0, 0.8)).length(), 0.0, 1e-12);
+        assert_within(s.weight.r, 1.0, 1e-12);
+        assert_eq!(bsdf.eval(&wo, &s.direction, &up()), Spectrum::BLACK);
+    }
+}
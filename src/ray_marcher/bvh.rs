@@ -0,0 +1,202 @@
+use super::threed_data_types::bounding_box::BoundingBox;
+use super::Point3D;
+
+// A bounding volume hierarchy over a list of boxes. It doesn't know what the boxes
+// hold, callers pass a distance function for the items and the hierarchy only
+// evaluates the items whose box could still beat the closest distance found so far.
+
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Debug)]
+enum BvhNode {
+    Leaf { bounds: BoundingBox, start: usize, count: usize },
+    Inner { bounds: BoundingBox, left: usize, right: usize },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    items: Vec<usize>, // Item indices, grouped so every leaf owns a contiguous range
+}
+
+#[allow(dead_code)]
+impl Bvh {
+    pub fn new(boxes: &[BoundingBox]) -> Self {
+        let mut bvh = Bvh { nodes: Vec::new(), items: (0..boxes.len()).collect() };
+        if !boxes.is_empty() {
+            bvh.build(boxes, 0, boxes.len());
+        }
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Builds the node for items[start..end] and returns its index
+    fn build(&mut self, boxes: &[BoundingBox], start: usize, end: usize) -> usize {
+        let bounds = self.items[start..end]
+            .iter()
+            .fold(BoundingBox::empty(), |b, i| b.union(&boxes[*i]));
+        let index = self.nodes.len();
+        if end - start <= MAX_LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf { bounds, start, count: end - start });
+            return index;
+        }
+
+        // Split at the median center along the axis the centers spread the most
+        let centers = BoundingBox::from_points(self.items[start..end].iter().map(|i| boxes[*i].center()).collect::<Vec<_>>().iter());
+        let axis = centers.longest_axis();
+        let mid = start + (end - start) / 2;
+        self.items[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            boxes[*a].center().get_axis(axis).total_cmp(&boxes[*b].center().get_axis(axis))
+        });
+
+        self.nodes.push(BvhNode::Leaf { bounds, start, count: 0 }); // Replaced once the children exist
+        let left = self.build(boxes, start, mid);
+        let right = self.build(boxes, mid, end);
+        self.nodes[index] = BvhNode::Inner { bounds, left, right };
+        index
+    }
+
+    // Finds the item with the smallest distance to `p`. The distance of an item must
+    // never be smaller than the distance from `p` to its box, unless `p` is inside it.
+    #[allow(clippy::unnecessary_map_or)] // is_none_or needs Rust 1.82
+    pub fn closest<F>(&self, p: &Point3D, mut distance: F) -> Option<(usize, f64)> where F: FnMut(usize) -> f64 {
+        let mut best: Option<(usize, f64)> = None;
+        self.visit_nearest(p, |item| {
+            let d = distance(item);
+            if best.map_or(true, |(_, b)| d < b) {
+                best = Some((item, d));
+            }
+            best.map_or(f64::INFINITY, |(_, b)| b.max(0.0))
+        });
+        best
+    }
+
+    // Calls `visit` with items nearest first, skipping every node whose box is farther
+    // than the radius the last call returned
+    pub fn visit_nearest<F>(&self, p: &Point3D, mut visit: F) where F: FnMut(usize) -> f64 {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut radius = f64::INFINITY;
+        let mut stack = vec![(0, self.nodes[0].bounds().distance_to(p))];
+        while let Some((node, box_distance)) = stack.pop() {
+            if box_distance > radius {
+                continue;
+            }
+            match &self.nodes[node] {
+                BvhNode::Leaf { start, count, .. } => {
+                    for item in self.items[*start..*start + *count].iter() {
+                        radius = visit(*item);
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    let dl = self.nodes[*left].bounds().distance_to(p);
+                    let dr = self.nodes[*right].bounds().distance_to(p);
+                    // Push the farther child first so the nearer one is searched first
+                    if dl < dr {
+                        stack.push((*right, dr));
+                        stack.push((*left, dl));
+                    } else {
+                        stack.push((*left, dl));
+                        stack.push((*right, dr));
+                    }
+                }
+            }
+        }
+    }
+
+    // Calls `visit` with every item whose box comes within `radius` of `p`
+    pub fn visit_within<F>(&self, p: &Point3D, radius: f64, mut visit: F) where F: FnMut(usize) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            if self.nodes[node].bounds().distance_to(p) > radius {
+                continue;
+            }
+            match &self.nodes[node] {
+                BvhNode::Leaf { start, count, .. } => {
+                    for item in self.items[*start..*start + *count].iter() {
+                        visit(*item);
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    stack.push(*left);
+                    stack.push(*right);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unit_boxes(centers: &[Point3D]) -> Vec<BoundingBox> {
+        centers.iter().map(|c| BoundingBox::new(*c - 0.5, *c + 0.5)).collect()
+    }
+
+    #[test]
+    fn test_closest_matches_linear_scan() {
+        let centers: Vec<Point3D> = (0..200)
+            .map(|i| {
+                let f = i as f64;
+                Point3D::new((f * 7.3).sin() * 20.0, (f * 3.1).cos() * 20.0, (f * 1.7).sin() * 20.0)
+            })
+            .collect();
+        let bvh = Bvh::new(&unit_boxes(&centers));
+        let sdf = |i: usize, p: &Point3D| p.distance_to(&centers[i]) - 0.5;
+        for q in [Point3D::new(0.0, 0.0, 0.0), Point3D::new(15.0, -3.0, 8.0), Point3D::new(-40.0, 2.0, 1.0)] {
+            let expected = (0..centers.len()).map(|i| sdf(i, &q)).fold(f64::INFINITY, f64::min);
+            let (_, found) = bvh.closest(&q, |i| sdf(i, &q)).unwrap();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn test_closest_prunes() {
+        let centers: Vec<Point3D> = (0..100).map(|i| Point3D::new(i as f64 * 3.0, 0.0, 0.0)).collect();
+        let bvh = Bvh::new(&unit_boxes(&centers));
+        let mut evaluated = 0;
+        let q = Point3D::new(0.0, 0.0, 0.0);
+        bvh.closest(&q, |i| {
+            evaluated += 1;
+            q.distance_to(&centers[i]) - 0.5
+        });
+        assert!(evaluated < 20);
+    }
+
+    #[test]
+    fn test_visit_within() {
+        let centers: Vec<Point3D> = (0..50).map(|i| Point3D::new(i as f64, 0.0, 0.0)).collect();
+        let bvh = Bvh::new(&unit_boxes(&centers));
+        let mut found = Vec::new();
+        bvh.visit_within(&Point3D::new(10.2, 0.0, 0.0), 1.0, |i| found.push(i));
+        found.sort();
+        assert_eq!(found, vec![9, 10, 11]);
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = Bvh::new(&[]);
+        assert!(bvh.closest(&Point3D::new(0.0, 0.0, 0.0), |_| 0.0).is_none());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod operations;
+pub mod spectrum;
 
 use super::screen::Pixelatable;
 
@@ -14,6 +15,7 @@ pub fn f64_to_u8(c: f64) -> u8 {
 }
 
 pub static BLACK: Color = Color{ r: 0.0, g: 0.0, b: 0.0 };
+pub static WHITE: Color = Color{ r: 1.0, g: 1.0, b: 1.0 };
 
 #[allow(dead_code)]
 fn between_0_1(i: f64) -> bool{
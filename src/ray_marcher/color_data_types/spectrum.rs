@@ -0,0 +1,114 @@
+use std::ops;
+
+use super::Color;
+
+// RGB light or reflectance that isn't limited to [0, 1] the way `Color` is, for
+// carrying radiance along a path and for BSDF values that can be far above 1.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Spectrum {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+impl Spectrum {
+    pub const BLACK: Spectrum = Spectrum { r: 0.0, g: 0.0, b: 0.0 };
+    pub const WHITE: Spectrum = Spectrum { r: 1.0, g: 1.0, b: 1.0 };
+
+    pub fn new(r: f64, g: f64, b: f64) -> Self {
+        Spectrum { r, g, b }
+    }
+
+    pub fn splat(v: f64) -> Self {
+        Spectrum { r: v, g: v, b: v }
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.r <= 0.0 && self.g <= 0.0 && self.b <= 0.0
+    }
+
+    // Clamped into a displayable color
+    pub fn to_color(&self) -> Color {
+        Color::new(self.r, self.g, self.b)
+    }
+}
+
+impl From<Color> for Spectrum {
+    fn from(c: Color) -> Self {
+        let (r, g, b) = c.get_components();
+        Spectrum { r, g, b }
+    }
+}
+
+macro_rules! spectrum_op_impl {
+    ($impl_op: ident, $op: tt, $impl_op_func: ident) => {
+        impl ops::$impl_op<Spectrum> for Spectrum {
+            type Output = Spectrum;
+
+            fn $impl_op_func(self, rhs: Self) -> Self::Output {
+                Spectrum { r: self.r $op rhs.r, g: self.g $op rhs.g, b: self.b $op rhs.b }
+            }
+        }
+        impl ops::$impl_op<f64> for Spectrum {
+            type Output = Spectrum;
+
+            fn $impl_op_func(self, rhs: f64) -> Self::Output {
+                Spectrum { r: self.r $op rhs, g: self.g $op rhs, b: self.b $op rhs }
+            }
+        }
+    };
+}
+
+macro_rules! spectrum_op_assign_impl {
+    ($impl_op: ident, $op: tt, $impl_op_func: ident) => {
+        impl ops::$impl_op for Spectrum {
+            fn $impl_op_func(&mut self, rhs: Self) {
+                self.r $op rhs.r;
+                self.g $op rhs.g;
+                self.b $op rhs.b;
+            }
+        }
+        impl ops::$impl_op<f64> for Spectrum {
+            fn $impl_op_func(&mut self, rhs: f64) {
+                self.r $op rhs;
+                self.g $op rhs;
+                self.b $op rhs;
+            }
+        }
+    };
+}
+
+spectrum_op_impl!(Add, +, add);
+spectrum_op_impl!(Sub, -, sub);
+spectrum_op_impl!(Mul, *, mul);
+spectrum_op_impl!(Div, /, div);
+spectrum_op_assign_impl!(AddAssign, +=, add_assign);
+spectrum_op_assign_impl!(MulAssign, *=, mul_assign);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unclamped_ops() {
+        let s = Spectrum::new(1.0, 0.5, 0.0) * 4.0 + Spectrum::splat(1.0);
+        assert_eq!(s, Spectrum::new(5.0, 3.0, 1.0));
+        assert_eq!(s.max_component(), 5.0);
+        assert_eq!(s.to_color(), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_from_color() {
+        let s: Spectrum = Color::new(0.25, 0.5, 1.0).into();
+        assert_eq!(s * Spectrum::new(2.0, 2.0, 0.5), Spectrum::new(0.5, 1.0, 0.5));
+        assert!(Spectrum::BLACK.is_black());
+    }
+}
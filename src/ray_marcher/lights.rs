@@ -0,0 +1,345 @@
+use std::f64::consts::PI;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::bsdf::{to_world, Bsdf};
+use super::color_data_types::Color;
+use super::color_data_types::spectrum::Spectrum;
+use super::scene::Scene;
+use super::scene_file::description::{vec3, LightDescription};
+use super::scene_objects::SurfaceMaterial;
+use super::{Point3D, Vector3D};
+
+// Lights that aren't part of the scene geometry. Rays never hit them; instead every
+// surface a ray hits asks each light how much reaches it and marches a shadow ray to
+// check nothing is in the way.
+
+const SHADOW_OFFSET: f64 = 1e-3; // How far off the surface shadow rays start
+
+// How a light's shadow rays decide what reaches a point. The soft kinds darken points
+// whose shadow ray passes close to an object, giving a penumbra from a single ray;
+// larger `k` gives harder edges. `SoftImproved` also uses the previous distance to guess
+// where the ray came closest, which cuts down banding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Shadow {
+    #[default]
+    Hard,
+    Soft { k: f64 },
+    SoftImproved { k: f64 },
+}
+
+pub struct LightSample {
+    pub direction: Vector3D, // Unit direction from the lit point toward the light
+    pub distance: f64,       // How far the light is along `direction`, infinite for sunlight
+    pub light: Spectrum,     // Light arriving from that direction, divided by the sample's pdf
+}
+
+pub trait Light: Send + Sync {
+    fn sample(&self, p: &Point3D, rng: &mut dyn RngCore) -> Option<LightSample>; // None when no light reaches p
+    fn get_shadow(&self) -> Shadow{
+        Shadow::Hard
+    }
+    fn describe(&self) -> Option<LightDescription>{ // How the light is written to a scene file, None if it can't be
+        None
+    }
+}
+
+fn dot(a: &Vector3D, b: &Vector3D) -> f64 {
+    Vector3D::get_dot(a, b)
+}
+
+fn components(c: &Color) -> (f64, f64, f64) {
+    c.get_components()
+}
+
+// Light from every direction off a single point, fading with the square of the distance
+pub struct PointLight {
+    pub position: Point3D,
+    pub color: Color,
+    pub intensity: f64,
+    pub shadow: Shadow,
+}
+
+impl PointLight {
+    pub fn new(position: Point3D, color: Color, intensity: f64) -> Self {
+        PointLight { position, color, intensity, shadow: Shadow::Hard }
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3D, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_light = (self.position - *p).to_direction();
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let light = Spectrum::from(self.color) * (self.intensity / (distance * distance));
+        Some(LightSample { direction: to_light / distance, distance, light })
+    }
+
+    fn get_shadow(&self) -> Shadow {
+        self.shadow
+    }
+
+    fn describe(&self) -> Option<LightDescription> {
+        Some(LightDescription::Point { position: vec3(&self.position), color: components(&self.color), intensity: self.intensity, shadow: self.shadow })
+    }
+}
+
+// Parallel light from very far away, like the sun. `direction` is the way the light
+// travels.
+pub struct DirectionalLight {
+    pub direction: Vector3D,
+    pub color: Color,
+    pub intensity: f64,
+    pub shadow: Shadow,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vector3D, color: Color, intensity: f64) -> Self {
+        DirectionalLight { direction: direction.get_norm(), color, intensity, shadow: Shadow::Hard }
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _p: &Point3D, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        let light = Spectrum::from(self.color) * self.intensity;
+        Some(LightSample { direction: self.direction * -1.0, distance: f64::INFINITY, light })
+    }
+
+    fn get_shadow(&self) -> Shadow {
+        self.shadow
+    }
+
+    fn describe(&self) -> Option<LightDescription> {
+        let d = self.direction;
+        Some(LightDescription::Directional { direction: (d.x, d.y, d.z), color: components(&self.color), intensity: self.intensity, shadow: self.shadow })
+    }
+}
+
+// A point light shining down `direction`. Full strength inside `inner_angle` of the
+// axis, fading smoothly to nothing at `outer_angle` (both in radians).
+pub struct SpotLight {
+    pub position: Point3D,
+    pub direction: Vector3D,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+    pub color: Color,
+    pub intensity: f64,
+    pub shadow: Shadow,
+}
+
+impl SpotLight {
+    pub fn new(position: Point3D, direction: Vector3D, inner_angle: f64, outer_angle: f64, color: Color, intensity: f64) -> Self {
+        SpotLight { position, direction: direction.get_norm(), inner_angle, outer_angle, color, intensity, shadow: Shadow::Hard }
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = shadow;
+        self
+    }
+
+    // 1 inside the inner cone, 0 outside the outer one
+    pub fn falloff(&self, cos_angle: f64) -> f64 {
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        if cos_inner <= cos_outer {
+            return if cos_angle >= cos_outer { 1.0 } else { 0.0 };
+        }
+        let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3D, _rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_light = (self.position - *p).to_direction();
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let falloff = self.falloff(-dot(&direction, &self.direction));
+        if falloff <= 0.0 {
+            return None;
+        }
+        let light = Spectrum::from(self.color) * (self.intensity * falloff / (distance * distance));
+        Some(LightSample { direction, distance, light })
+    }
+
+    fn get_shadow(&self) -> Shadow {
+        self.shadow
+    }
+
+    fn describe(&self) -> Option<LightDescription> {
+        let d = self.direction;
+        Some(LightDescription::Spot {
+            position: vec3(&self.position),
+            direction: (d.x, d.y, d.z),
+            inner_angle: self.inner_angle.to_degrees(),
+            outer_angle: self.outer_angle.to_degrees(),
+            color: components(&self.color),
+            intensity: self.intensity,
+            shadow: self.shadow,
+        })
+    }
+}
+
+// A glowing ball. `intensity` is how bright its surface looks, so a bigger or nearer
+// ball gives more light. Its size softens the edges of the shadows it casts.
+pub struct SphereLight {
+    pub center: Point3D,
+    pub radius: f64,
+    pub color: Color,
+    pub intensity: f64,
+    pub shadow: Shadow,
+}
+
+impl SphereLight {
+    pub fn new(center: Point3D, radius: f64, color: Color, intensity: f64) -> Self {
+        SphereLight { center, radius, color, intensity, shadow: Shadow::Hard }
+    }
+
+    pub fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = shadow;
+        self
+    }
+}
+
+impl Light for SphereLight {
+    // Picks a direction evenly over the cone the ball covers as seen from p
+    fn sample(&self, p: &Point3D, rng: &mut dyn RngCore) -> Option<LightSample> {
+        let to_center = (self.center - *p).to_direction();
+        let d = to_center.length();
+        if d <= self.radius {
+            return None;
+        }
+        let axis = to_center / d;
+        let sin_max = self.radius / d;
+        let cos_max = (1.0 - sin_max * sin_max).max(0.0).sqrt();
+        let (u, v): (f64, f64) = (rng.gen(), rng.gen());
+        let cos = 1.0 - u * (1.0 - cos_max);
+        let sin = (1.0 - cos * cos).max(0.0).sqrt();
+        let phi = 2.0 * PI * v;
+        let direction = to_world(sin * phi.cos(), sin * phi.sin(), cos, &axis);
+        // The near side of the ball along the direction
+        let distance = d * cos - (self.radius * self.radius - d * d * sin * sin).max(0.0).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_max);
+        let light = Spectrum::from(self.color) * (self.intensity * solid_angle);
+        Some(LightSample { direction, distance, light })
+    }
+
+    fn get_shadow(&self) -> Shadow {
+        self.shadow
+    }
+
+    fn describe(&self) -> Option<LightDescription> {
+        Some(LightDescription::Sphere { center: vec3(&self.center), radius: self.radius, color: components(&self.color), intensity: self.intensity, shadow: self.shadow })
+    }
+}
+
+// Light reaching p straight from the scene's lights and reflected back along wo. Every
+// object blocks the light, clear ones included.
+pub fn direct_light(scene: &Scene, p: &Point3D, normal: &Vector3D, wo: &Vector3D, material: &SurfaceMaterial, rng: &mut dyn RngCore) -> Spectrum {
+    let n = normal.get_norm();
+    let mut total = Spectrum::BLACK;
+    if scene.get_lights().is_empty() || dot(wo, &n) <= 0.0 {
+        return total;
+    }
+    let bsdf = material.bsdf();
+    let from = *p + n.to_point() * SHADOW_OFFSET;
+    for light in scene.get_lights() {
+        let s = match light.sample(&from, rng) {
+            Some(s) => s,
+            None => continue,
+        };
+        let cos = dot(&s.direction, &n);
+        if cos <= 0.0 {
+            continue;
+        }
+        let f = bsdf.eval(wo, &s.direction, &n);
+        if f.is_black() || s.light.is_black() {
+            continue;
+        }
+        let visibility = scene.visibility(&from, &s.direction, s.distance, light.get_shadow());
+        if visibility <= 0.0 {
+            continue;
+        }
+        total += f * s.light * (cos * visibility);
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+    use super::super::scene_objects::objects::Sphere;
+
+    #[test]
+    fn test_point_light_falls_off() {
+        let light = PointLight::new(Point3D::new(0.0, 0.0, 4.0), Color::new(1.0, 0.5, 0.0), 32.0);
+        let s = light.sample(&Point3D::new(0.0, 0.0, 0.0), &mut rand::thread_rng()).unwrap();
+        assert_eq!(s.direction, Vector3D::new(0.0, 0.0, 1.0));
+        assert_eq!(s.distance, 4.0);
+        assert_eq!(s.light, Spectrum::new(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_spot_light_cone() {
+        let light = SpotLight::new(Point3D::new(0.0, 0.0, 1.0), Vector3D::new(0.0, 0.0, -1.0), 20_f64.to_radians(), 40_f64.to_radians(), Color::new(1.0, 1.0, 1.0), 1.0);
+        let mut rng = rand::thread_rng();
+        assert_eq!(light.sample(&Point3D::new(0.0, 0.0, 0.0), &mut rng).unwrap().light, Spectrum::WHITE);
+        assert!(light.sample(&Point3D::new(1.0, 0.0, 0.0), &mut rng).is_none()); // 45 degrees off the axis
+        let edge = light.sample(&Point3D::new(30_f64.to_radians().tan(), 0.0, 0.0), &mut rng).unwrap();
+        assert!(edge.light.r > 0.0 && edge.light.r < 1.0);
+        let halfway = (20_f64.to_radians().cos() + 40_f64.to_radians().cos()) / 2.0;
+        assert_close(light.falloff(halfway), 0.5);
+    }
+
+    #[test]
+    fn test_sphere_light_matches_point_light_far_away() {
+        // A small ball looks like a point light with intensity L * pi * r^2
+        let ball = SphereLight::new(Point3D::new(0.0, 0.0, 100.0), 0.5, Color::new(1.0, 1.0, 1.0), 4.0);
+        let point = PointLight::new(Point3D::new(0.0, 0.0, 100.0), Color::new(1.0, 1.0, 1.0), 4.0 * PI * 0.25);
+        let mut rng = rand::thread_rng();
+        let p = Point3D::new(0.0, 0.0, 0.0);
+        let s = ball.sample(&p, &mut rng).unwrap();
+        assert!(s.direction.z > 0.9999);
+        assert!(s.distance > 99.49 && s.distance < 100.0);
+        assert!((s.light.r / point.sample(&p, &mut rng).unwrap().light.r - 1.0).abs() < 1e-4);
+        assert!(ball.sample(&Point3D::new(0.0, 0.0, 100.2), &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_direct_light_and_shadows() {
+        let mut scene = Scene::new();
+        scene.add_light(DirectionalLight::new(Vector3D::new(0.0, 0.0, -1.0), Color::new(1.0, 1.0, 1.0), PI));
+        let matte = SurfaceMaterial::diffuse(Color::new(0.5, 0.5, 0.5));
+        let up = Vector3D::new(0.0, 0.0, 1.0);
+        let mut rng = rand::thread_rng();
+        // f * cos * pi, with the light straight above
+        let expected = matte.bsdf().eval(&up, &up, &up).r * PI;
+        let lit = direct_light(&scene, &Point3D::new(0.0, 0.0, 0.0), &up, &up, &matte, &mut rng);
+        assert_close(lit.r, expected);
+        scene.add_scene_object(Sphere::new(Point3D::new(0.0, 0.0, 5.0), 1.0, None));
+        scene.build();
+        let shadowed = direct_light(&scene, &Point3D::new(0.0, 0.0, 0.0), &up, &up, &matte, &mut rng);
+        assert!(shadowed.is_black());
+        let beside = direct_light(&scene, &Point3D::new(3.0, 0.0, 0.0), &up, &up, &matte, &mut rng);
+        assert_close(beside.r, expected);
+        // Seen from behind the surface
+        let below = direct_light(&scene, &Point3D::new(3.0, 0.0, 0.0), &up, &(up * -1.0), &matte, &mut rng);
+        assert!(below.is_black());
+    }
+}
@@ -6,8 +6,10 @@ use rayon;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
 
 use super::color_data_types::Color;
-use super::scene::{Scene, ClosestObject};
-use super::scene_objects::{objects, SceneObject};
+use super::color_data_types::spectrum::Spectrum;
+use super::lights::{self, Light};
+use super::scene::{Scene, ClosestObject, NodeId};
+use super::scene_objects::SceneObject;
 use super::screen::{Screen, Displayable};
 use super::{ray, screen};
 use super::camera;
@@ -21,7 +23,8 @@ pub const MAX_DISTANCE: f64 = 1e7;
 #[allow(dead_code)]
 const EPSILON: f64 = 1e-7;
 #[allow(dead_code)]
-const MIN_ANGLE: f64 = (0.1_f64 * 180_f64) / PI;
+const MIN_ANGLE: f64 = 0.1_f64 * PI / 180_f64; // Jitter of restarted camera rays, 0.1 degrees
+pub const DEFAULT_SKY: Spectrum = Spectrum::WHITE; // Light reaching rays that leave the scene
 
 
 #[allow(dead_code)]
@@ -30,8 +33,9 @@ pub struct MarcherHandler{
     num_iterations: u32,
     max_distance: f64,
     rays: Vec<ray::Ray>,
-    scene: Scene<objects::Sphere>,
+    scene: Scene,
     camera: camera::Camera,
+    sky: Spectrum,
     pub debug: bool
 }
 
@@ -39,7 +43,7 @@ pub struct MarcherHandler{
 impl MarcherHandler {
 
     pub fn new(num_bounces: u32, max_distance: f64, num_iterations: u32, camera: camera::Camera) -> Self {
-        let mut ret = MarcherHandler { num_steps: num_bounces, rays: Vec::<ray::Ray>::new(), scene: Scene::new(), camera, debug: false, max_distance, num_iterations };
+        let mut ret = MarcherHandler { num_steps: num_bounces, rays: Vec::<ray::Ray>::new(), scene: Scene::new(), camera, sky: DEFAULT_SKY, debug: false, max_distance, num_iterations };
         ret.generate_rays();
         return ret;
     }
@@ -48,8 +52,38 @@ impl MarcherHandler {
         &self.camera
     }
 
-    pub fn add_scene_object(&mut self, o: objects::Sphere){
-        self.scene.add_scene_object(o);
+    pub fn add_scene_object<T>(&mut self, o: T) -> NodeId where T: SceneObject + 'static {
+        self.scene.add_scene_object(o)
+    }
+
+    pub fn add_boxed_scene_object(&mut self, o: Box<dyn SceneObject>) -> NodeId {
+        self.scene.add_boxed_scene_object(o)
+    }
+
+    pub fn add_light<T>(&mut self, l: T) where T: Light + 'static {
+        self.scene.add_light(l);
+    }
+
+    pub fn get_sky(&self) -> Spectrum {
+        self.sky
+    }
+
+    // Black for interiors lit only by their own lamps
+    pub fn set_sky(&mut self, sky: Spectrum) {
+        self.sky = sky;
+    }
+
+    pub fn get_scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn set_scene(&mut self, scene: Scene) {
+        self.scene = scene;
+    }
+
+    // Gives access to the scene graph for naming, moving and removing nodes
+    pub fn get_scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
     }
 
     fn add_ray(&mut self, r: ray::Ray){
@@ -69,29 +103,44 @@ impl MarcherHandler {
         let num_bounce_const = self.num_steps;
         let num_iters = self.num_iterations;
         let mut screen: screen::Screen<Color> = screen::Screen::new(self.camera.resolution);
+        self.scene.build();
         loop{
             self.rays.par_iter_mut().for_each(|ray| {
                 if ray.has_stopped() {
                     // println!("stopped - {}", i);
                     return;
                 }
-                let closest_obj = self.scene.get_closest_object(ray.get_position());
-                if let Some(ClosestObject { distance, obj }) = closest_obj{
+                // Inside a clear object the distance to its own surface is flipped
+                let closest_obj = match ray.get_medium() {
+                    Some(medium) => self.scene.get_closest_object_inside(ray.get_position(), medium.node),
+                    None => self.scene.get_closest_object(ray.get_position()),
+                };
+                if let Some(ClosestObject { distance, step_distance, obj, node }) = closest_obj{
                     // println!("{} - {}", distance, i);
-                    ray.step(distance);
-                    if distance >= MAX_DISTANCE || ray.get_num_hits() > MAX_HITS{
+                    ray.step(step_distance);
+                    if distance >= MAX_DISTANCE{
+                        ray.escape(self.sky);
+                        return;
+                    }
+                    if ray.get_num_hits() > MAX_HITS{
                         ray.stop();
                         return;
                     }
                     if distance < MIN_HIT_DIST{
                         if self.debug {
                             let n = obj.get_surface_normal(&ray.get_position(), EPSILON).get_norm().to_point();
-                            ray.color = Color::new(n.x, n.y, n.z);
+                            ray.radiance = Spectrum::new(n.x, n.y, n.z);
                             ray.stop();
-                        }else{
-                            ray.color = Color::blend_colors(&obj.get_surface_material().color, &ray.color, 0.5);
+                            return;
+                        }
+                        let normal = obj.get_surface_normal(ray.get_position(), EPSILON);
+                        let material = obj.get_surface_material_at(ray.get_position());
+                        if !material.is_emissive() {
+                            let wo = ray.get_direction().get_norm() * -1.0;
+                            let direct = lights::direct_light(&self.scene, ray.get_position(), &normal, &wo, &material, &mut rand::thread_rng());
+                            ray.add_light(direct);
                         }
-                        ray.scatter(&obj.get_surface_normal(ray.get_position(), EPSILON), &obj.get_surface_material(), 120_f64.to_radians(), 0.001);
+                        ray.scatter(&normal, &material, 0.001, node);
                     }
                 }
             });
@@ -100,11 +149,12 @@ impl MarcherHandler {
                 println!("{}%", 100_f64 - (self.num_iterations as f64 * 100_f64 / num_iters as f64));
                 self.num_iterations -= 1;
                 self.num_steps = num_bounce_const;
-                self.copy_colors(&mut screen);
-                self.reset_rays();
+                self.copy_colors(&mut screen, num_iters - self.num_iterations);
+                // The last paths are kept so their colors can still be read
                 if self.num_iterations <= 0 {
                     break;
                 }
+                self.reset_rays();
             }
         }
         screen
@@ -112,13 +162,15 @@ impl MarcherHandler {
 
     pub fn get_color(&self, x: u32, y: u32) -> Color {
         let (rows, _cols) = self.camera.get_resolution();
-        let index = (x * rows) + y;
+        let index = x + (rows * y);
         self.rays.get(index as usize).unwrap().get_color()
     }
 
-    fn index_to_res_coords(cam_row: u32, cam_col: u32, i: usize) -> (u32, u32){
-        let c = i as u32 % cam_col;
-        let r = i as u32 / cam_row;
+    // Inverse of the order `generate_rays` lays the rays out in, so restarted paths
+    // start from the same pixel
+    fn index_to_res_coords(cam_row: u32, _cam_col: u32, i: usize) -> (u32, u32){
+        let r = i as u32 % cam_row;
+        let c = i as u32 / cam_row;
         (r, c)
     }
 
@@ -132,27 +184,29 @@ impl MarcherHandler {
             let rand_y = (rng.gen::<f64>() * 2.0) - 1.0;
             let rand_z = (rng.gen::<f64>() * 2.0) - 1.0;
             d.rotate_vector(rand_z * MIN_ANGLE, rand_y * MIN_ANGLE);
-            ray.set_position(p);
-            ray.set_direction(d);
+            ray.restart(p, d);
         }
     }
 
-    fn copy_colors(&self, screen: &mut Screen<Color>){
+    // Folds the rays' colors into the running mean of the `samples` iterations so far
+    fn copy_colors(&self, screen: &mut Screen<Color>, samples: u32){
         let (res_rows, res_cols) = self.camera.resolution;
         self.rays.iter().enumerate().for_each(|(i, ray)|{
-            let row = i as u32 / res_rows;
-            let col = i as u32 % res_cols;
-            let (r, g, b) = screen.get_color_components((row, col));
-            let new_color = (Color::new(r, g, b) + ray.get_color()) / 2_f64;
-            screen.set_red_channel((row, col), new_color.r());
-            screen.set_green_channel((row, col), new_color.g());
-            screen.set_blue_channel((row, col), new_color.b());
+            // The same pixel the ray was aimed through in reset_rays
+            let pixel = Self::index_to_res_coords(res_rows, res_cols, i);
+            let (r, g, b) = screen.get_color_components(pixel);
+            let (new_r, new_g, new_b) = ray.get_color().get_components();
+            let n = samples as f64;
+            screen.set_red_channel(pixel, r + (new_r - r) / n);
+            screen.set_green_channel(pixel, g + (new_g - g) / n);
+            screen.set_blue_channel(pixel, b + (new_b - b) / n);
         });
     }
 }
 
 #[cfg(test)]
 mod test{
+    use crate::ray_marcher::lights::PointLight;
     use crate::ray_marcher::scene_objects::{objects::*, SurfaceMaterial};
 
     use super::*;
@@ -170,12 +224,28 @@ mod test{
         assert_eq!(c, 0);
     }
 
+    #[test]
+    fn test_index_to_coords_on_wide_camera(){
+        let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 1.0, 10.0_f64.to_radians(), (3,2));
+        let marcher = MarcherHandler::new(1, MAX_DISTANCE, 1, camera);
+        let mut screen: Screen<Color> = Screen::new((3, 2));
+        for (i, ray) in marcher.rays.iter().enumerate() {
+            let (r, c) = MarcherHandler::index_to_res_coords(3, 2, i);
+            assert_eq!(*ray.get_direction(), marcher.camera.get_near_plane_point(r, c).1);
+            screen.set_red_channel((r, c), i as f64);
+        }
+        // Every ray has a pixel of its own
+        for i in 0..marcher.rays.len() {
+            assert_eq!(screen.get_red_channel(MarcherHandler::index_to_res_coords(3, 2, i)), i as f64);
+        }
+    }
+
     #[test]
     // #[ignore = "Could be computationally expensive"]
     fn test_march_one_pixel(){
         let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 0.1, 0.0, (1,1));
         let mut marcher = MarcherHandler::new(100, MAX_DISTANCE, 1, camera);
-        let sphere = Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial{ color: Color::new(1.0, 0.0, 0.0), reflectivity: 1.0 }));
+        let sphere = Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial::mirror(Color::new(1.0, 0.0, 0.0))));
         marcher.add_scene_object(sphere);
         marcher.march();
         let(r, _g, _b) = marcher.get_color(0, 0).get_u8_components();
@@ -186,10 +256,40 @@ mod test{
     fn test_march_two_pixels(){
         let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 0.1, 1.0_f64.to_radians(), (2,1));
         let mut marcher = MarcherHandler::new(100, MAX_DISTANCE, 1, camera);
-        let sphere = Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial{ color: Color::new(1.0, 0.0, 0.0), reflectivity: 1.0 }));
+        let sphere = Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial::mirror(Color::new(1.0, 0.0, 0.0))));
         marcher.add_scene_object(sphere);
         marcher.march();
         let(r, _g, _b) = marcher.get_color(0, 0).get_u8_components();
         assert_eq!(r, 255 as u8);
     }
+
+    #[test]
+    fn test_lamp_lights_dark_scene(){
+        let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 1.0, 1.0_f64.to_radians(), (2,1));
+        let mut marcher = MarcherHandler::new(100, MAX_DISTANCE, 1, camera);
+        marcher.set_sky(Spectrum::BLACK);
+        marcher.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial::light(Spectrum::new(0.0, 3.0, 0.0)))));
+        marcher.march();
+        assert_eq!(marcher.get_color(0, 0), Color::new(0.0, 1.0, 0.0));
+
+        let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 1.0, 1.0_f64.to_radians(), (2,1));
+        let mut marcher = MarcherHandler::new(100, MAX_DISTANCE, 1, camera);
+        marcher.set_sky(Spectrum::BLACK);
+        marcher.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial::diffuse(Color::new(1.0, 1.0, 1.0)))));
+        marcher.march();
+        assert_eq!(marcher.get_color(0, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_light_source_lights_dark_scene(){
+        let camera = camera::Camera::new(Const_3D::ORIGIN, Const_3D::X_DIR, 1.0, 1.0_f64.to_radians(), (2,1));
+        let mut marcher = MarcherHandler::new(100, MAX_DISTANCE, 1, camera);
+        marcher.set_sky(Spectrum::BLACK);
+        marcher.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, Some(SurfaceMaterial::diffuse(Color::new(1.0, 1.0, 1.0)))));
+        marcher.add_light(PointLight::new(Point3D::new(0.0, 0.0, 0.0), Color::new(1.0, 0.0, 0.0), 1000.0));
+        marcher.march();
+        let (r, g, b) = marcher.get_color(0, 0).get_components();
+        assert!(r > 0.5);
+        assert_eq!((g, b), (0.0, 0.0));
+    }
 }
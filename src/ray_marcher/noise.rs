@@ -0,0 +1,457 @@
+use super::Point3D;
+
+// Deterministic 3D noise. Every generator takes an explicit seed, the same seed always
+// gives the same values on every machine. Values stay between -1 and 1, and each
+// generator reports a bound on its slope so callers that feed it into distances
+// (displacement, terrain) can keep their estimates conservative.
+
+pub trait Noise3D: Send + Sync {
+    fn sample(&self, p: &Point3D) -> f64; // Between -1 and 1
+    fn lipschitz(&self) -> f64; // Upper bound on how fast `sample` changes per unit of distance
+}
+
+impl Noise3D for Box<dyn Noise3D> {
+    fn sample(&self, p: &Point3D) -> f64 {
+        self.as_ref().sample(p)
+    }
+
+    fn lipschitz(&self) -> f64 {
+        self.as_ref().lipschitz()
+    }
+}
+
+// Small seedable generator used to build permutation tables and feature points
+#[derive(Clone, Debug)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64 { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_f64(&mut self) -> f64 { // Between 0 and 1, excluding 1
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+// Shuffled 0..256, repeated twice so lookups can add offsets without wrapping
+fn permutation_table(seed: u64) -> [u8; 512] {
+    let mut rng = SplitMix64::new(seed);
+    let mut perm = [0_u8; 256];
+    for (i, p) in perm.iter_mut().enumerate() {
+        *p = i as u8;
+    }
+    for i in (1..256).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        perm.swap(i, j);
+    }
+    let mut table = [0_u8; 512];
+    for i in 0..512 {
+        table[i] = perm[i & 255];
+    }
+    table
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+// Ken Perlin's improved noise
+#[derive(Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+#[allow(dead_code)]
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        Perlin { perm: permutation_table(seed) }
+    }
+
+    fn grad(hash: u8, x: f64, y: f64, z: f64) -> f64 {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 {
+            y
+        } else if h == 12 || h == 14 {
+            x
+        } else {
+            z
+        };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    }
+}
+
+impl Noise3D for Perlin {
+    fn sample(&self, p: &Point3D) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let xi = (fx as i64 & 255) as usize;
+        let yi = (fy as i64 & 255) as usize;
+        let zi = (fz as i64 & 255) as usize;
+        let (x, y, z) = (p.x - fx, p.y - fy, p.z - fz);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let perm = &self.perm;
+        let a = perm[xi] as usize + yi;
+        let aa = perm[a] as usize + zi;
+        let ab = perm[a + 1] as usize + zi;
+        let b = perm[xi + 1] as usize + yi;
+        let ba = perm[b] as usize + zi;
+        let bb = perm[b + 1] as usize + zi;
+
+        let g = |i: usize, dx: f64, dy: f64, dz: f64| Perlin::grad(perm[i], x - dx, y - dy, z - dz);
+        lerp(
+            w,
+            lerp(v, lerp(u, g(aa, 0.0, 0.0, 0.0), g(ba, 1.0, 0.0, 0.0)), lerp(u, g(ab, 0.0, 1.0, 0.0), g(bb, 1.0, 1.0, 0.0))),
+            lerp(v, lerp(u, g(aa + 1, 0.0, 0.0, 1.0), g(ba + 1, 1.0, 0.0, 1.0)), lerp(u, g(ab + 1, 0.0, 1.0, 1.0), g(bb + 1, 1.0, 1.0, 1.0))),
+        )
+        .clamp(-1.0, 1.0)
+    }
+
+    fn lipschitz(&self) -> f64 {
+        PERLIN_LIPSCHITZ
+    }
+}
+
+// Stefan Gustavson's 3D simplex noise
+#[derive(Clone)]
+pub struct Simplex {
+    perm: [u8; 512],
+}
+
+const SIMPLEX_GRADIENTS: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+#[allow(dead_code)]
+impl Simplex {
+    pub fn new(seed: u64) -> Self {
+        Simplex { perm: permutation_table(seed) }
+    }
+}
+
+impl Noise3D for Simplex {
+    fn sample(&self, p: &Point3D) -> f64 {
+        const F3: f64 = 1.0 / 3.0;
+        const G3: f64 = 1.0 / 6.0;
+
+        // Skew into the simplex grid to find the cell, then unskew back
+        let s = (p.x + p.y + p.z) * F3;
+        let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+        let t = (i + j + k) * G3;
+        let x0 = Point3D::new(p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+        // Which of the six tetrahedra in the cell the point is in
+        let (o1, o2) = if x0.x >= x0.y {
+            if x0.y >= x0.z {
+                ([1, 0, 0], [1, 1, 0])
+            } else if x0.x >= x0.z {
+                ([1, 0, 0], [1, 0, 1])
+            } else {
+                ([0, 0, 1], [1, 0, 1])
+            }
+        } else if x0.y < x0.z {
+            ([0, 0, 1], [0, 1, 1])
+        } else if x0.x < x0.z {
+            ([0, 1, 0], [0, 1, 1])
+        } else {
+            ([0, 1, 0], [1, 1, 0])
+        };
+
+        let offset = |o: [usize; 3], g: f64| Point3D::new(x0.x - o[0] as f64 + g, x0.y - o[1] as f64 + g, x0.z - o[2] as f64 + g);
+        let corners = [
+            ([0, 0, 0], x0),
+            (o1, offset(o1, G3)),
+            (o2, offset(o2, 2.0 * G3)),
+            ([1, 1, 1], offset([1, 1, 1], 3.0 * G3)),
+        ];
+
+        let (ii, jj, kk) = ((i as i64 & 255) as usize, (j as i64 & 255) as usize, (k as i64 & 255) as usize);
+        let perm = &self.perm;
+        let total: f64 = corners
+            .iter()
+            .map(|(o, d)| {
+                let t = 0.5 - d.dot(d);
+                if t < 0.0 {
+                    return 0.0;
+                }
+                let hash = perm[ii + o[0] + perm[jj + o[1] + perm[kk + o[2]] as usize] as usize] as usize % 12;
+                let g = SIMPLEX_GRADIENTS[hash];
+                let t2 = t * t;
+                t2 * t2 * (g[0] * d.x + g[1] * d.y + g[2] * d.z)
+            })
+            .sum();
+        (SIMPLEX_SCALE * total).clamp(-1.0, 1.0)
+    }
+
+    fn lipschitz(&self) -> f64 {
+        SIMPLEX_LIPSCHITZ
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorleyFeature {
+    F1,        // Distance to the closest feature point
+    F2,        // Distance to the second closest
+    F2MinusF1, // Zero along the borders between cells
+}
+
+// Cellular noise with one jittered feature point per unit cell. Distances are mapped
+// from 0..1 onto -1..1, so closest to a feature point is -1.
+#[derive(Clone)]
+pub struct Worley {
+    seed: u64,
+    feature: WorleyFeature,
+}
+
+#[allow(dead_code)]
+impl Worley {
+    pub fn new(seed: u64, feature: WorleyFeature) -> Self {
+        Worley { seed, feature }
+    }
+
+    fn feature_point(&self, x: i64, y: i64, z: i64) -> Point3D {
+        let hash = self.seed
+            ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+        let mut rng = SplitMix64::new(hash);
+        Point3D::new(x as f64 + rng.next_f64(), y as f64 + rng.next_f64(), z as f64 + rng.next_f64())
+    }
+
+    // The two closest feature point distances
+    pub fn distances(&self, p: &Point3D) -> (f64, f64) {
+        let (cx, cy, cz) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+        let (mut f1, mut f2) = (f64::INFINITY, f64::INFINITY);
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let d = p.distance_to(&self.feature_point(cx + dx, cy + dy, cz + dz));
+                    if d < f1 {
+                        f2 = f1;
+                        f1 = d;
+                    } else if d < f2 {
+                        f2 = d;
+                    }
+                }
+            }
+        }
+        (f1, f2)
+    }
+}
+
+impl Noise3D for Worley {
+    fn sample(&self, p: &Point3D) -> f64 {
+        let (f1, f2) = self.distances(p);
+        let value = match self.feature {
+            WorleyFeature::F1 => f1,
+            WorleyFeature::F2 => f2,
+            WorleyFeature::F2MinusF1 => f2 - f1,
+        };
+        (2.0 * value - 1.0).clamp(-1.0, 1.0)
+    }
+
+    fn lipschitz(&self) -> f64 {
+        // Distances change at most 1 per unit, doubled by the mapping onto -1..1
+        match self.feature {
+            WorleyFeature::F2MinusF1 => 4.0,
+            _ => 2.0,
+        }
+    }
+}
+
+// Slopes found by sampling millions of gradients (3.21 and 6.9), with some headroom
+const PERLIN_LIPSCHITZ: f64 = 4.0;
+const SIMPLEX_LIPSCHITZ: f64 = 8.0;
+// Brings the simplex sum to about -1..1. The kernel radius is 0.5 rather than the
+// often quoted 0.6, which leaks past the simplex and leaves seams.
+const SIMPLEX_SCALE: f64 = 76.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FractalKind {
+    Fbm,        // Plain sum of octaves
+    Ridged,     // Sharp crests where the noise crosses zero
+    Turbulence, // Sum of absolute values, billowy
+}
+
+// Sums octaves of a noise at increasing frequencies and decreasing amplitudes
+#[derive(Clone)]
+pub struct FractalNoise<N> where N: Noise3D {
+    noise: N,
+    kind: FractalKind,
+    octaves: u32,
+    frequency: f64, // Of the first octave
+    lacunarity: f64, // Frequency multiplier between octaves
+    gain: f64, // Amplitude multiplier between octaves
+}
+
+#[allow(dead_code)]
+impl<N> FractalNoise<N> where N: Noise3D {
+    pub fn new(noise: N, kind: FractalKind, octaves: u32) -> Self {
+        FractalNoise { noise, kind, octaves: octaves.max(1), frequency: 1.0, lacunarity: 2.0, gain: 0.5 }
+    }
+
+    pub fn with_frequency(mut self, frequency: f64) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    pub fn with_lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    pub fn with_gain(mut self, gain: f64) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    // Frequency and amplitude of each octave, amplitudes summing to 1
+    fn octave_weights(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let total: f64 = (0..self.octaves).map(|i| self.gain.powi(i as i32)).sum();
+        (0..self.octaves).map(move |i| (self.frequency * self.lacunarity.powi(i as i32), self.gain.powi(i as i32) / total))
+    }
+}
+
+impl<N> Noise3D for FractalNoise<N> where N: Noise3D {
+    fn sample(&self, p: &Point3D) -> f64 {
+        let mut total = 0.0;
+        for (i, (frequency, amplitude)) in self.octave_weights().enumerate() {
+            // Shift every octave so their lattices don't line up at the origin
+            let shift = i as f64 * 31.416;
+            let n = self.noise.sample(&(*p * frequency + shift));
+            total += amplitude * match self.kind {
+                FractalKind::Fbm => n,
+                FractalKind::Ridged => (1.0 - n.abs()) * (1.0 - n.abs()),
+                FractalKind::Turbulence => n.abs(),
+            };
+        }
+        match self.kind {
+            FractalKind::Fbm => total,
+            _ => 2.0 * total - 1.0,
+        }
+    }
+
+    fn lipschitz(&self) -> f64 {
+        let octaves: f64 = self.octave_weights().map(|(frequency, amplitude)| frequency * amplitude).sum();
+        let kind = match self.kind {
+            FractalKind::Fbm => 1.0,
+            FractalKind::Turbulence => 2.0,
+            FractalKind::Ridged => 4.0, // (1 - |n|)^2 is twice as steep as n, then doubled onto -1..1
+        };
+        self.noise.lipschitz() * octaves * kind
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_points() -> Vec<Point3D> {
+        let mut rng = SplitMix64::new(7);
+        (0..4000)
+            .map(|_| Point3D::new(rng.next_f64() * 40.0 - 20.0, rng.next_f64() * 40.0 - 20.0, rng.next_f64() * 40.0 - 20.0))
+            .collect()
+    }
+
+    // Finite differences along random directions never beat the reported bound
+    fn assert_lipschitz(noise: &dyn Noise3D) {
+        let mut rng = SplitMix64::new(11);
+        let h = 1e-4;
+        for p in sample_points() {
+            let dir = Point3D::new(rng.next_f64() - 0.5, rng.next_f64() - 0.5, rng.next_f64() - 0.5);
+            let dir = dir / dir.length();
+            let slope = (noise.sample(&(p + dir * h)) - noise.sample(&p)).abs() / h;
+            assert!(slope <= noise.lipschitz(), "slope {} above {} at {:?}", slope, noise.lipschitz(), p);
+        }
+    }
+
+    fn assert_in_range(noise: &dyn Noise3D) {
+        for p in sample_points() {
+            let n = noise.sample(&p);
+            assert!((-1.0..=1.0).contains(&n), "{} at {:?}", n, p);
+        }
+    }
+
+    fn all_noises(seed: u64) -> Vec<Box<dyn Noise3D>> {
+        vec![
+            Box::new(Perlin::new(seed)),
+            Box::new(Simplex::new(seed)),
+            Box::new(Worley::new(seed, WorleyFeature::F1)),
+            Box::new(Worley::new(seed, WorleyFeature::F2MinusF1)),
+            Box::new(FractalNoise::new(Perlin::new(seed), FractalKind::Fbm, 5)),
+            Box::new(FractalNoise::new(Simplex::new(seed), FractalKind::Ridged, 4).with_frequency(0.3)),
+            Box::new(FractalNoise::new(Perlin::new(seed), FractalKind::Turbulence, 3).with_gain(0.6)),
+        ]
+    }
+
+    #[test]
+    fn test_noise_deterministic() {
+        let p = Point3D::new(1.3, -7.2, 0.4);
+        for (a, b) in all_noises(42).iter().zip(all_noises(42).iter()) {
+            assert_eq!(a.sample(&p), b.sample(&p));
+        }
+    }
+
+    #[test]
+    fn test_noise_seed_changes_values() {
+        let points = sample_points();
+        for (a, b) in all_noises(1).iter().zip(all_noises(2).iter()) {
+            assert!(points.iter().any(|p| a.sample(p) != b.sample(p)));
+        }
+    }
+
+    #[test]
+    fn test_noise_in_range() {
+        for noise in all_noises(3) {
+            assert_in_range(noise.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_noise_lipschitz_bounds() {
+        for noise in all_noises(5) {
+            assert_lipschitz(noise.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_gradient_noise_zero_on_lattice() {
+        let perlin = Perlin::new(9);
+        assert_eq!(perlin.sample(&Point3D::new(3.0, -2.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn test_noise_not_constant() {
+        for noise in all_noises(13) {
+            let values: Vec<f64> = sample_points().iter().map(|p| noise.sample(p)).collect();
+            let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            assert!(max - min > 0.5);
+        }
+    }
+
+    #[test]
+    fn test_worley_zero_at_feature_point() {
+        let worley = Worley::new(21, WorleyFeature::F1);
+        let feature = worley.feature_point(2, -1, 4);
+        assert_eq!(worley.distances(&feature).0, 0.0);
+        assert_eq!(worley.sample(&feature), -1.0);
+    }
+}
@@ -1,16 +1,36 @@
-use super::{color_data_types::Color, scene_objects::SurfaceMaterial, Point3D, Vector3D};
-use rand::{self, Rng};
+use super::bsdf::Bsdf;
+use super::scene::NodeId;
+use super::{color_data_types::Color, color_data_types::spectrum::Spectrum, scene_objects::SurfaceMaterial, Point3D, Vector3D};
+use rand;
 
 #[allow(dead_code)]
 const MIN_HIT_DIST: f64 = 0.001;
 
+// The inside of an object a ray is passing through. Media don't nest, so a ray that
+// enters another clear object from inside this one forgets it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    pub node: NodeId,
+    pub absorption: Spectrum,
+}
+
+impl Medium {
+    // Share of light that makes it through `distance` of the medium
+    pub fn transmittance(&self, distance: f64) -> Spectrum {
+        let a = self.absorption * -distance;
+        Spectrum::new(a.r.exp(), a.g.exp(), a.b.exp())
+    }
+}
+
 #[allow(dead_code)]
 pub struct Ray {
     position: Point3D,
     direction: Vector3D,
     num_hits: i32,
     must_stop: bool,
-    pub color: Color,
+    throughput: Spectrum, // The share of light arriving along the path that reaches the camera
+    pub radiance: Spectrum, // Light gathered so far
+    medium: Option<Medium>,
 }
 
 impl Ray {
@@ -19,13 +39,22 @@ impl Ray {
             position: pos,
             direction: dir,
             num_hits: 0,
-            color: Color::new(0.0, 0.0, 0.0),
             must_stop: false,
+            throughput: Spectrum::WHITE,
+            radiance: Spectrum::BLACK,
+            medium: None,
         }
     }
+    // Starts a new path, forgetting everything the last one gathered
+    pub fn restart(&mut self, pos: Point3D, dir: Vector3D) {
+        *self = Ray::new(pos, dir);
+    }
     pub fn step(&mut self, step_size: f64) {
         if !self.must_stop {
             self.position += self.direction.clone().to_point() * step_size;
+            if let Some(m) = &self.medium {
+                self.throughput *= m.transmittance(step_size.abs());
+            }
         }
     }
     pub fn get_position(&self) -> &Point3D {
@@ -43,31 +72,60 @@ impl Ray {
     pub fn get_num_hits(&self) -> i32 {
         self.num_hits
     }
+    pub fn get_throughput(&self) -> &Spectrum {
+        &self.throughput
+    }
+    pub fn get_medium(&self) -> Option<&Medium> {
+        self.medium.as_ref()
+    }
     pub fn reflect(&mut self, surf_normal: &Vector3D, _back_off_dist: f64) {
         let normal = surf_normal.get_norm();
         self.direction -= normal * 2.0 * Vector3D::get_dot(&self.direction, &normal);
         // self.position += surf_normal.to_point() * back_off_dist;
         self.num_hits += 1;
     }
+    // Picks the next direction from the material's BSDF and weights the path by how much
+    // light the surface sends that way. Paths the surface absorbs stop, and so do paths
+    // reaching a light, which is taken to outshine anything it reflects. `surf_normal`
+    // points out of the object held by `node`, which the ray may enter or leave.
     pub fn scatter(
         &mut self,
         surf_normal: &Vector3D,
         surf_material: &SurfaceMaterial,
-        max_angle_change: f64,
         back_off_dist: f64,
+        node: NodeId,
     ) {
         let mut rng = rand::thread_rng();
-        let rand_z_rot: f64 = (rng.gen::<f64>() * 2.0) - 1.0;
-        let rand_y_rot: f64 = (rng.gen::<f64>() * 2.0) - 1.0;
-
-        let mut normal = surf_normal.clone();
-        self.position += surf_normal.to_point() * back_off_dist;
-        normal.rotate_vector(
-            rand_z_rot * (1.0 - surf_material.reflectivity) * max_angle_change,
-            rand_y_rot * (1.0 - surf_material.reflectivity) * max_angle_change,
-        );
-
-        self.reflect(&normal, back_off_dist)
+        let normal = surf_normal.get_norm();
+        let wo = self.direction.get_norm() * -1.0;
+        self.num_hits += 1;
+        if surf_material.is_emissive() {
+            self.radiance += self.throughput * surf_material.emission;
+            return self.stop();
+        }
+        let s = match surf_material.bsdf().sample(&wo, &normal, &mut rng) {
+            Some(s) => s,
+            None => return self.stop(),
+        };
+        self.throughput *= s.weight;
+        self.direction = s.direction;
+        let from_outside = Vector3D::get_dot(&wo, &normal) >= 0.0;
+        let to_outside = Vector3D::get_dot(&s.direction, &normal) >= 0.0;
+        if from_outside != to_outside {
+            self.medium = if to_outside { None } else { Some(Medium { node, absorption: surf_material.absorption }) };
+        }
+        // Backed off on the side the ray carries on from
+        let side = if to_outside { 1.0 } else { -1.0 };
+        self.position += normal.to_point() * (back_off_dist * side);
+    }
+    // Light reaching the path's current point, such as from a light source
+    pub fn add_light(&mut self, light: Spectrum) {
+        self.radiance += self.throughput * light;
+    }
+    // The path left the scene, lit by the sky along its direction
+    pub fn escape(&mut self, sky: Spectrum) {
+        self.radiance += self.throughput * sky;
+        self.stop();
     }
     pub fn stop(&mut self) {
         self.must_stop = true;
@@ -76,15 +134,13 @@ impl Ray {
         self.must_stop
     }
     pub fn get_color(&self) -> Color {
-        if self.num_hits > 0 {
-            return self.color.clone();
-        }
-        Color::new(1.0, 1.0, 1.0)
+        self.radiance.to_color()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::scene::Scene;
     use super::super::Const_3D;
     use super::*;
 
@@ -118,4 +174,44 @@ mod tests {
             Vector3D::new(1.0, -1.0, 0.0).get_norm()
         );
     }
+
+    #[test]
+    fn test_scatter_off_mirror() {
+        let mut incoming = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        let mirror = SurfaceMaterial::mirror(Color::new(1.0, 0.5, 0.0));
+        incoming.scatter(&Vector3D::new(-1.0, 0.0, 0.0), &mirror, 0.01, Scene::new().root());
+        assert_eq!(*incoming.get_direction(), Vector3D::new(-1.0, 0.0, 0.0));
+        assert_eq!(*incoming.get_position(), Point3D::new(-0.01, 0.0, 0.0));
+        incoming.escape(Spectrum::WHITE);
+        assert!(incoming.has_stopped());
+        assert_eq!(incoming.get_color(), Color::new(1.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn test_emission_ends_the_path() {
+        let mut incoming = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        let lamp = SurfaceMaterial::light(Spectrum::new(4.0, 0.5, 0.0));
+        incoming.scatter(&Vector3D::new(-1.0, 0.0, 0.0), &lamp, 0.01, Scene::new().root());
+        assert_eq!(incoming.radiance, Spectrum::new(4.0, 0.5, 0.0));
+        assert_eq!(incoming.get_color(), Color::new(1.0, 0.5, 0.0));
+        assert!(incoming.has_stopped());
+    }
+
+    #[test]
+    fn test_refraction_enters_and_absorbs() {
+        let mut incoming = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+        let node = Scene::new().root();
+        let glass = SurfaceMaterial::glass(1.5).with_absorption(Spectrum::new(0.0, 1.0, 2.0));
+        // Head on, 96% of paths go in; keep trying until one does
+        while incoming.get_medium().is_none() {
+            incoming.restart(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0));
+            incoming.scatter(&Vector3D::new(-1.0, 0.0, 0.0), &glass, 0.01, node);
+        }
+        assert_eq!(incoming.get_medium().unwrap().node, node);
+        assert_eq!(*incoming.get_position(), Point3D::new(0.01, 0.0, 0.0));
+        assert_eq!(*incoming.get_direction(), Vector3D::new(1.0, 0.0, 0.0));
+        incoming.step(2.0);
+        let t = incoming.get_throughput();
+        assert_eq!((t.r, t.g, t.b), (1.0, (-2.0_f64).exp(), (-4.0_f64).exp()));
+    }
 }
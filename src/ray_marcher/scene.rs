@@ -1,33 +1,415 @@
-use super::scene_objects;
-use super::Point3D;
-use core::slice::{Iter, IterMut};
+use super::bvh::Bvh;
+use super::lights::{Light, Shadow};
+use super::scene_objects::SceneObject;
+use super::threed_data_types::bounding_box::BoundingBox;
+use super::threed_data_types::transform::Transform;
+use super::{Point3D, Vector3D};
 
+pub mod node;
+
+pub use node::{NodeId, SceneError, SceneNode};
+
+const SHADOW_HIT_DIST: f64 = 1e-6;
+const MAX_SHADOW_STEPS: u32 = 1000; // A shadow ray still marching after this is taken to be clear
+const MAX_SHADOW_DISTANCE: f64 = 1e7;
+
+// A tree of named nodes under a single root. Each node has a transform relative to its
+// parent and may hold an object, which is drawn in the node's world space. Lights sit
+// beside the tree in world space.
 #[allow(dead_code)]
-pub struct Scene<T> where T: scene_objects::SceneObject + Clone {
-    scene_objects: Vec<T>
+pub struct Scene {
+    nodes: Vec<Option<SceneNode>>, // Indexed by node id, removed nodes leave a gap
+    acceleration: Option<Acceleration>, // Built by `build`, dropped whenever the tree changes
+    lights: Vec<Box<dyn Light>>,
+}
+
+// A hierarchy over every drawn object with a bounding box. Objects without one are always
+// checked, the rest only when their box could still hold something closer.
+struct Acceleration {
+    bvh: Bvh,
+    bounded: Vec<usize>, // Node index of each item in the hierarchy
+    unbounded: Vec<usize>,
+    min_step_scale: f64, // Smallest step scale among the bounded objects
 }
 
 #[allow(dead_code)]
-pub struct ClosestObject<T> where T: scene_objects::SceneObject {
+pub struct ClosestObject<'a> {
     pub distance: f64,
-    pub obj: T,
+    pub step_distance: f64, // How far a ray can safely travel, given every object's step scale
+    pub obj: &'a dyn SceneObject,
+    pub node: NodeId, // The node holding the object
 }
 
 #[allow(dead_code)]
-impl<T> Scene<T> where T: scene_objects::SceneObject + Clone {
+impl Scene {
 
     pub fn new() -> Self{
-        Scene { scene_objects: Vec::<T>::new() }
+        let mut root = SceneNode::new("root", Transform::identity(), None, None);
+        root.update_world(&Transform::identity(), true);
+        Scene { nodes: vec![Some(root)], acceleration: None, lights: Vec::new() }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    // Adds an unnamed object directly under the root
+    pub fn add_scene_object<T>(&mut self, o: T) -> NodeId where T: SceneObject + 'static {
+        self.add_boxed_scene_object(Box::new(o))
+    }
+
+    pub fn add_boxed_scene_object(&mut self, o: Box<dyn SceneObject>) -> NodeId {
+        self.add_node(self.root(), "", Transform::identity(), Some(o)).unwrap()
+    }
+
+    pub fn add_node(&mut self, parent: NodeId, name: &str, transform: Transform, object: Option<Box<dyn SceneObject>>) -> Result<NodeId, SceneError> {
+        self.node(parent)?;
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(SceneNode::new(name, transform, object, Some(parent))));
+        self.node_mut(parent)?.children.push(id);
+        self.update_subtree(id);
+        Ok(id)
+    }
+
+    pub fn add_light<T>(&mut self, l: T) where T: Light + 'static {
+        self.add_boxed_light(Box::new(l));
+    }
+
+    pub fn add_boxed_light(&mut self, l: Box<dyn Light>) {
+        self.lights.push(l);
+    }
+
+    pub fn get_lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    pub fn get_node(&self, id: NodeId) -> Option<&SceneNode> {
+        self.nodes.get(id.0).and_then(|n| n.as_ref())
+    }
+
+    // The first node with the given name, in the order the nodes were added
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|(_, n)| n.name == name).map(|(id, _)| id)
+    }
+
+    pub fn find_all(&self, name: &str) -> Vec<NodeId> {
+        self.iter().filter(|(_, n)| n.name == name).map(|(id, _)| id).collect()
+    }
+
+    // Every node, root included, in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &SceneNode)> {
+        self.nodes.iter().enumerate().filter_map(|(i, n)| n.as_ref().map(|n| (NodeId(i), n)))
+    }
+
+    // The number of objects in the scene, shown or not
+    pub fn len(&self) -> usize {
+        self.iter().filter(|(_, n)| n.object.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) -> Result<(), SceneError> {
+        self.node_mut(id)?.transform = transform;
+        self.update_subtree(id);
+        Ok(())
+    }
+
+    // Hiding a node hides everything below it
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) -> Result<(), SceneError> {
+        self.node_mut(id)?.visible = visible;
+        self.update_subtree(id);
+        Ok(())
+    }
+
+    pub fn rename(&mut self, id: NodeId, name: &str) -> Result<(), SceneError> {
+        self.node_mut(id)?.name = name.to_string();
+        Ok(())
+    }
+
+    // Replaces the node's object, returning the old one
+    pub fn set_object(&mut self, id: NodeId, object: Option<Box<dyn SceneObject>>) -> Result<Option<Box<dyn SceneObject>>, SceneError> {
+        let node = self.node_mut(id)?;
+        let old = std::mem::replace(&mut node.object, object);
+        node.update_position();
+        Ok(old)
+    }
+
+    pub fn update_object<F>(&mut self, id: NodeId, f: F) -> Result<(), SceneError> where F: FnOnce(&mut dyn SceneObject) {
+        let node = self.node_mut(id)?;
+        match node.object.as_mut() {
+            Some(o) => f(o.as_mut()),
+            None => return Err(SceneError::NoObject(id)),
+        }
+        node.update_position();
+        Ok(())
+    }
+
+    // Removes the node along with everything below it
+    pub fn remove(&mut self, id: NodeId) -> Result<(), SceneError> {
+        if id == self.root() {
+            return Err(SceneError::RootNode);
+        }
+        let parent = self.node(id)?.parent.unwrap();
+        self.node_mut(parent)?.children.retain(|c| *c != id);
+        let mut stack = vec![id];
+        while let Some(n) = stack.pop() {
+            if let Some(node) = self.nodes[n.0].take() {
+                stack.extend(node.children);
+            }
+        }
+        self.acceleration = None;
+        Ok(())
+    }
+
+    // Removes the first node with the given name, if there is one
+    pub fn remove_by_name(&mut self, name: &str) -> bool {
+        match self.find(name) {
+            Some(id) => self.remove(id).is_ok(),
+            None => false,
+        }
+    }
+
+    // Moves the node under a new parent. Its local transform is kept, so it follows the
+    // new parent from then on.
+    pub fn reparent(&mut self, id: NodeId, parent: NodeId) -> Result<(), SceneError> {
+        if id == self.root() {
+            return Err(SceneError::RootNode);
+        }
+        self.node(id)?;
+        let mut ancestor = Some(parent);
+        while let Some(a) = ancestor {
+            if a == id {
+                return Err(SceneError::Cycle { node: id, parent });
+            }
+            ancestor = self.node(a)?.parent;
+        }
+        let old = self.node(id)?.parent.unwrap();
+        self.node_mut(old)?.children.retain(|c| *c != id);
+        self.node_mut(parent)?.children.push(id);
+        self.node_mut(id)?.parent = Some(parent);
+        self.update_subtree(id);
+        Ok(())
+    }
+
+    fn node(&self, id: NodeId) -> Result<&SceneNode, SceneError> {
+        self.get_node(id).ok_or(SceneError::UnknownNode(id))
+    }
+
+    // Any change through here may move an object, so the hierarchy is dropped
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut SceneNode, SceneError> {
+        self.acceleration = None;
+        self.nodes.get_mut(id.0).and_then(|n| n.as_mut()).ok_or(SceneError::UnknownNode(id))
+    }
+
+    // Brings the world transforms and visibility of a node and its descendants up to date
+    fn update_subtree(&mut self, id: NodeId) {
+        let mut stack = vec![id];
+        while let Some(n) = stack.pop() {
+            let (world, shown) = match self.nodes[n.0].as_ref().and_then(|node| node.parent) {
+                Some(p) => {
+                    let parent = self.nodes[p.0].as_ref().unwrap();
+                    (parent.world, parent.shown)
+                },
+                None => (Transform::identity(), true),
+            };
+            let node = self.nodes[n.0].as_mut().unwrap();
+            node.update_world(&world, shown);
+            stack.extend(node.children.iter().copied());
+        }
+    }
+
+    // Nodes that hold an object and are shown, along with their index
+    fn drawn(&self) -> impl Iterator<Item = (usize, &SceneNode)> {
+        self.nodes.iter().enumerate()
+            .filter_map(|(i, n)| n.as_ref().map(|n| (i, n)))
+            .filter(|(_, n)| n.shown && n.object.is_some())
+    }
+
+    fn drawn_node(&self, i: usize) -> &SceneNode {
+        self.nodes[i].as_ref().unwrap()
+    }
+
+    // Builds the bounding volume hierarchy used by the distance queries. Until it is
+    // built, and after any change to the tree, every query scans every drawn object.
+    pub fn build(&mut self){
+        let mut boxes: Vec<BoundingBox> = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut min_step_scale: f64 = 1.0;
+        for (i, n) in self.drawn() {
+            match n.bounding_box() {
+                Some(b) if !b.is_empty() && b.size().length().is_finite() => {
+                    boxes.push(b);
+                    bounded.push(i);
+                    min_step_scale = min_step_scale.min(n.get_step_scale());
+                },
+                _ => unbounded.push(i),
+            }
+        }
+        self.acceleration = Some(Acceleration { bvh: Bvh::new(&boxes), bounded, unbounded, min_step_scale });
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.acceleration.is_some()
+    }
+
+    pub fn get_min_distance(&self, p: &Point3D) -> Option<f64> {
+        let acceleration = match &self.acceleration {
+            Some(a) => a,
+            None => return self.get_min_distance_linear(p),
+        };
+        let mut min_dist: Option<f64> = None;
+        for i in acceleration.unbounded.iter() {
+            let dist = self.drawn_node(*i).signed_distance(p);
+            min_dist = Some(min_dist.map_or(dist, |d| d.min(dist)));
+        }
+        acceleration.bvh.visit_nearest(p, |item| {
+            let dist = self.drawn_node(acceleration.bounded[item]).signed_distance(p);
+            let best = min_dist.map_or(dist, |d| d.min(dist));
+            min_dist = Some(best);
+            best.max(0.0)
+        });
+        min_dist
+    }
+
+    // Whether anything lies within `max_distance` of `from` along the unit `direction`
+    pub fn is_occluded(&self, from: &Point3D, direction: &Vector3D, max_distance: f64) -> bool {
+        let max_distance = max_distance.min(MAX_SHADOW_DISTANCE);
+        let mut t = 0.0;
+        for _ in 0..MAX_SHADOW_STEPS {
+            let p = *from + direction.to_point() * t;
+            let closest = match self.get_closest_object(&p) {
+                Some(c) => c,
+                None => return false,
+            };
+            if closest.distance < SHADOW_HIT_DIST {
+                return true;
+            }
+            t += closest.step_distance;
+            if t >= max_distance {
+                return false;
+            }
+        }
+        false
+    }
+
+    // How much light gets from `from` to `max_distance` along `direction`, from 0 when
+    // something is in the way to 1 when nothing is
+    pub fn visibility(&self, from: &Point3D, direction: &Vector3D, max_distance: f64, shadow: Shadow) -> f64 {
+        match shadow {
+            Shadow::Hard => if self.is_occluded(from, direction, max_distance) { 0.0 } else { 1.0 },
+            Shadow::Soft { k } => self.soft_shadow(from, direction, max_distance, k, false),
+            Shadow::SoftImproved { k } => self.soft_shadow(from, direction, max_distance, k, true),
+        }
+    }
+
+    // The ray's closest pass to any object, relative to how far along it was, sets the
+    // penumbra. Quilez, "Soft shadows in raymarched SDFs" (2010, improved 2018).
+    fn soft_shadow(&self, from: &Point3D, direction: &Vector3D, max_distance: f64, k: f64, improved: bool) -> f64 {
+        let max_distance = max_distance.min(MAX_SHADOW_DISTANCE);
+        let mut light: f64 = 1.0;
+        let mut t = 0.0;
+        let mut last_distance = f64::INFINITY;
+        for _ in 0..MAX_SHADOW_STEPS {
+            let p = *from + direction.to_point() * t;
+            let closest = match self.get_closest_object(&p) {
+                Some(c) => c,
+                None => return light,
+            };
+            let h = closest.distance;
+            if h < SHADOW_HIT_DIST {
+                return 0.0;
+            }
+            if improved {
+                // Where the ray came closest to the sphere of radius h around p, given
+                // the last sphere of radius last_distance around the previous point
+                let y = if last_distance.is_finite() { h * h / (2.0 * last_distance) } else { 0.0 };
+                let d = (h * h - y * y).max(0.0).sqrt();
+                if t - y > 0.0 {
+                    light = light.min(k * d / (t - y));
+                }
+                last_distance = h;
+            } else if t > 0.0 {
+                light = light.min(k * h / t);
+            }
+            t += closest.step_distance;
+            if t >= max_distance {
+                break;
+            }
+        }
+        light.clamp(0.0, 1.0)
+    }
+
+    // The returned object is the scene node, which places its object in world space
+    pub fn get_closest_object(&self, p: &Point3D) -> Option<ClosestObject<'_>>{
+        self.closest_object(p, None)
     }
 
-    pub fn add_scene_object(&mut self, o: T){
-        self.scene_objects.push(o)
+    // For a point inside the object of node `inside`, such as a ray passing through
+    // glass. That object's distance is negated so it measures the way out, while every
+    // other object is measured as usual.
+    pub fn get_closest_object_inside(&self, p: &Point3D, inside: NodeId) -> Option<ClosestObject<'_>>{
+        let node = match self.nodes.get(inside.0).and_then(|n| n.as_ref()) {
+            Some(n) if n.shown && n.object.is_some() => n,
+            _ => return self.get_closest_object(p),
+        };
+        let mut closest = self.closest_object(p, Some(inside.0));
+        Scene::update_closest(&mut closest, inside.0, node, -node.signed_distance(p));
+        closest
     }
 
-     pub fn get_min_distance(&self, p: &Point3D) -> Option<f64> {
+    fn closest_object(&self, p: &Point3D, skip: Option<usize>) -> Option<ClosestObject<'_>>{
+        let acceleration = match &self.acceleration {
+            Some(a) => a,
+            None => return self.closest_object_linear(p, skip),
+        };
+        let mut min_dist: Option<ClosestObject<'_>> = None;
+        for i in acceleration.unbounded.iter().filter(|i| Some(**i) != skip) {
+            let n = self.drawn_node(*i);
+            Scene::update_closest(&mut min_dist, *i, n, n.signed_distance(p));
+        }
+        // A skipped object is farther than its box, so it can't be closer than the best
+        // distance. Its step is at least its box distance times the smallest step scale,
+        // so it can't shorten the step either.
+        acceleration.bvh.visit_nearest(p, |item| {
+            let i = acceleration.bounded[item];
+            if Some(i) == skip {
+                return min_dist.as_ref().map_or(f64::INFINITY, |c| c.distance.max(c.step_distance / acceleration.min_step_scale).max(0.0));
+            }
+            let n = self.drawn_node(i);
+            let closest = Scene::update_closest(&mut min_dist, i, n, n.signed_distance(p));
+            closest.distance.max(closest.step_distance / acceleration.min_step_scale).max(0.0)
+        });
+        min_dist
+    }
+
+    fn update_closest<'a, 'b>(closest: &'b mut Option<ClosestObject<'a>>, i: usize, o: &'a dyn SceneObject, dist: f64) -> &'b ClosestObject<'a> {
+        let step = dist * o.get_step_scale();
+        match closest {
+            Some(c) => {
+                c.step_distance = c.step_distance.min(step);
+                if dist < c.distance{
+                    c.distance = dist;
+                    c.obj = o;
+                    c.node = NodeId(i);
+                }
+            },
+            None => *closest = Some(ClosestObject { distance: dist, step_distance: step, obj: o, node: NodeId(i) }),
+        }
+        closest.as_ref().unwrap()
+    }
+
+    // Scans every drawn object, whether or not the scene has been built
+    pub fn get_min_distance_linear(&self, p: &Point3D) -> Option<f64> {
         let mut min_dist: Option<f64> = None;
-        for o in self.scene_objects.iter(){
-            let dist = o.signed_distance(p);
+        for (_, n) in self.drawn(){
+            let dist = n.signed_distance(p);
 
             match min_dist {
                 Some(min_d) => {
@@ -42,30 +424,259 @@ impl<T> Scene<T> where T: scene_objects::SceneObject + Clone {
         min_dist
     }
 
-    pub fn get_closest_object(&self, p: &Point3D) -> Option<ClosestObject<T>>{
-        let mut min_dist: Option<ClosestObject<T>> = None;
+    pub fn get_closest_object_linear(&self, p: &Point3D) -> Option<ClosestObject<'_>>{
+        self.closest_object_linear(p, None)
+    }
 
-        for o in self.scene_objects.iter(){
-            let dist = o.signed_distance(p);
+    fn closest_object_linear(&self, p: &Point3D, skip: Option<usize>) -> Option<ClosestObject<'_>>{
+        let mut min_dist: Option<ClosestObject<'_>> = None;
+        for (i, n) in self.drawn().filter(|(i, _)| Some(*i) != skip){
+            Scene::update_closest(&mut min_dist, i, n, n.signed_distance(p));
+        }
+        min_dist
+    }
+}
 
-            match min_dist {
-                Some(ClosestObject { distance, obj: _ }) => {
-                    if dist < distance{
-                        min_dist = Some(ClosestObject { distance: dist, obj: o.clone() })
-                    }
-                },
-                None => min_dist = Some(ClosestObject { distance: dist, obj: o.clone() }),
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::scene_objects::{deformations::Displace, objects::{AxisAlignedBox, Sphere}, SurfaceMaterial};
+    use super::super::color_data_types::Color;
+
+    struct Slab {
+        position: Point3D,
+    }
+
+    impl SceneObject for Slab {
+        fn signed_distance(&self, p: &Point3D) -> f64 {
+            (p.x - self.position.x).abs() - 0.5
+        }
+
+        fn get_position(&self) -> &Point3D {
+            &self.position
+        }
+
+        fn get_surface_material(&self) -> SurfaceMaterial {
+            SurfaceMaterial::diffuse(Color::new(0.0, 1.0, 0.0))
+        }
+    }
+
+    #[test]
+    fn test_closest_object_mixed_types(){
+        let mut scene = Scene::new();
+        scene.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, None));
+        scene.add_scene_object(Slab { position: Point3D::new(4.0, 0.0, 0.0) });
+        let closest = scene.get_closest_object(&Point3D::new(0.0, 0.0, 0.0)).unwrap();
+        assert_eq!(closest.distance, 3.5);
+        assert_eq!(*closest.obj.get_position(), Point3D::new(4.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_closest_object_step_distance(){
+        // The displaced sphere is farther away but its step scale limits the step
+        let mut scene = Scene::new();
+        scene.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, None));
+        let displaced = Displace::new(Sphere::new(Point3D::new(-12.0, 0.0, 0.0), 1.0, None), Box::new(|_p: &Point3D| 0.0), 1.0);
+        scene.add_scene_object(displaced);
+        let closest = scene.get_closest_object(&Point3D::new(0.0, 0.0, 0.0)).unwrap();
+        assert_eq!(closest.distance, 9.0);
+        assert_eq!(closest.step_distance, 5.5);
+    }
+
+    #[test]
+    fn test_shadow_visibility(){
+        let mut scene = Scene::new();
+        scene.add_scene_object(Sphere::new(Point3D::new(0.0, 0.0, 5.0), 1.0, None));
+        scene.build();
+        let up = Vector3D::new(0.0, 0.0, 1.0);
+        let shadows = [Shadow::Hard, Shadow::Soft { k: 8.0 }, Shadow::SoftImproved { k: 8.0 }];
+        for shadow in shadows {
+            assert_eq!(scene.visibility(&Point3D::new(0.0, 0.0, 0.0), &up, 10.0, shadow), 0.0);
+            assert_eq!(scene.visibility(&Point3D::new(20.0, 0.0, 0.0), &up, 10.0, shadow), 1.0);
+            // The light is closer than the sphere
+            assert_eq!(scene.visibility(&Point3D::new(0.0, 0.0, 0.0), &up, 3.0, shadow), 1.0);
+        }
+        // Passing half a unit from the sphere: lit for hard shadows, in the penumbra for soft
+        let near = Point3D::new(1.5, 0.0, 0.0);
+        assert_eq!(scene.visibility(&near, &up, 10.0, Shadow::Hard), 1.0);
+        for soft in [Shadow::Soft { k: 8.0 }, Shadow::SoftImproved { k: 8.0 }] {
+            let v = scene.visibility(&near, &up, 10.0, soft);
+            assert!(v > 0.0 && v < 1.0, "{:?} gave {}", soft, v);
+        }
+        // A harder penumbra is narrower, so the same point gets more light
+        let soft = scene.visibility(&near, &up, 10.0, Shadow::Soft { k: 2.0 });
+        let hard = scene.visibility(&near, &up, 10.0, Shadow::Soft { k: 16.0 });
+        assert!(soft < hard);
+    }
+
+    #[test]
+    fn test_closest_object_inside(){
+        // A ball sealed inside a glass sphere, seen from within the glass
+        let mut scene = Scene::new();
+        let glass = scene.add_scene_object(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 3.0, None));
+        let ball = scene.add_scene_object(Sphere::new(Point3D::new(1.5, 0.0, 0.0), 0.5, None));
+        for built in [false, true] {
+            if built {
+                scene.build();
             }
+            let closest = scene.get_closest_object_inside(&Point3D::new(0.0, 0.0, 0.0), glass).unwrap();
+            assert_eq!((closest.node, closest.distance), (ball, 1.0));
+            let closest = scene.get_closest_object_inside(&Point3D::new(-2.0, 0.0, 0.0), glass).unwrap();
+            assert_eq!((closest.node, closest.distance), (glass, 1.0));
+            assert_eq!(scene.get_closest_object(&Point3D::new(-2.0, 0.0, 0.0)).unwrap().distance, -1.0);
         }
+    }
 
-        min_dist
+    #[test]
+    fn test_min_distance_empty_scene(){
+        let scene = Scene::new();
+        assert_eq!(scene.get_min_distance(&Point3D::new(0.0, 0.0, 0.0)), None);
+    }
+
+    fn crowded_scene() -> Scene {
+        let mut scene = Scene::new();
+        for i in 0..500 {
+            let f = i as f64;
+            let center = Point3D::new((f * 7.3).sin() * 40.0, (f * 3.1).cos() * 40.0, (f * 1.7).sin() * 40.0);
+            if i % 2 == 0 {
+                scene.add_scene_object(Sphere::new(center, 0.5 + (f * 0.9).sin().abs(), None));
+            } else {
+                scene.add_scene_object(AxisAlignedBox::new(center, Point3D::new(0.5, 1.0, 0.75), None));
+            }
+        }
+        // An unbounded object and one with a reduced step scale
+        scene.add_scene_object(Slab { position: Point3D::new(60.0, 0.0, 0.0) });
+        scene.add_scene_object(Displace::new(Sphere::new(Point3D::new(-5.0, 3.0, 0.0), 2.0, None), Box::new(|_p: &Point3D| 0.0), 3.0));
+        scene
+    }
+
+    #[test]
+    fn test_built_scene_matches_linear_scan(){
+        let mut scene = crowded_scene();
+        scene.build();
+        assert!(scene.is_built());
+        for i in 0..300 {
+            let f = i as f64;
+            let p = Point3D::new((f * 2.3).cos() * 55.0, (f * 0.7).sin() * 45.0, (f * 1.1).cos() * 50.0);
+            let fast = scene.get_closest_object(&p).unwrap();
+            let slow = scene.get_closest_object_linear(&p).unwrap();
+            assert_eq!(fast.distance, slow.distance);
+            assert_eq!(fast.step_distance, slow.step_distance);
+            assert_eq!(fast.obj.get_position(), slow.obj.get_position());
+            assert_eq!(scene.get_min_distance(&p), scene.get_min_distance_linear(&p));
+        }
     }
 
-    pub fn iter(&self) -> Iter<'_, T> {
-        self.scene_objects.iter()
+    #[test]
+    fn test_changes_drop_the_hierarchy(){
+        let mut scene = crowded_scene();
+        scene.build();
+        scene.add_scene_object(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None));
+        assert!(!scene.is_built());
+        scene.build();
+        scene.set_transform(NodeId(3), Transform::from_translation(Point3D::new(1.0, 0.0, 0.0))).unwrap();
+        assert!(!scene.is_built());
+        scene.build();
+        scene.remove(NodeId(3)).unwrap();
+        assert!(!scene.is_built());
     }
 
-    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
-        self.scene_objects.iter_mut()
+    #[test]
+    fn test_built_empty_scene(){
+        let mut scene = Scene::new();
+        scene.build();
+        assert!(scene.get_closest_object(&Point3D::new(0.0, 0.0, 0.0)).is_none());
+        assert_eq!(scene.get_min_distance(&Point3D::new(0.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn test_find_and_rename(){
+        let mut scene = Scene::new();
+        let group = scene.add_node(scene.root(), "group", Transform::identity(), None).unwrap();
+        let ball = scene.add_node(group, "ball", Transform::identity(), Some(Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None)))).unwrap();
+        let other = scene.add_node(scene.root(), "ball", Transform::identity(), None).unwrap();
+        assert_eq!(scene.find("ball"), Some(ball));
+        assert_eq!(scene.find_all("ball"), vec![ball, other]);
+        assert_eq!(scene.find("root"), Some(scene.root()));
+        assert_eq!(scene.get_node(group).unwrap().get_children(), &[ball]);
+        assert_eq!(scene.len(), 1);
+        scene.rename(other, "cube").unwrap();
+        assert_eq!(scene.find_all("ball"), vec![ball]);
+        assert_eq!(scene.find("missing"), None);
+    }
+
+    #[test]
+    fn test_parent_transform_applies_to_children(){
+        let mut scene = Scene::new();
+        let group = scene.add_node(scene.root(), "group", Transform::from_translation(Point3D::new(5.0, 0.0, 0.0)), None).unwrap();
+        let ball = scene.add_node(group, "ball", Transform::from_uniform_scale(2.0), Some(Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None)))).unwrap();
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        assert!((scene.get_min_distance(&origin).unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(*scene.get_closest_object(&origin).unwrap().obj.get_position(), Point3D::new(5.0, 0.0, 0.0));
+
+        scene.set_transform(group, Transform::from_translation(Point3D::new(0.0, 10.0, 0.0))).unwrap();
+        assert!((scene.get_min_distance(&origin).unwrap() - 8.0).abs() < 1e-9);
+        scene.build();
+        assert!((scene.get_min_distance(&origin).unwrap() - 8.0).abs() < 1e-9);
+
+        scene.update_object(ball, |_| {}).unwrap();
+        assert_eq!(scene.update_object(group, |_| {}), Err(SceneError::NoObject(group)));
+    }
+
+    #[test]
+    fn test_hidden_nodes_are_skipped(){
+        let mut scene = Scene::new();
+        scene.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, None));
+        let group = scene.add_node(scene.root(), "group", Transform::identity(), None).unwrap();
+        let near = scene.add_node(group, "near", Transform::identity(), Some(Box::new(Sphere::new(Point3D::new(3.0, 0.0, 0.0), 1.0, None)))).unwrap();
+        let origin = Point3D::new(0.0, 0.0, 0.0);
+        assert_eq!(scene.get_min_distance(&origin), Some(2.0));
+        scene.set_visible(group, false).unwrap();
+        assert!(!scene.get_node(near).unwrap().is_shown());
+        assert!(scene.get_node(near).unwrap().is_visible());
+        assert_eq!(scene.get_min_distance(&origin), Some(9.0));
+        scene.build();
+        assert_eq!(scene.get_min_distance(&origin), Some(9.0));
+        scene.set_visible(group, true).unwrap();
+        assert_eq!(scene.get_min_distance(&origin), Some(2.0));
+    }
+
+    #[test]
+    fn test_remove_subtree(){
+        let mut scene = Scene::new();
+        let group = scene.add_node(scene.root(), "group", Transform::identity(), None).unwrap();
+        let child = scene.add_node(group, "child", Transform::identity(), Some(Box::new(Sphere::new(Point3D::new(3.0, 0.0, 0.0), 1.0, None)))).unwrap();
+        let keep = scene.add_scene_object(Sphere::new(Point3D::new(10.0, 0.0, 0.0), 1.0, None));
+        assert!(scene.remove_by_name("group"));
+        assert!(scene.get_node(group).is_none());
+        assert!(scene.get_node(child).is_none());
+        assert!(scene.get_node(keep).is_some());
+        assert_eq!(scene.get_node(scene.root()).unwrap().get_children(), &[keep]);
+        assert_eq!(scene.get_min_distance(&Point3D::new(0.0, 0.0, 0.0)), Some(9.0));
+        assert!(!scene.remove_by_name("group"));
+        assert_eq!(scene.remove(child), Err(SceneError::UnknownNode(child)));
+        assert_eq!(scene.remove(scene.root()), Err(SceneError::RootNode));
+        // Ids aren't reused
+        assert_ne!(scene.add_node(scene.root(), "", Transform::identity(), None).unwrap(), group);
+    }
+
+    #[test]
+    fn test_reparent(){
+        let mut scene = Scene::new();
+        let a = scene.add_node(scene.root(), "a", Transform::from_translation(Point3D::new(5.0, 0.0, 0.0)), None).unwrap();
+        let b = scene.add_node(a, "b", Transform::identity(), None).unwrap();
+        let ball = scene.add_scene_object(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None));
+        scene.reparent(ball, b).unwrap();
+        assert_eq!(scene.get_node(ball).unwrap().get_parent(), Some(b));
+        assert_eq!(*scene.get_node(ball).unwrap().get_position(), Point3D::new(5.0, 0.0, 0.0));
+        assert!(scene.get_node(scene.root()).unwrap().get_children().iter().all(|c| *c != ball));
+
+        assert_eq!(scene.reparent(a, b), Err(SceneError::Cycle { node: a, parent: b }));
+        assert_eq!(scene.reparent(a, a), Err(SceneError::Cycle { node: a, parent: a }));
+        assert_eq!(scene.reparent(scene.root(), a), Err(SceneError::RootNode));
+        assert_eq!(scene.get_node(a).unwrap().get_parent(), Some(scene.root()));
+
+        scene.reparent(b, scene.root()).unwrap();
+        assert_eq!(*scene.get_node(ball).unwrap().get_position(), Point3D::new(0.0, 0.0, 0.0));
     }
 }
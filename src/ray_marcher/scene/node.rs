@@ -0,0 +1,153 @@
+use std::fmt;
+
+use super::super::scene_objects::{SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::super::threed_data_types::transform::Transform;
+use super::Point3D;
+
+// Identifies a node for as long as the scene holds it. Ids of removed nodes are never
+// handed out again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(pub(super) usize);
+
+#[derive(Debug, PartialEq)]
+pub enum SceneError {
+    UnknownNode(NodeId),
+    NoObject(NodeId),
+    RootNode, // The root can't be removed or moved
+    Cycle { node: NodeId, parent: NodeId }, // The new parent is inside the node's own subtree
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::UnknownNode(id) => write!(f, "no node with id {}", id.0),
+            SceneError::NoObject(id) => write!(f, "node {} holds no object", id.0),
+            SceneError::RootNode => write!(f, "the root node can't be removed or reparented"),
+            SceneError::Cycle { node, parent } => write!(f, "node {} can't be moved under its own descendant {}", node.0, parent.0),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// A node in the scene tree. Its transform is relative to its parent, and the world
+// transform, visibility and position below are kept up to date by the scene.
+pub struct SceneNode {
+    pub(super) name: String,
+    pub(super) transform: Transform,
+    pub(super) object: Option<Box<dyn SceneObject>>,
+    pub(super) parent: Option<NodeId>,
+    pub(super) children: Vec<NodeId>,
+    pub(super) visible: bool,
+    pub(super) world: Transform, // The parent's world transform followed by this node's
+    pub(super) moved: bool, // Whether the world transform is anything but the identity
+    pub(super) shown: bool, // Visible along with every ancestor
+    pub(super) position: Point3D, // The object's position in world space
+}
+
+#[allow(dead_code)]
+impl SceneNode {
+    pub(super) fn new(name: &str, transform: Transform, object: Option<Box<dyn SceneObject>>, parent: Option<NodeId>) -> Self {
+        SceneNode {
+            name: name.to_string(),
+            transform,
+            object,
+            parent,
+            children: Vec::new(),
+            visible: true,
+            world: transform,
+            moved: false,
+            shown: true,
+            position: Point3D::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn get_world_transform(&self) -> &Transform {
+        &self.world
+    }
+
+    pub fn get_object(&self) -> Option<&dyn SceneObject> {
+        self.object.as_deref()
+    }
+
+    pub fn get_parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn get_children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    // Whether the node is drawn, which needs every ancestor to be visible too
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    // Places the node under a parent's world transform and visibility
+    pub(super) fn update_world(&mut self, parent_world: &Transform, parent_shown: bool) {
+        self.world = self.transform.then(parent_world);
+        self.moved = self.world != Transform::identity();
+        self.shown = self.visible && parent_shown;
+        self.update_position();
+    }
+
+    pub(super) fn update_position(&mut self) {
+        if let Some(o) = &self.object {
+            self.position = self.world.transform_point(o.get_position());
+        }
+    }
+
+    fn local(&self, p: &Point3D) -> Point3D {
+        if self.moved { self.world.inverse_transform_point(p) } else { *p }
+    }
+}
+
+// A node stands in for its object placed in the world, the same way `Transformed` does.
+// Nodes without an object are never returned by scene queries.
+impl SceneObject for SceneNode {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        match &self.object {
+            Some(o) if self.moved => o.signed_distance(&self.local(p)) * self.world.get_min_scale(),
+            Some(o) => o.signed_distance(p),
+            None => f64::INFINITY,
+        }
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.as_ref().map(|o| o.get_surface_material()).unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.as_ref().map(|o| o.get_surface_material_at(&self.local(p))).unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.as_ref().map(|o| o.get_step_scale()).unwrap_or(1.0)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let b = self.object.as_ref()?.bounding_box()?;
+        if !self.moved {
+            return Some(b);
+        }
+        let corners = b.corners().map(|c| self.world.transform_point(&c));
+        Some(BoundingBox::from_points(corners.iter()))
+    }
+}
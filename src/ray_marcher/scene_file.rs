@@ -0,0 +1,919 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+use ron::Options;
+
+use super::camera::Camera;
+use super::color_data_types::Color;
+use super::lights::{DirectionalLight, Light, PointLight, Shadow, SphereLight, SpotLight};
+use super::color_data_types::spectrum::Spectrum;
+use super::marcher::MarcherHandler;
+use super::scene::{NodeId, Scene};
+use super::scene_objects::csg::{Intersection, Subtraction, Union};
+use super::scene_objects::deformations::{Bend, Elongate, Onion, Round, Twist};
+use super::scene_objects::expression::Expression;
+use super::scene_objects::fractals::{Julia, Mandelbulb, MengerSponge, SierpinskiTetrahedron};
+use super::scene_objects::heightfield::Heightfield;
+use super::scene_objects::mesh::Mesh;
+use super::scene_objects::metaballs::{Metaball, Metaballs};
+use super::scene_objects::objects::*;
+use super::scene_objects::profiles::{Arc, Circle, Extrude, Point2D, Polygon, Profile, QuadraticBezier, Rectangle, Revolve, Segment};
+use super::scene_objects::repetition::{LimitedRepeat, Mirror, PolarRepeat, Repeat};
+use super::scene_objects::smooth::{SmoothIntersection, SmoothSubtraction, SmoothUnion};
+use super::scene_objects::text::{Text, TextProfile};
+use super::scene_objects::transformed::Transformed;
+use super::scene_objects::tubes::Tube;
+use super::scene_objects::voxel_grid::VoxelGrid;
+use super::scene_objects::{SceneObject, SurfaceMaterial};
+use super::threed_data_types::bounding_box::BoundingBox;
+use super::threed_data_types::transform::Transform;
+use super::{Point3D, Vector3D};
+
+pub mod description;
+
+use description::*;
+
+// Scenes written as RON text. A file holds the camera, the render settings, a table of
+// shared materials and the tree of nodes under the scene's root, e.g.
+//
+//   (
+//       camera: (position: (0, 0, 0), direction: (1, 0, 0), view_angle: 60, resolution: (600, 600)),
+//       materials: { "red": (albedo: (1, 0, 0), metallic: 1, roughness: 0) },
+//       nodes: [
+//           (name: "ball", object: Sphere(center: (30, -10, 0), radius: 10, material: "red")),
+//       ],
+//   )
+//
+// Optional values can be written without `Some(...)`.
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    Syntax { line: usize, column: usize, message: String }, // Lines and columns start at 1
+    Invalid { path: String, message: String }, // The text parsed but describes something impossible
+    Unsupported { path: String }, // An object with no scene file form
+    Write(String),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io(e) => write!(f, "could not read scene: {}", e),
+            SceneFileError::Syntax { line, column, message } => write!(f, "line {}, column {}: {}", line, column, message),
+            SceneFileError::Invalid { path, message } => write!(f, "{}: {}", path, message),
+            SceneFileError::Unsupported { path } => write!(f, "{}: this object can't be written to a scene file", path),
+            SceneFileError::Write(message) => write!(f, "could not write scene: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneFileError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneFileError {
+    fn from(e: std::io::Error) -> Self {
+        SceneFileError::Io(e)
+    }
+}
+
+fn invalid<T>(path: &str, message: &str) -> Result<T, SceneFileError> {
+    Err(SceneFileError::Invalid { path: path.to_string(), message: message.to_string() })
+}
+
+// Everything a scene file describes, ready to render
+pub struct SceneFile {
+    pub camera: Camera,
+    pub render: RenderSettings,
+    pub scene: Scene,
+}
+
+#[allow(dead_code)]
+impl SceneFile {
+    pub fn load<P>(path: P) -> Result<Self, SceneFileError> where P: AsRef<Path> {
+        SceneFile::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SceneFileError> {
+        let options = Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+        let description: SceneDescription = options.from_str(text).map_err(|e| SceneFileError::Syntax {
+            line: e.position.line,
+            column: e.position.col,
+            message: e.code.to_string(),
+        })?;
+        SceneFile::from_description(&description)
+    }
+
+    pub fn from_description(description: &SceneDescription) -> Result<Self, SceneFileError> {
+        let camera = build_camera(&description.camera)?;
+        let (r, g, b) = description.render.sky;
+        if r < 0.0 || g < 0.0 || b < 0.0 {
+            return invalid("render.sky", "sky light can't be negative");
+        }
+        let builder = Builder { materials: &description.materials };
+        let mut scene = Scene::new();
+        let root = scene.root();
+        for (i, node) in description.nodes.iter().enumerate() {
+            builder.add_node(&mut scene, root, node, &node_path("", i, node))?;
+        }
+        for (i, light) in description.lights.iter().enumerate() {
+            scene.add_boxed_light(build_light(light, &format!("lights[{}]", i))?);
+        }
+        Ok(SceneFile { camera, render: description.render.clone(), scene })
+    }
+
+    pub fn save<P>(&self, path: P) -> Result<(), SceneFileError> where P: AsRef<Path> {
+        save(path, &self.scene, &self.camera, &self.render)
+    }
+
+    pub fn into_marcher(self) -> MarcherHandler {
+        let mut marcher = MarcherHandler::new(self.render.steps, self.render.max_distance, self.render.iterations, self.camera);
+        let (r, g, b) = self.render.sky;
+        marcher.set_sky(Spectrum::new(r, g, b));
+        marcher.set_scene(self.scene);
+        marcher
+    }
+}
+
+pub fn save<P>(path: P, scene: &Scene, camera: &Camera, render: &RenderSettings) -> Result<(), SceneFileError> where P: AsRef<Path> {
+    fs::write(path, to_ron(scene, camera, render)?)?;
+    Ok(())
+}
+
+pub fn to_ron(scene: &Scene, camera: &Camera, render: &RenderSettings) -> Result<String, SceneFileError> {
+    let config = PrettyConfig::new().extensions(Extensions::IMPLICIT_SOME);
+    ron::ser::to_string_pretty(&describe(scene, camera, render)?, config).map_err(|e| SceneFileError::Write(e.to_string()))
+}
+
+pub fn describe(scene: &Scene, camera: &Camera, render: &RenderSettings) -> Result<SceneDescription, SceneFileError> {
+    let root = scene.get_node(scene.root()).unwrap();
+    let mut nodes = Vec::new();
+    for (i, child) in root.get_children().iter().enumerate() {
+        nodes.push(describe_node(scene, *child, &format!("nodes[{}]", i))?);
+    }
+    // Files have no way to move or hide the root, so a changed root becomes a node
+    if !root.is_visible() || *root.get_transform() != Transform::identity() {
+        let transform = TransformDescription::from_transform(root.get_transform());
+        let wrapper = NodeDescription { name: root.get_name().to_string(), transform, visible: root.is_visible(), object: None, children: nodes };
+        nodes = vec![wrapper];
+    }
+    let mut lights = Vec::new();
+    for (i, light) in scene.get_lights().iter().enumerate() {
+        lights.push(light.describe().ok_or(SceneFileError::Unsupported { path: format!("lights[{}]", i) })?);
+    }
+    let view_direction = camera.view_direction;
+    Ok(SceneDescription {
+        camera: CameraDescription {
+            position: vec3(&camera.position),
+            direction: (view_direction.x, view_direction.y, view_direction.z),
+            near_plane: camera.near_plane_dist,
+            view_angle: camera.camera_angle.to_degrees(),
+            resolution: camera.resolution,
+        },
+        render: render.clone(),
+        materials: BTreeMap::new(),
+        nodes,
+        lights,
+    })
+}
+
+fn describe_node(scene: &Scene, id: NodeId, path: &str) -> Result<NodeDescription, SceneFileError> {
+    let node = scene.get_node(id).unwrap();
+    let object = match node.get_object() {
+        Some(o) => Some(o.describe().ok_or(SceneFileError::Unsupported { path: format!("{}.object", path) })?),
+        None => None,
+    };
+    let mut children = Vec::new();
+    for (i, child) in node.get_children().iter().enumerate() {
+        children.push(describe_node(scene, *child, &format!("{}.children[{}]", path, i))?);
+    }
+    Ok(NodeDescription {
+        name: node.get_name().to_string(),
+        transform: TransformDescription::from_transform(node.get_transform()),
+        visible: node.is_visible(),
+        object,
+        children,
+    })
+}
+
+// Where a node sits in the file, used to point at mistakes
+fn node_path(parent: &str, i: usize, node: &NodeDescription) -> String {
+    let list = if parent.is_empty() { "nodes".to_string() } else { format!("{}.children", parent) };
+    if node.name.is_empty() {
+        format!("{}[{}]", list, i)
+    } else {
+        format!("{}[{}] \"{}\"", list, i, node.name)
+    }
+}
+
+fn point(v: &Vec3) -> Point3D {
+    Point3D::new(v.0, v.1, v.2)
+}
+
+fn point2(v: &Vec2) -> Point2D {
+    Point2D::new(v.0, v.1)
+}
+
+fn build_camera(c: &CameraDescription) -> Result<Camera, SceneFileError> {
+    let direction = Vector3D::new(c.direction.0, c.direction.1, c.direction.2);
+    if direction.length() == 0.0 {
+        return invalid("camera", "the direction can't be zero");
+    }
+    if c.resolution.0 == 0 || c.resolution.1 == 0 {
+        return invalid("camera", "the resolution can't be zero");
+    }
+    Ok(Camera::new(point(&c.position), direction.get_norm(), c.near_plane, c.view_angle.to_radians(), c.resolution))
+}
+
+fn build_light(l: &LightDescription, path: &str) -> Result<Box<dyn Light>, SceneFileError> {
+    let (color, intensity, shadow) = match l {
+        LightDescription::Point { color, intensity, shadow, .. }
+        | LightDescription::Directional { color, intensity, shadow, .. }
+        | LightDescription::Spot { color, intensity, shadow, .. }
+        | LightDescription::Sphere { color, intensity, shadow, .. } => (color, *intensity, *shadow),
+    };
+    if let Shadow::Soft { k } | Shadow::SoftImproved { k } = shadow {
+        if k.is_nan() || k <= 0.0 {
+            return invalid(&format!("{}.shadow", path), "the penumbra hardness k must be positive");
+        }
+    }
+    if [color.0, color.1, color.2].iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return invalid(path, "color components must be between 0 and 1");
+    }
+    if intensity.is_nan() || intensity < 0.0 {
+        return invalid(path, "intensity can't be negative");
+    }
+    let color = Color::new(color.0, color.1, color.2);
+    let direction = |d: &Vec3| {
+        let d = Vector3D::new(d.0, d.1, d.2);
+        if d.length() == 0.0 {
+            return invalid(path, "the direction can't be zero");
+        }
+        Ok(d)
+    };
+    Ok(match l {
+        LightDescription::Point { position, .. } => Box::new(PointLight::new(point(position), color, intensity).with_shadow(shadow)),
+        LightDescription::Directional { direction: d, .. } => Box::new(DirectionalLight::new(direction(d)?, color, intensity).with_shadow(shadow)),
+        LightDescription::Spot { position, direction: d, inner_angle, outer_angle, .. } => {
+            if !(0.0 <= *inner_angle && inner_angle <= outer_angle && *outer_angle < 180.0) {
+                return invalid(path, "spot angles must satisfy 0 <= inner_angle <= outer_angle < 180");
+            }
+            Box::new(SpotLight::new(point(position), direction(d)?, inner_angle.to_radians(), outer_angle.to_radians(), color, intensity).with_shadow(shadow))
+        }
+        LightDescription::Sphere { center, radius, .. } => {
+            if radius.is_nan() || *radius <= 0.0 {
+                return invalid(path, "a sphere light needs a positive radius");
+            }
+            Box::new(SphereLight::new(point(center), *radius, color, intensity).with_shadow(shadow))
+        }
+    })
+}
+
+fn build_transform(t: &TransformDescription, path: &str) -> Result<Transform, SceneFileError> {
+    let values = [t.translate, t.rotate, t.scale].into_iter().flat_map(|v| [v.0, v.1, v.2]);
+    if !values.chain(t.matrix.iter().flatten().flatten().copied()).all(f64::is_finite) {
+        return invalid(path, "a transform's values must be finite");
+    }
+    let translation = point(&t.translate);
+    if let Some(m) = t.matrix {
+        return Transform::try_from_matrix(m, translation).map_or_else(|| invalid(path, "the transform matrix flattens space"), Ok);
+    }
+    let rotation = Point3D::new(t.rotate.0.to_radians(), t.rotate.1.to_radians(), t.rotate.2.to_radians());
+    Transform::try_new(translation, rotation, point(&t.scale)).map_or_else(|| invalid(path, "the transform's scale flattens space"), Ok)
+}
+
+fn build_profile(p: &ProfileDescription, path: &str) -> Result<Box<dyn Profile>, SceneFileError> {
+    let radius = |r: f64| {
+        if r.is_nan() || r < 0.0 {
+            return invalid(path, "a profile's radius can't be negative");
+        }
+        Ok(r)
+    };
+    Ok(match p {
+        ProfileDescription::Circle { center, radius: r } => Box::new(Circle::new(point2(center), radius(*r)?)),
+        ProfileDescription::Rectangle { center, half_extents } => {
+            if !(half_extents.0 >= 0.0 && half_extents.1 >= 0.0) {
+                return invalid(path, "a rectangle's half extents can't be negative");
+            }
+            Box::new(Rectangle::new(point2(center), point2(half_extents)))
+        }
+        ProfileDescription::Polygon { vertices } => {
+            if vertices.len() < 3 {
+                return invalid(path, &format!("a polygon needs at least 3 vertices, found {}", vertices.len()));
+            }
+            Box::new(Polygon::new(vertices.iter().map(point2).collect()))
+        }
+        ProfileDescription::Segment { start, end, radius: r } => Box::new(Segment::new(point2(start), point2(end), radius(*r)?)),
+        ProfileDescription::QuadraticBezier { start, control, end, radius: r } => {
+            Box::new(QuadraticBezier::new(point2(start), point2(control), point2(end), radius(*r)?))
+        }
+        ProfileDescription::Arc { center, arc_radius, start, sweep, radius: r } => {
+            Box::new(Arc::new(point2(center), radius(*arc_radius)?, start.to_radians(), sweep.to_radians(), radius(*r)?))
+        }
+    })
+}
+
+// Turns descriptions into scene objects, looking up shared materials by name
+struct Builder<'a> {
+    materials: &'a BTreeMap<String, MaterialDescription>,
+}
+
+impl Builder<'_> {
+    fn add_node(&self, scene: &mut Scene, parent: NodeId, node: &NodeDescription, path: &str) -> Result<(), SceneFileError> {
+        let transform = build_transform(&node.transform, &format!("{}.transform", path))?;
+        let object = match &node.object {
+            Some(o) => Some(self.object(o, &format!("{}.object", path))?),
+            None => None,
+        };
+        let id = scene.add_node(parent, &node.name, transform, object).unwrap();
+        if !node.visible {
+            scene.set_visible(id, false).unwrap();
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            self.add_node(scene, id, child, &node_path(path, i, child))?;
+        }
+        Ok(())
+    }
+
+    fn material(&self, m: &Option<MaterialRef>, path: &str) -> Result<Option<SurfaceMaterial>, SceneFileError> {
+        let description = match m {
+            None => return Ok(None),
+            Some(MaterialRef::Inline(description)) => description,
+            Some(MaterialRef::Named(name)) => match self.materials.get(name) {
+                Some(description) => description,
+                None => return invalid(path, &format!("there is no material named \"{}\"", name)),
+            },
+        };
+        let (r, g, b) = description.albedo;
+        if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return invalid(path, "albedo components must be between 0 and 1");
+        }
+        if !(0.0..=1.0).contains(&description.metallic) || !(0.0..=1.0).contains(&description.roughness) {
+            return invalid(path, "metallic and roughness must be between 0 and 1");
+        }
+        let (er, eg, eb) = description.emission;
+        if er < 0.0 || eg < 0.0 || eb < 0.0 {
+            return invalid(path, "emission can't be negative");
+        }
+        if !(0.0..=1.0).contains(&description.transmission) {
+            return invalid(path, "transmission must be between 0 and 1");
+        }
+        if description.ior.is_nan() || description.ior < 1.0 {
+            return invalid(path, "ior must be at least 1");
+        }
+        let (ar, ag, ab) = description.absorption;
+        if ar < 0.0 || ag < 0.0 || ab < 0.0 {
+            return invalid(path, "absorption can't be negative");
+        }
+        let sm = SurfaceMaterial::new(Color::new(r, g, b), description.metallic, description.roughness)
+            .with_emission(Spectrum::new(er, eg, eb))
+            .with_transmission(description.transmission, description.ior)
+            .with_absorption(Spectrum::new(ar, ag, ab));
+        Ok(Some(sm))
+    }
+
+    fn objects(&self, list: &[ObjectDescription], path: &str) -> Result<Vec<Box<dyn SceneObject>>, SceneFileError> {
+        list.iter().enumerate().map(|(i, o)| self.object(o, &format!("{}[{}]", path, i))).collect()
+    }
+
+    fn object(&self, d: &ObjectDescription, path: &str) -> Result<Box<dyn SceneObject>, SceneFileError> {
+        let field = |name: &str| format!("{}.{}", path, name);
+        // Sizes are checked against NaN too, which would slip past a plain comparison
+        let non_negative = |name: &str, value: f64| {
+            if value.is_nan() || value < 0.0 {
+                return invalid(&field(name), &format!("{} can't be negative", name.replace('_', " ")));
+            }
+            Ok(value)
+        };
+        let positive = |name: &str, value: f64| {
+            if value.is_nan() || value <= 0.0 {
+                return invalid(&field(name), &format!("{} must be positive", name.replace('_', " ")));
+            }
+            Ok(value)
+        };
+        let non_negative3 = |name: &str, v: &Vec3| {
+            if [v.0, v.1, v.2].iter().any(|c| c.is_nan() || *c < 0.0) {
+                return invalid(&field(name), &format!("{} can't be negative", name.replace('_', " ")));
+            }
+            Ok(point(v))
+        };
+        let object: Box<dyn SceneObject> = match d {
+            ObjectDescription::Sphere { center, radius, material } => {
+                Box::new(Sphere::new(point(center), non_negative("radius", *radius)?, self.material(material, path)?))
+            }
+            ObjectDescription::Box { center, half_extents, material } => {
+                Box::new(AxisAlignedBox::new(point(center), non_negative3("half_extents", half_extents)?, self.material(material, path)?))
+            }
+            ObjectDescription::RoundedBox { center, half_extents, radius, material } => {
+                let half_extents = non_negative3("half_extents", half_extents)?;
+                Box::new(RoundedBox::new(point(center), half_extents, non_negative("radius", *radius)?, self.material(material, path)?))
+            }
+            ObjectDescription::Torus { center, major_radius, minor_radius, material } => {
+                let major_radius = non_negative("major_radius", *major_radius)?;
+                Box::new(Torus::new(point(center), major_radius, non_negative("minor_radius", *minor_radius)?, self.material(material, path)?))
+            }
+            ObjectDescription::Capsule { start, end, radius, material } => {
+                Box::new(Capsule::new(point(start), point(end), non_negative("radius", *radius)?, self.material(material, path)?))
+            }
+            ObjectDescription::CappedCylinder { center, radius, half_height, material } => {
+                let radius = non_negative("radius", *radius)?;
+                Box::new(CappedCylinder::new(point(center), radius, non_negative("half_height", *half_height)?, self.material(material, path)?))
+            }
+            ObjectDescription::InfiniteCylinder { center, radius, material } => {
+                Box::new(InfiniteCylinder::new(point(center), non_negative("radius", *radius)?, self.material(material, path)?))
+            }
+            ObjectDescription::Cone { center, height, base_radius, top_radius, material } => {
+                // A flat cone's distance divides by its height
+                let height = positive("height", *height)?;
+                let (base_radius, top_radius) = (non_negative("base_radius", *base_radius)?, non_negative("top_radius", *top_radius)?);
+                Box::new(Cone::new_capped(point(center), height, base_radius, top_radius, self.material(material, path)?))
+            }
+            ObjectDescription::Ellipsoid { center, radii, material } => {
+                // The distance divides by every radius
+                if [radii.0, radii.1, radii.2].iter().any(|r| r.is_nan() || *r <= 0.0) {
+                    return invalid(&field("radii"), "radii must be positive");
+                }
+                Box::new(Ellipsoid::new(point(center), point(radii), self.material(material, path)?))
+            }
+            ObjectDescription::HexagonalPrism { center, radius, half_length, material } => {
+                let radius = non_negative("radius", *radius)?;
+                Box::new(HexagonalPrism::new(point(center), radius, non_negative("half_length", *half_length)?, self.material(material, path)?))
+            }
+            ObjectDescription::Octahedron { center, size, material } => {
+                Box::new(Octahedron::new(point(center), non_negative("size", *size)?, self.material(material, path)?))
+            }
+            ObjectDescription::Plane { point: p, normal, material } => {
+                let normal = Vector3D::new(normal.0, normal.1, normal.2);
+                if normal.length() == 0.0 {
+                    return invalid(&field("normal"), "a plane's normal can't be zero");
+                }
+                Box::new(Plane::new(point(p), normal, self.material(material, path)?))
+            }
+            ObjectDescription::Union { children } => Box::new(Union::new(self.objects(children, &field("children"))?)),
+            ObjectDescription::Intersection { children } => Box::new(Intersection::new(self.objects(children, &field("children"))?)),
+            ObjectDescription::Subtraction { base, subtracted } => {
+                Box::new(Subtraction::new(self.object(base, &field("base"))?, self.objects(subtracted, &field("subtracted"))?))
+            }
+            ObjectDescription::SmoothUnion { a, b, k, kind } => {
+                Box::new(SmoothUnion::new(self.object(a, &field("a"))?, self.object(b, &field("b"))?, *k, *kind))
+            }
+            ObjectDescription::SmoothIntersection { a, b, k, kind } => {
+                Box::new(SmoothIntersection::new(self.object(a, &field("a"))?, self.object(b, &field("b"))?, *k, *kind))
+            }
+            ObjectDescription::SmoothSubtraction { base, subtracted, k, kind } => {
+                Box::new(SmoothSubtraction::new(self.object(base, &field("base"))?, self.object(subtracted, &field("subtracted"))?, *k, *kind))
+            }
+            ObjectDescription::Transformed { object, transform } => {
+                let transform = build_transform(transform, &field("transform"))?;
+                Box::new(Transformed::new(self.object(object, &field("object"))?, transform))
+            }
+            ObjectDescription::Twist { object, axis, rate, extent } => {
+                Box::new(Twist::new(self.object(object, &field("object"))?, *axis, *rate, *extent))
+            }
+            ObjectDescription::Bend { object, axis, curvature, extent } => {
+                Box::new(Bend::new(self.object(object, &field("object"))?, *axis, *curvature, *extent))
+            }
+            ObjectDescription::Elongate { object, half_lengths } => {
+                Box::new(Elongate::new(self.object(object, &field("object"))?, point(half_lengths)))
+            }
+            ObjectDescription::Round { object, radius } => Box::new(Round::new(self.object(object, &field("object"))?, *radius)),
+            ObjectDescription::Onion { object, thickness } => Box::new(Onion::new(self.object(object, &field("object"))?, *thickness)),
+            ObjectDescription::Repeat { object, period } => Box::new(Repeat::new(self.object(object, &field("object"))?, point(period))),
+            ObjectDescription::LimitedRepeat { object, period, count } => {
+                Box::new(LimitedRepeat::new(self.object(object, &field("object"))?, point(period), *count))
+            }
+            ObjectDescription::Mirror { object, center, axes } => {
+                Box::new(Mirror::new(self.object(object, &field("object"))?, point(center), axes))
+            }
+            ObjectDescription::PolarRepeat { object, center, axis, count } => {
+                Box::new(PolarRepeat::new(self.object(object, &field("object"))?, point(center), *axis, *count))
+            }
+            ObjectDescription::Mandelbulb { center, size, power, iterations, bailout, material, trap_material } => {
+                let mut bulb = Mandelbulb::new(point(center), *size, *power, *iterations, *bailout, self.material(material, path)?);
+                if let Some(trap) = self.material(trap_material, path)? {
+                    bulb = bulb.with_trap_material(trap);
+                }
+                Box::new(bulb)
+            }
+            ObjectDescription::Julia { center, size, constant, power, iterations, bailout, material, trap_material } => {
+                let mut julia = Julia::new(point(center), *size, *constant, *power, *iterations, *bailout, self.material(material, path)?);
+                if let Some(trap) = self.material(trap_material, path)? {
+                    julia = julia.with_trap_material(trap);
+                }
+                Box::new(julia)
+            }
+            ObjectDescription::MengerSponge { center, size, iterations, material, trap_material } => {
+                let mut sponge = MengerSponge::new(point(center), *size, *iterations, self.material(material, path)?);
+                if let Some(trap) = self.material(trap_material, path)? {
+                    sponge = sponge.with_trap_material(trap);
+                }
+                Box::new(sponge)
+            }
+            ObjectDescription::SierpinskiTetrahedron { center, size, iterations, bailout, material, trap_material } => {
+                let mut tetrahedron = SierpinskiTetrahedron::new(point(center), *size, *iterations, *bailout, self.material(material, path)?);
+                if let Some(trap) = self.material(trap_material, path)? {
+                    tetrahedron = tetrahedron.with_trap_material(trap);
+                }
+                Box::new(tetrahedron)
+            }
+            ObjectDescription::Metaballs { balls, kernel, threshold, material } => {
+                if *threshold <= 0.0 {
+                    return invalid(&field("threshold"), "the threshold must be positive");
+                }
+                if balls.iter().any(|b| b.radius <= 0.0) {
+                    return invalid(&field("balls"), "every ball needs a positive radius");
+                }
+                if balls.iter().any(|b| b.weight == 0.0) {
+                    return invalid(&field("balls"), "a ball's weight can't be zero");
+                }
+                let balls = balls.iter().map(|b| Metaball::new(point(&b.center), b.radius, b.weight)).collect();
+                Box::new(Metaballs::new(balls, *kernel, *threshold, self.material(material, path)?))
+            }
+            ObjectDescription::Tube { points, radii, path: tube_path, material, end_material } => {
+                Box::new(self.tube(points, radii, *tube_path, self.material(material, path)?, self.material(end_material, path)?, path)?)
+            }
+            ObjectDescription::Mesh { path: file, material } => {
+                Box::new(Mesh::load(file, self.material(material, path)?).map_err(|e| SceneFileError::Invalid { path: field("path"), message: e.to_string() })?)
+            }
+            ObjectDescription::VoxelGrid { path: file, interpolation, material } => {
+                let grid = VoxelGrid::load(file, self.material(material, path)?).map_err(|e| SceneFileError::Invalid { path: field("path"), message: e.to_string() })?;
+                Box::new(grid.with_interpolation(*interpolation))
+            }
+            ObjectDescription::Heightfield { image, center, extents, vertical_scale, material } => {
+                if !(extents.0 > 0.0 && extents.1 > 0.0) {
+                    return invalid(&field("extents"), "extents must be positive");
+                }
+                if vertical_scale.is_nan() || *vertical_scale < 0.0 {
+                    return invalid(&field("vertical_scale"), "the vertical scale can't be negative");
+                }
+                let terrain = Heightfield::load(point(center), *extents, *vertical_scale, image, self.material(material, path)?);
+                Box::new(terrain.map_err(|e| SceneFileError::Invalid { path: field("image"), message: e.to_string() })?)
+            }
+            ObjectDescription::Revolve { profile, center, axis, material } => {
+                Box::new(Revolve::new(build_profile(profile, &field("profile"))?, point(center), *axis, self.material(material, path)?))
+            }
+            ObjectDescription::Extrude { profile, center, axis, half_depth, material } => {
+                if half_depth.is_nan() || *half_depth < 0.0 {
+                    return invalid(&field("half_depth"), "the half depth can't be negative");
+                }
+                Box::new(Extrude::new(build_profile(profile, &field("profile"))?, point(center), *axis, *half_depth, self.material(material, path)?))
+            }
+            ObjectDescription::Text { text, center, axis, size, half_depth, weight, material } => {
+                if *size <= 0.0 || *weight <= 0.0 {
+                    return invalid(path, "text needs a positive size and weight");
+                }
+                let profile = TextProfile::new(text, *size, *weight);
+                Box::new(Text::from_profile(profile, point(center), *axis, *half_depth, self.material(material, path)?))
+            }
+            ObjectDescription::Expression { source, step_scale, bounds, material } => {
+                if *step_scale <= 0.0 || *step_scale > 1.0 {
+                    return invalid(&field("step_scale"), "the step scale must be above 0 and at most 1");
+                }
+                let mut shape = Expression::new(source, self.material(material, path)?).map_err(|e| SceneFileError::Invalid { path: field("source"), message: e.to_string() })?;
+                shape = shape.with_step_scale(*step_scale);
+                if let Some((min, max)) = bounds {
+                    shape = shape.with_bounds(BoundingBox::new(point(min), point(max)));
+                }
+                Box::new(shape)
+            }
+        };
+        Ok(object)
+    }
+
+    fn tube(&self, points: &[Vec3], radii: &[f64], tube_path: TubePath, sm: Option<SurfaceMaterial>, end: Option<SurfaceMaterial>, path: &str) -> Result<Tube, SceneFileError> {
+        let points: Vec<Point3D> = points.iter().map(point).collect();
+        let spans_ends = match tube_path {
+            TubePath::CubicBezier(_) => {
+                if points.len() < 4 || points.len() % 3 != 1 {
+                    return invalid(&format!("{}.points", path), "cubic Bezier spans need 3n + 1 points");
+                }
+                (points.len() - 1) / 3 + 1
+            }
+            _ => {
+                if points.len() < 2 {
+                    return invalid(&format!("{}.points", path), "a tube needs at least 2 points");
+                }
+                points.len()
+            }
+        };
+        // A single radius is shared by the whole tube
+        let radii = match radii.len() {
+            1 => vec![radii[0]; spans_ends],
+            n if n == spans_ends => radii.to_vec(),
+            _ => return invalid(&format!("{}.radii", path), &format!("expected 1 or {} radii, found {}", spans_ends, radii.len())),
+        };
+        if radii.iter().any(|r| r.is_nan() || *r < 0.0) {
+            return invalid(&format!("{}.radii", path), "a tube's radii can't be negative");
+        }
+        let tube = match tube_path {
+            TubePath::Polyline => Tube::new(points, radii, sm),
+            TubePath::CatmullRom(segments) => Tube::catmull_rom(&points, &radii, segments.max(1), sm),
+            TubePath::CubicBezier(segments) => Tube::cubic_bezier(&points, &radii, segments.max(1), sm),
+        };
+        Ok(match end {
+            Some(end) => tube.with_end_material(end),
+            None => tube,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+    use super::super::scene_objects::deformations::Displace;
+    use super::super::scene_objects::metaballs::FalloffKernel;
+    use super::super::scene_objects::smooth::BlendKind;
+    use super::super::scene_objects::voxel_grid::Interpolation;
+    use super::super::threed_data_types::Axis;
+
+    const TWO_SPHERES: &str = r#"
+        (
+            camera: (position: (0, 0, 0), direction: (2, 0, 0), view_angle: 60, resolution: (64, 64)),
+            render: (iterations: 3),
+            materials: {
+                "red": (albedo: (1, 0, 0), metallic: 1, roughness: 0),
+            },
+            nodes: [
+                (name: "ball", object: Sphere(center: (30, -10, 0), radius: 10, material: "red")),
+                (
+                    name: "group",
+                    transform: (translate: (0, 20, 0), scale: (2, 2, 2)),
+                    children: [
+                        (name: "blue", object: Sphere(center: (15, -5, 0), radius: 5, material: (albedo: (0, 0, 1)))),
+                    ],
+                ),
+            ],
+        )
+    "#;
+
+    #[test]
+    fn test_parse_scene() {
+        let file = SceneFile::parse(TWO_SPHERES).unwrap();
+        assert_eq!(file.camera.view_direction, Vector3D::new(1.0, 0.0, 0.0));
+        assert_close(file.camera.camera_angle, 60_f64.to_radians());
+        assert_eq!(file.render.iterations, 3);
+        assert_eq!(file.render.steps, RenderSettings::default().steps);
+
+        let scene = &file.scene;
+        let ball = scene.get_node(scene.find("ball").unwrap()).unwrap();
+        assert_eq!(ball.get_surface_material(), SurfaceMaterial::mirror(Color::new(1.0, 0.0, 0.0)));
+        // The group doubles the blue sphere and moves it up
+        let blue = scene.get_node(scene.find("blue").unwrap()).unwrap();
+        assert_eq!(*blue.get_position(), Point3D::new(30.0, 10.0, 0.0));
+        assert_close(blue.signed_distance(&Point3D::new(30.0, 30.0, 0.0)), 10.0);
+        assert_eq!(blue.get_surface_material(), SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0)));
+        assert_close(scene.get_min_distance(&Point3D::new(0.0, 0.0, 0.0)).unwrap(), 1000_f64.sqrt() - 10.0);
+    }
+
+    #[test]
+    fn test_example_scene_file() {
+        let file = SceneFile::parse(include_str!("../../scenes/two_spheres.ron")).unwrap();
+        assert_eq!(file.scene.len(), 2);
+        assert_eq!(file.camera.resolution, (600, 600));
+        let blue = file.scene.get_node(file.scene.find("blue").unwrap()).unwrap();
+        assert_eq!(blue.get_surface_material().albedo, Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_light_sources_scene_file() {
+        let file = SceneFile::parse(include_str!("../../scenes/lights.ron")).unwrap();
+        assert_eq!(file.scene.get_lights().len(), 3);
+        assert_eq!(file.scene.len(), 3);
+    }
+
+    #[test]
+    fn test_lit_interior_scene_file() {
+        let file = SceneFile::parse(include_str!("../../scenes/neon_room.ron")).unwrap();
+        assert_eq!(file.render.sky, (0.0, 0.0, 0.0));
+        let orb = file.scene.get_node(file.scene.find("orb").unwrap()).unwrap();
+        assert!(orb.get_surface_material().is_emissive());
+        let marcher = file.into_marcher();
+        assert!(marcher.get_sky().is_black());
+    }
+
+    #[test]
+    fn test_syntax_error_position() {
+        let text = "(\n    camera: (position: (0, 0, 0), direction: (1, 0, 0), view_angle: 60, resolution: (8, 8)),\n    nodes: [(object: Sphere(center: (0, 0, 0), radus: 1))],\n)";
+        match SceneFile::parse(text) {
+            Err(SceneFileError::Syntax { line, column, message }) => {
+                assert_eq!(line, 3);
+                assert!(column > 30, "column {}", column);
+                assert!(message.contains("radus"), "{}", message);
+            }
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_scene_errors() {
+        let with_nodes = |nodes: &str| format!("(camera: (position: (0, 0, 0), direction: (1, 0, 0), view_angle: 60, resolution: (8, 8)), nodes: [{}])", nodes);
+        let error = |nodes: &str| SceneFile::parse(&with_nodes(nodes)).err().unwrap().to_string();
+
+        assert_eq!(
+            error(r#"(name: "ball", object: Sphere(center: (0, 0, 0), radius: 1, material: "gold"))"#),
+            "nodes[0] \"ball\".object: there is no material named \"gold\""
+        );
+        assert_eq!(
+            error(r#"(), (children: [(transform: (scale: (1, 0, 1)))])"#),
+            "nodes[1].children[0].transform: the transform's scale flattens space"
+        );
+        assert_eq!(
+            error(r#"(transform: (scale: (0.0001, 0.0001, 0.0001)))"#),
+            "nodes[0].transform: the transform's scale flattens space"
+        );
+        assert_eq!(
+            error(r#"(transform: (matrix: Some(((1, 2, 0), (2, 4, 0), (0, 0, 1)))))"#),
+            "nodes[0].transform: the transform matrix flattens space"
+        );
+        assert_eq!(error(r#"(transform: (translate: (NaN, 0, 0)))"#), "nodes[0].transform: a transform's values must be finite");
+        assert_eq!(error(r#"(transform: (rotate: (0, inf, 0)))"#), "nodes[0].transform: a transform's values must be finite");
+        assert_eq!(
+            error(r#"(object: Tube(points: [(0, 0, 0), (1, 0, 0), (2, 0, 0)], radii: [1, 2]))"#),
+            "nodes[0].object.radii: expected 1 or 3 radii, found 2"
+        );
+        assert_eq!(
+            error(r#"(object: Tube(points: [(0, 0, 0), (1, 0, 0)], radii: [0.5, -0.1]))"#),
+            "nodes[0].object.radii: a tube's radii can't be negative"
+        );
+        assert_eq!(error(r#"(object: Sphere(center: (0, 0, 0), radius: -1))"#), "nodes[0].object.radius: radius can't be negative");
+        assert_eq!(error(r#"(object: Sphere(center: (0, 0, 0), radius: NaN))"#), "nodes[0].object.radius: radius can't be negative");
+        assert_eq!(
+            error(r#"(object: Box(center: (0, 0, 0), half_extents: (1, -1, 1)))"#),
+            "nodes[0].object.half_extents: half extents can't be negative"
+        );
+        assert_eq!(
+            error(r#"(object: Torus(center: (0, 0, 0), major_radius: 2, minor_radius: -0.5))"#),
+            "nodes[0].object.minor_radius: minor radius can't be negative"
+        );
+        assert_eq!(
+            error(r#"(object: Cone(center: (0, 0, 0), height: 0, base_radius: 1, top_radius: 1))"#),
+            "nodes[0].object.height: height must be positive"
+        );
+        assert_eq!(error(r#"(object: Ellipsoid(center: (0, 0, 0), radii: (1, 0, 2)))"#), "nodes[0].object.radii: radii must be positive");
+        assert_eq!(
+            error(r#"(object: Union(children: [Sphere(center: (0, 0, 0), radius: 1), Text(text: "A", center: (0, 0, 0), axis: Z, size: 0, half_depth: 1)]))"#),
+            "nodes[0].object.children[1]: text needs a positive size and weight"
+        );
+        assert_eq!(
+            error(r#"(object: Expression(source: "smin(sphere(p, 1), box(p, 1), 0.2)"))"#),
+            "nodes[0].object.source: type error at 1:20: no version of `box` takes (vec3, float), it takes (vec3, vec3)"
+        );
+        assert_eq!(
+            error(r#"(object: Sphere(center: (0, 0, 0), radius: 1, material: (albedo: (1, 1, 1), transmission: 1, ior: 0.5)))"#),
+            "nodes[0].object: ior must be at least 1"
+        );
+        assert_eq!(
+            error(r#"(object: Metaballs(balls: [(center: (0, 0, 0), radius: 1, weight: 0)], kernel: Wyvill, threshold: 0.5))"#),
+            "nodes[0].object.balls: a ball's weight can't be zero"
+        );
+        assert!(error(r#"(object: Mesh(path: "missing.obj"))"#).starts_with("nodes[0].object.path: could not read mesh: "));
+        assert!(error(r#"(object: Heightfield(image: "missing.png", center: (0, 0, 0), extents: (1, 1), vertical_scale: 1))"#).starts_with("nodes[0].object.image: "));
+        assert_eq!(
+            error(r#"(object: Heightfield(image: "missing.png", center: (0, 0, 0), extents: (1, 0), vertical_scale: 1))"#),
+            "nodes[0].object.extents: extents must be positive"
+        );
+        assert_eq!(
+            error(r#"(object: Extrude(profile: Polygon(vertices: [(0, 0), (1, 0)]), center: (0, 0, 0), axis: Z, half_depth: 1))"#),
+            "nodes[0].object.profile: a polygon needs at least 3 vertices, found 2"
+        );
+        assert_eq!(
+            error(r#"(object: Revolve(profile: Circle(center: (2, 0), radius: -1), center: (0, 0, 0), axis: Y))"#),
+            "nodes[0].object.profile: a profile's radius can't be negative"
+        );
+        let with_lights = |lights: &str| format!("(camera: (position: (0, 0, 0), direction: (1, 0, 0), view_angle: 60, resolution: (8, 8)), lights: [{}])", lights);
+        let light_error = |lights: &str| SceneFile::parse(&with_lights(lights)).err().unwrap().to_string();
+        assert_eq!(
+            light_error("Point(position: (0, 0, 0), intensity: 1), Spot(position: (0, 0, 0), direction: (0, 0, -1), inner_angle: 40, outer_angle: 30, intensity: 1)"),
+            "lights[1]: spot angles must satisfy 0 <= inner_angle <= outer_angle < 180"
+        );
+        assert_eq!(light_error("Directional(direction: (0, 0, 0), intensity: 1)"), "lights[0]: the direction can't be zero");
+        assert_eq!(
+            light_error("Point(position: (0, 0, 0), intensity: 1, shadow: Soft(k: 0))"),
+            "lights[0].shadow: the penumbra hardness k must be positive"
+        );
+    }
+
+    fn assert_same_scene(a: &Scene, b: &Scene) {
+        let a_nodes: Vec<_> = a.iter().map(|(_, n)| n).collect();
+        let b_nodes: Vec<_> = b.iter().map(|(_, n)| n).collect();
+        assert_eq!(a_nodes.len(), b_nodes.len());
+        for (x, y) in a_nodes.iter().zip(b_nodes.iter()) {
+            assert_eq!(x.get_name(), y.get_name());
+            assert_eq!(x.is_shown(), y.is_shown());
+            assert_eq!(x.get_children().len(), y.get_children().len());
+            for i in 0..50 {
+                let f = i as f64;
+                let p = Point3D::new((f * 1.3).sin() * 12.0, (f * 0.7).cos() * 12.0, (f * 2.1).sin() * 12.0);
+                assert_close(x.signed_distance(&p), y.signed_distance(&p));
+                assert_eq!(x.get_surface_material_at(&p), y.get_surface_material_at(&p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_round_trip() {
+        let red = SurfaceMaterial::new(Color::new(1.0, 0.0, 0.0), 0.5, 0.25).with_emission(Spectrum::new(2.0, 0.0, 0.0));
+        let glass = SurfaceMaterial::glass(1.33).with_absorption(Spectrum::new(0.1, 0.05, 0.0));
+        let mut scene = Scene::new();
+        let group = scene.add_node(scene.root(), "group", Transform::new(Point3D::new(1.0, 2.0, 3.0), Point3D::new(0.3, -0.2, 1.1), Point3D::new(1.0, 2.0, 0.5)), None).unwrap();
+        let carved = Subtraction::new(
+            Box::new(RoundedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 1.0, 1.0), 0.2, Some(red))),
+            vec![Box::new(Sphere::new(Point3D::new(1.0, 0.0, 0.0), 1.2, Some(glass)))],
+        );
+        scene.add_node(group, "carved", Transform::identity(), Some(Box::new(carved))).unwrap();
+        let blend = SmoothUnion::new(
+            Box::new(Torus::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5, None)),
+            Box::new(Twist::new(Cone::new_capped(Point3D::new(0.0, 1.0, 0.0), 2.0, 1.0, 0.5, Some(red)), Axis::Y, 0.5, 1.5)),
+            0.4,
+            BlendKind::Exponential,
+        );
+        scene.add_node(scene.root(), "blend", Transform::from_translation(Point3D::new(-3.0, 0.0, 0.0)), Some(Box::new(blend))).unwrap();
+        let hidden = scene.add_scene_object(PolarRepeat::new(Capsule::new(Point3D::new(0.0, 2.0, 0.0), Point3D::new(0.0, 3.0, 1.0), 0.3, None), Point3D::new(0.0, 0.0, 0.0), Axis::X, 5));
+        scene.set_visible(hidden, false).unwrap();
+        scene.add_scene_object(Metaballs::new(vec![Metaball::new(Point3D::new(0.0, 0.0, 4.0), 2.0, 1.0), Metaball::new(Point3D::new(1.0, 0.0, 4.0), 1.5, -0.5)], FalloffKernel::Wyvill, 0.3, None));
+        scene.add_scene_object(Tube::with_radius(vec![Point3D::new(0.0, -4.0, 0.0), Point3D::new(2.0, -4.0, 1.0), Point3D::new(3.0, -5.0, 1.0)], 0.25, None).with_end_material(red));
+        scene.add_scene_object(MengerSponge::new(Point3D::new(5.0, 5.0, 5.0), 1.5, 3, None).with_trap_material(red));
+        scene.add_scene_object(Text::new("HI", Point3D::new(0.0, 6.0, 0.0), Axis::Z, 1.0, 0.2, Some(red)));
+        scene.add_scene_object(Revolve::new(Circle::new(Point2D::new(2.0, 0.0), 0.5), Point3D::new(6.0, -6.0, 0.0), Axis::Y, Some(glass)));
+        let arc = Arc::new(Point2D::new(0.0, 0.0), 1.0, 0.5, -2.0, 0.1);
+        scene.add_scene_object(Extrude::new(arc, Point3D::new(-6.0, -6.0, 0.0), Axis::X, 0.5, None));
+        let bounds = BoundingBox::new(Point3D::new(-7.0, -1.0, -1.0), Point3D::new(-5.0, 1.0, 1.0));
+        scene.add_scene_object(Expression::new("smin(sphere(p - vec3(-6, 0, 0), 1), box(p - vec3(-6, 1, 0), vec3(.5)), 0.2)", Some(red)).unwrap().with_step_scale(0.8).with_bounds(bounds));
+        // Shear can't be split into a rotation and scale, so it is saved as a matrix
+        let shear = Transform::from_matrix([[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], Point3D::new(0.0, 0.0, -6.0));
+        scene.add_scene_object(Transformed::new(Elongate::new(Octahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None), Point3D::new(0.5, 0.0, 0.0)), shear));
+        scene.add_light(PointLight::new(Point3D::new(0.0, 5.0, 0.0), Color::new(1.0, 0.9, 0.8), 200.0));
+        scene.add_light(DirectionalLight::new(Vector3D::new(1.0, -1.0, -1.0), Color::new(1.0, 1.0, 1.0), 2.0).with_shadow(Shadow::Soft { k: 16.0 }));
+        scene.add_light(SpotLight::new(Point3D::new(0.0, 0.0, 8.0), Vector3D::new(0.0, 0.0, -1.0), 0.2, 0.5, Color::new(0.2, 0.4, 1.0), 500.0).with_shadow(Shadow::SoftImproved { k: 4.0 }));
+        scene.add_light(SphereLight::new(Point3D::new(-5.0, -5.0, 5.0), 1.5, Color::new(1.0, 0.5, 0.0), 3.0));
+
+        let camera = Camera::new(Point3D::new(-10.0, 1.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 1.0, 45_f64.to_radians(), (32, 32));
+        let render = RenderSettings { steps: 10, iterations: 2, max_distance: 100.0, output: "round_trip.png".to_string(), sky: (0.0, 0.0, 0.1) };
+        let text = to_ron(&scene, &camera, &render).unwrap();
+        let loaded = SceneFile::parse(&text).unwrap();
+        assert_same_scene(&scene, &loaded.scene);
+        assert_eq!(loaded.render, render);
+        assert_eq!(loaded.camera.position, camera.position);
+        assert_close(loaded.camera.camera_angle, camera.camera_angle);
+        assert!(text.contains("matrix"));
+        assert!(text.contains("rotate"));
+        assert!(text.contains("SoftImproved"));
+        // Spot angles go through degrees, so compare with some rounding
+        let lights = |s: &Scene| format!("{:.9?}", s.get_lights().iter().map(|l| l.describe().unwrap()).collect::<Vec<_>>());
+        assert_eq!(lights(&loaded.scene), lights(&scene));
+        assert_eq!(loaded.scene.get_lights().len(), 4);
+    }
+
+    #[test]
+    fn test_save_round_trip_of_files() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let obj = dir.join(format!("ray_marcher_scene_mesh_{}.obj", id));
+        fs::write(&obj, "v 0 0 0\nv 0 1 0\nv 1 0 0\nv 0 0 1\nf 1 2 3\nf 1 3 4\nf 1 4 2\nf 2 4 3\n").unwrap();
+        let grid = dir.join(format!("ray_marcher_scene_grid_{}.sdf", id));
+        let bounds = BoundingBox::new(Point3D::new(-2.0, -2.0, -2.0), Point3D::new(2.0, 2.0, 2.0));
+        VoxelGrid::bake(&Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.5, None), bounds, [5, 5, 5], None).unwrap().save(&grid).unwrap();
+        let png = dir.join(format!("ray_marcher_scene_terrain_{}.png", id));
+        image::GrayImage::from_fn(4, 3, |x, y| image::Luma([(x * 60 + y * 20) as u8])).save(&png).unwrap();
+
+        let mut scene = Scene::new();
+        let red = SurfaceMaterial::diffuse(Color::new(1.0, 0.0, 0.0));
+        scene.add_scene_object(Mesh::load(&obj, Some(red)).unwrap());
+        scene.add_scene_object(VoxelGrid::load(&grid, None).unwrap().with_interpolation(Interpolation::Tricubic));
+        scene.add_scene_object(Heightfield::load(Point3D::new(0.0, -5.0, 0.0), (4.0, 3.0), 2.0, &png, None).unwrap());
+        let camera = Camera::new(Point3D::new(-10.0, 1.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 1.0, 1.0, (8, 8));
+        let text = to_ron(&scene, &camera, &RenderSettings::default()).unwrap();
+        let loaded = SceneFile::parse(&text);
+        for path in [&obj, &grid, &png] {
+            fs::remove_file(path).unwrap();
+        }
+        assert_same_scene(&scene, &loaded.unwrap().scene);
+        assert!(text.contains("Tricubic"));
+
+        // Objects built in code have no file to point at
+        let mut scene = Scene::new();
+        scene.add_scene_object(Heightfield::from_image(Point3D::new(0.0, 0.0, 0.0), (1.0, 1.0), 1.0, &image::GrayImage::new(2, 2), None));
+        assert!(to_ron(&scene, &camera, &RenderSettings::default()).is_err());
+    }
+
+    #[test]
+    fn test_save_unsupported_object() {
+        let mut scene = Scene::new();
+        let group = scene.add_node(scene.root(), "group", Transform::identity(), None).unwrap();
+        scene.add_node(group, "", Transform::identity(), Some(Box::new(Displace::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None), Box::new(|_p: &Point3D| 0.0), 1.0)))).unwrap();
+        let camera = Camera::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 1.0, 1.0, (8, 8));
+        let error = to_ron(&scene, &camera, &RenderSettings::default()).err().unwrap();
+        assert_eq!(error.to_string(), "nodes[0].children[0].object: this object can't be written to a scene file");
+    }
+}
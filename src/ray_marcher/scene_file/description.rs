@@ -0,0 +1,645 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::super::lights::Shadow;
+use super::super::marcher::MAX_DISTANCE;
+use super::super::scene_objects::{SurfaceMaterial, DEFAULT_SURFACEMAT};
+use super::super::scene_objects::metaballs::FalloffKernel;
+use super::super::scene_objects::profiles::Point2D;
+use super::super::scene_objects::smooth::BlendKind;
+use super::super::scene_objects::text::DEFAULT_WEIGHT;
+use super::super::scene_objects::voxel_grid::Interpolation;
+use super::super::threed_data_types::transform::Transform;
+use super::super::threed_data_types::Axis;
+use super::Point3D;
+
+// The plain data written to and read from scene files. Everything here maps one to one
+// onto the RON text, so field names are what people type.
+
+pub type Vec3 = (f64, f64, f64);
+pub type Vec2 = (f64, f64);
+
+pub fn vec3(p: &Point3D) -> Vec3 {
+    (p.x, p.y, p.z)
+}
+
+pub fn vec2(p: &Point2D) -> Vec2 {
+    (p.x, p.y)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub materials: BTreeMap<String, MaterialDescription>, // Shared materials, referred to by name
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>, // Children of the root
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: Vec3,
+    pub direction: Vec3,
+    #[serde(default = "default_near_plane")]
+    pub near_plane: f64,
+    pub view_angle: f64, // Degrees from the center line to the edge of the view
+    pub resolution: (u32, u32),
+}
+
+fn default_near_plane() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub steps: u32, // March steps per pass
+    pub iterations: u32, // Passes averaged into the image
+    pub max_distance: f64,
+    pub output: String, // Where the image is written
+    pub sky: Vec3, // Light from rays that leave the scene, black for interiors
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings { steps: 2000, iterations: 100, max_distance: MAX_DISTANCE, output: "ray_marched.png".to_string(), sky: (1.0, 1.0, 1.0) }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDescription {
+    pub albedo: Vec3,
+    #[serde(default)]
+    pub metallic: f64,
+    #[serde(default = "default_roughness")]
+    pub roughness: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub emission: Vec3,
+    #[serde(default, skip_serializing_if = "is_opaque")]
+    pub transmission: f64,
+    #[serde(default = "default_ior", skip_serializing_if = "is_default_ior")]
+    pub ior: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub absorption: Vec3, // Per unit of distance travelled inside
+}
+
+fn default_roughness() -> f64 {
+    1.0
+}
+
+fn is_opaque(transmission: &f64) -> bool {
+    *transmission == 0.0
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+fn is_default_ior(ior: &f64) -> bool {
+    *ior == default_ior()
+}
+
+// Lights placed in world space, each with a color and an intensity
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum LightDescription {
+    Point {
+        position: Vec3,
+        #[serde(default = "default_white", skip_serializing_if = "is_one")]
+        color: Vec3,
+        intensity: f64,
+        #[serde(default, skip_serializing_if = "is_hard")]
+        shadow: Shadow,
+    },
+    Directional {
+        direction: Vec3, // The way the light travels
+        #[serde(default = "default_white", skip_serializing_if = "is_one")]
+        color: Vec3,
+        intensity: f64,
+        #[serde(default, skip_serializing_if = "is_hard")]
+        shadow: Shadow,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        inner_angle: f64, // Degrees from the axis to where the light starts to fade
+        outer_angle: f64, // Degrees from the axis to where it is gone
+        #[serde(default = "default_white", skip_serializing_if = "is_one")]
+        color: Vec3,
+        intensity: f64,
+        #[serde(default, skip_serializing_if = "is_hard")]
+        shadow: Shadow,
+    },
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        #[serde(default = "default_white", skip_serializing_if = "is_one")]
+        color: Vec3,
+        intensity: f64, // How bright the surface of the ball is
+        #[serde(default, skip_serializing_if = "is_hard")]
+        shadow: Shadow,
+    },
+}
+
+fn default_white() -> Vec3 {
+    (1.0, 1.0, 1.0)
+}
+
+fn is_hard(shadow: &Shadow) -> bool {
+    *shadow == Shadow::Hard
+}
+
+// Either the name of a shared material or one written out in place
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Named(String),
+    Inline(MaterialDescription),
+}
+
+impl MaterialRef {
+    // Written out in place, or left out when it is the default material
+    pub fn describe(sm: &SurfaceMaterial) -> Option<Self> {
+        if *sm == DEFAULT_SURFACEMAT {
+            return None;
+        }
+        Some(MaterialRef::inline(sm))
+    }
+
+    pub fn inline(sm: &SurfaceMaterial) -> Self {
+        let emission = (sm.emission.r, sm.emission.g, sm.emission.b);
+        let absorption = (sm.absorption.r, sm.absorption.g, sm.absorption.b);
+        MaterialRef::Inline(MaterialDescription {
+            albedo: sm.albedo.get_components(),
+            metallic: sm.metallic,
+            roughness: sm.roughness,
+            emission,
+            transmission: sm.transmission,
+            ior: sm.ior,
+            absorption,
+        })
+    }
+}
+
+// Scales, then rotates around X, Y and Z (in degrees), then translates. A matrix, when
+// given, replaces the rotation and scale.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TransformDescription {
+    #[serde(skip_serializing_if = "is_zero")]
+    pub translate: Vec3,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub rotate: Vec3,
+    #[serde(skip_serializing_if = "is_one")]
+    pub scale: Vec3,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<[[f64; 3]; 3]>,
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        TransformDescription { translate: (0.0, 0.0, 0.0), rotate: (0.0, 0.0, 0.0), scale: (1.0, 1.0, 1.0), matrix: None }
+    }
+}
+
+impl TransformDescription {
+    pub fn is_identity(&self) -> bool {
+        *self == TransformDescription::default()
+    }
+
+    // Splits the transform back into a scale and rotation when it is made of them, and
+    // falls back to the matrix otherwise
+    pub fn from_transform(t: &Transform) -> Self {
+        let m = t.get_matrix();
+        let translate = vec3(t.get_translation());
+        if *t == Transform::from_translation(*t.get_translation()) {
+            return TransformDescription { translate, ..Default::default() };
+        }
+        let column = |c: usize| Point3D::new(m[0][c], m[1][c], m[2][c]);
+        let (x, y, z) = (column(0), column(1), column(2));
+        // A mirrored transform keeps its reflection in the X scale
+        let flip = if x.cross(&y).dot(&z) < 0.0 { -1.0 } else { 1.0 };
+        let scale = Point3D::new(x.length() * flip, y.length(), z.length());
+        let r = [0, 1, 2].map(|row| [m[row][0] / scale.x, m[row][1] / scale.y, m[row][2] / scale.z]);
+
+        // The rotation is Rz * Ry * Rx
+        let around_y = (-r[2][0]).clamp(-1.0, 1.0).asin();
+        let (around_x, around_z) = if r[2][0].abs() < 1.0 - 1e-12 {
+            (r[2][1].atan2(r[2][2]), r[1][0].atan2(r[0][0]))
+        } else {
+            ((-r[1][2]).atan2(r[1][1]), 0.0)
+        };
+        let rotation = Point3D::new(around_x, around_y, around_z);
+        let rebuilt = Transform::new(*t.get_translation(), rotation, scale);
+        let matches = rebuilt.get_matrix().iter().flatten().zip(m.iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-9);
+        if !matches {
+            return TransformDescription { translate, matrix: Some(*m), ..Default::default() };
+        }
+        let rotate = (around_x.to_degrees(), around_y.to_degrees(), around_z.to_degrees());
+        TransformDescription { translate, rotate, scale: vec3(&scale), matrix: None }
+    }
+}
+
+fn is_zero(v: &Vec3) -> bool {
+    *v == (0.0, 0.0, 0.0)
+}
+
+fn is_one(v: &Vec3) -> bool {
+    *v == (1.0, 1.0, 1.0)
+}
+
+fn is_true(b: &bool) -> bool {
+    *b
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeDescription {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(default, skip_serializing_if = "TransformDescription::is_identity")]
+    pub transform: TransformDescription,
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub visible: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<ObjectDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetaballDescription {
+    pub center: Vec3,
+    pub radius: f64,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+// How a tube's points are joined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum TubePath {
+    #[default]
+    Polyline,
+    CatmullRom(u32), // Segments per span
+    CubicBezier(u32),
+}
+
+fn is_polyline(path: &TubePath) -> bool {
+    *path == TubePath::Polyline
+}
+
+// 2D shapes for Revolve and Extrude, following the constructors in profiles
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ProfileDescription {
+    Circle {
+        center: Vec2,
+        radius: f64,
+    },
+    Rectangle {
+        center: Vec2,
+        half_extents: Vec2,
+    },
+    Polygon {
+        vertices: Vec<Vec2>,
+    },
+    Segment {
+        start: Vec2,
+        end: Vec2,
+        radius: f64,
+    },
+    QuadraticBezier {
+        start: Vec2,
+        control: Vec2,
+        end: Vec2,
+        radius: f64,
+    },
+    Arc {
+        center: Vec2,
+        arc_radius: f64,
+        start: f64, // Degrees counter clockwise from +x
+        sweep: f64, // Degrees, clockwise when negative
+        radius: f64,
+    },
+}
+
+fn is_trilinear(interpolation: &Interpolation) -> bool {
+    *interpolation == Interpolation::Trilinear
+}
+
+fn default_trilinear() -> Interpolation {
+    Interpolation::Trilinear
+}
+
+fn default_text_weight() -> f64 {
+    DEFAULT_WEIGHT
+}
+
+fn default_step_scale() -> f64 {
+    1.0
+}
+
+fn is_full_step(step_scale: &f64) -> bool {
+    *step_scale == 1.0
+}
+
+fn default_polynomial() -> BlendKind {
+    BlendKind::Polynomial
+}
+
+// Every object the format knows about. The fields follow the constructors of the
+// matching scene objects.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Box {
+        center: Vec3,
+        half_extents: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    RoundedBox {
+        center: Vec3,
+        half_extents: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Torus {
+        center: Vec3,
+        major_radius: f64,
+        minor_radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    CappedCylinder {
+        center: Vec3,
+        radius: f64,
+        half_height: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    InfiniteCylinder {
+        center: Vec3,
+        radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Cone {
+        center: Vec3,
+        height: f64,
+        base_radius: f64,
+        #[serde(default)]
+        top_radius: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Ellipsoid {
+        center: Vec3,
+        radii: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    HexagonalPrism {
+        center: Vec3,
+        radius: f64,
+        half_length: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Octahedron {
+        center: Vec3,
+        size: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Union {
+        children: Vec<ObjectDescription>,
+    },
+    Intersection {
+        children: Vec<ObjectDescription>,
+    },
+    Subtraction {
+        base: Box<ObjectDescription>,
+        subtracted: Vec<ObjectDescription>,
+    },
+    SmoothUnion {
+        a: Box<ObjectDescription>,
+        b: Box<ObjectDescription>,
+        k: f64,
+        #[serde(default = "default_polynomial")]
+        kind: BlendKind,
+    },
+    SmoothIntersection {
+        a: Box<ObjectDescription>,
+        b: Box<ObjectDescription>,
+        k: f64,
+        #[serde(default = "default_polynomial")]
+        kind: BlendKind,
+    },
+    SmoothSubtraction {
+        base: Box<ObjectDescription>,
+        subtracted: Box<ObjectDescription>,
+        k: f64,
+        #[serde(default = "default_polynomial")]
+        kind: BlendKind,
+    },
+    Transformed {
+        object: Box<ObjectDescription>,
+        transform: TransformDescription,
+    },
+    Twist {
+        object: Box<ObjectDescription>,
+        axis: Axis,
+        rate: f64,
+        extent: f64,
+    },
+    Bend {
+        object: Box<ObjectDescription>,
+        axis: Axis,
+        curvature: f64,
+        extent: f64,
+    },
+    Elongate {
+        object: Box<ObjectDescription>,
+        half_lengths: Vec3,
+    },
+    Round {
+        object: Box<ObjectDescription>,
+        radius: f64,
+    },
+    Onion {
+        object: Box<ObjectDescription>,
+        thickness: f64,
+    },
+    Repeat {
+        object: Box<ObjectDescription>,
+        period: Vec3,
+    },
+    LimitedRepeat {
+        object: Box<ObjectDescription>,
+        period: Vec3,
+        count: (u32, u32, u32),
+    },
+    Mirror {
+        object: Box<ObjectDescription>,
+        center: Vec3,
+        axes: Vec<Axis>,
+    },
+    PolarRepeat {
+        object: Box<ObjectDescription>,
+        center: Vec3,
+        axis: Axis,
+        count: u32,
+    },
+    Mandelbulb {
+        center: Vec3,
+        size: f64,
+        power: f64,
+        iterations: u32,
+        bailout: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trap_material: Option<MaterialRef>,
+    },
+    Julia {
+        center: Vec3,
+        size: f64,
+        constant: [f64; 4],
+        power: u32,
+        iterations: u32,
+        bailout: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trap_material: Option<MaterialRef>,
+    },
+    MengerSponge {
+        center: Vec3,
+        size: f64,
+        iterations: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trap_material: Option<MaterialRef>,
+    },
+    SierpinskiTetrahedron {
+        center: Vec3,
+        size: f64,
+        iterations: u32,
+        bailout: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trap_material: Option<MaterialRef>,
+    },
+    Metaballs {
+        balls: Vec<MetaballDescription>,
+        kernel: FalloffKernel,
+        threshold: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Tube {
+        points: Vec<Vec3>,
+        radii: Vec<f64>, // One per point, or a single radius for the whole tube
+        #[serde(default, skip_serializing_if = "is_polyline")]
+        path: TubePath,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end_material: Option<MaterialRef>,
+    },
+    Mesh {
+        path: String, // An .obj or .stl file, relative to the working directory
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    VoxelGrid {
+        path: String, // A baked grid written by VoxelGrid::save
+        #[serde(default = "default_trilinear", skip_serializing_if = "is_trilinear")]
+        interpolation: Interpolation,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Heightfield {
+        image: String, // Grayscale heights, black at the base and white `vertical_scale` above
+        center: Vec3, // Center of the base
+        extents: (f64, f64), // Half sizes along x and z
+        vertical_scale: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Revolve {
+        profile: ProfileDescription,
+        center: Vec3,
+        axis: Axis,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Extrude {
+        profile: ProfileDescription,
+        center: Vec3,
+        axis: Axis,
+        half_depth: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Text {
+        text: String,
+        center: Vec3,
+        axis: Axis,
+        size: f64,
+        half_depth: f64,
+        #[serde(default = "default_text_weight")]
+        weight: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+    Expression {
+        source: String, // A distance over the point `p`, e.g. "sphere(p, 1)"
+        #[serde(default = "default_step_scale", skip_serializing_if = "is_full_step")]
+        step_scale: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bounds: Option<(Vec3, Vec3)>, // Min and max corners
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialRef>,
+    },
+}
@@ -1,10 +1,29 @@
 pub mod objects;
+pub mod csg;
+pub mod smooth;
+pub mod transformed;
+pub mod repetition;
+pub mod deformations;
+pub mod fractals;
+pub mod mesh;
+pub mod voxel_grid;
+pub mod heightfield;
+pub mod textured;
+pub mod metaballs;
+pub mod profiles;
+pub mod tubes;
+pub mod text;
+pub mod expression;
 use super::Point3D;
 use super::Vector3D;
-use super::color_data_types::BLACK;
+use super::color_data_types::{BLACK, WHITE};
 use super::color_data_types::Color;
+use super::color_data_types::spectrum::Spectrum;
+use super::bsdf::Principled;
+use super::threed_data_types::bounding_box::BoundingBox;
+use super::scene_file::description::ObjectDescription;
 
-pub trait SceneObject {
+pub trait SceneObject: Send + Sync {
     fn signed_distance(&self, p: &Point3D) -> f64; // A minimum distance between the object and a point
     fn get_position(&self) -> &Point3D;
     fn get_surface_normal(&self, p: &Point3D, epsilon: f64) -> Vector3D{
@@ -16,12 +35,129 @@ pub trait SceneObject {
         (Point3D::new(x_off, y_off, z_off) - center).to_direction() / epsilon
     }
     fn get_surface_material(&self) -> SurfaceMaterial;
+    fn get_surface_material_at(&self, _p: &Point3D) -> SurfaceMaterial{ // Material of the surface closest to a point
+        self.get_surface_material()
+    }
+    fn get_step_scale(&self) -> f64{ // Fraction of the signed distance that is safe to march
+        1.0
+    }
+    fn bounding_box(&self) -> Option<BoundingBox>{ // Box holding the whole surface, None if it is unbounded
+        None
+    }
+    fn describe(&self) -> Option<ObjectDescription>{ // How the object is written to a scene file, None if it can't be
+        None
+    }
+}
+
+impl SceneObject for Box<dyn SceneObject> {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.as_ref().signed_distance(p)
+    }
+    fn get_position(&self) -> &Point3D {
+        self.as_ref().get_position()
+    }
+    fn get_surface_normal(&self, p: &Point3D, epsilon: f64) -> Vector3D {
+        self.as_ref().get_surface_normal(p, epsilon)
+    }
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.as_ref().get_surface_material()
+    }
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.as_ref().get_surface_material_at(p)
+    }
+    fn get_step_scale(&self) -> f64 {
+        self.as_ref().get_step_scale()
+    }
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.as_ref().bounding_box()
+    }
+    fn describe(&self) -> Option<ObjectDescription> {
+        self.as_ref().describe()
+    }
 }
 
+// Parameters of the metallic/roughness model, turned into a BSDF when a ray hits
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct SurfaceMaterial {
-    pub color: Color,
-    pub reflectivity: f64,
+    pub albedo: Color, // Diffuse color, or the tint of a metal's reflections
+    pub metallic: f64, // 0 for plastic, stone and the like, 1 for bare metal
+    pub roughness: f64, // 0 is a perfect mirror finish
+    pub emission: Spectrum, // Light given off, not limited to 1
+    pub transmission: f64, // Share of the surface that is clear like glass, letting rays inside
+    pub ior: f64, // Index of refraction of the inside
+    pub absorption: Spectrum, // Share of light absorbed per unit travelled inside, by Beer-Lambert
 }
-pub static DEFAULT_SURFACEMAT: SurfaceMaterial = SurfaceMaterial{ color: BLACK, reflectivity: 1.0 };
+
+#[allow(dead_code)]
+impl SurfaceMaterial {
+    pub fn new(albedo: Color, metallic: f64, roughness: f64) -> Self {
+        SurfaceMaterial { albedo, metallic, roughness, emission: Spectrum::BLACK, transmission: 0.0, ior: 1.5, absorption: Spectrum::BLACK }
+    }
+
+    pub fn diffuse(albedo: Color) -> Self {
+        SurfaceMaterial::new(albedo, 0.0, 1.0)
+    }
+
+    pub fn mirror(albedo: Color) -> Self {
+        SurfaceMaterial::new(albedo, 1.0, 0.0)
+    }
+
+    // A lamp giving off `emission`, which can be well above 1 for bright lights
+    pub fn light(emission: Spectrum) -> Self {
+        SurfaceMaterial::diffuse(BLACK).with_emission(emission)
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        !self.emission.is_black()
+    }
+
+    // Clear and smooth, like glass (1.5) or water (1.33)
+    pub fn glass(ior: f64) -> Self {
+        SurfaceMaterial { transmission: 1.0, ior, ..SurfaceMaterial::new(WHITE, 0.0, 0.0) }
+    }
+
+    // Lets `transmission` of the light through, bending it by `ior`
+    pub fn with_transmission(mut self, transmission: f64, ior: f64) -> Self {
+        self.transmission = transmission;
+        self.ior = ior;
+        self
+    }
+
+    // Tints what is seen through the surface the deeper it goes
+    pub fn with_absorption(mut self, absorption: Spectrum) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    pub fn with_emission(mut self, emission: Spectrum) -> Self {
+        self.emission = emission;
+        self
+    }
+
+    pub fn bsdf(&self) -> Principled {
+        Principled::from_material(self)
+    }
+
+    pub fn mix(m1: &Self, m2: &Self, ratio: f64) -> Self{ // A ratio of 0 gives m1, 1 gives m2
+        SurfaceMaterial {
+            albedo: Color::blend_colors(&m1.albedo, &m2.albedo, ratio),
+            metallic: m1.metallic * (1.0 - ratio) + m2.metallic * ratio,
+            roughness: m1.roughness * (1.0 - ratio) + m2.roughness * ratio,
+            emission: m1.emission * (1.0 - ratio) + m2.emission * ratio,
+            transmission: m1.transmission * (1.0 - ratio) + m2.transmission * ratio,
+            ior: m1.ior * (1.0 - ratio) + m2.ior * ratio,
+            absorption: m1.absorption * (1.0 - ratio) + m2.absorption * ratio,
+        }
+    }
+}
+
+pub static DEFAULT_SURFACEMAT: SurfaceMaterial = SurfaceMaterial{
+    albedo: BLACK,
+    metallic: 1.0,
+    roughness: 0.0,
+    emission: Spectrum::BLACK,
+    transmission: 0.0,
+    ior: 1.5,
+    absorption: Spectrum::BLACK,
+};
@@ -0,0 +1,317 @@
+use super::super::scene_file::description::ObjectDescription;
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Boolean combinations of scene objects. Every combinator picks the child whose
+// surface defines the result at the queried point, and reports that child's material.
+
+fn first_position(children: &[Box<dyn SceneObject>]) -> Point3D {
+    children
+        .first()
+        .map(|c| *c.get_position())
+        .unwrap_or(Point3D::new(0.0, 0.0, 0.0))
+}
+
+// None as soon as one child is unbounded
+fn union_bounds(children: &[Box<dyn SceneObject>]) -> Option<BoundingBox> {
+    children
+        .iter()
+        .try_fold(BoundingBox::empty(), |b, c| c.bounding_box().map(|cb| b.union(&cb)))
+}
+
+fn min_step_scale(children: &[Box<dyn SceneObject>]) -> f64 {
+    children.iter().map(|c| c.get_step_scale()).fold(1.0, f64::min)
+}
+
+pub struct Union {
+    children: Vec<Box<dyn SceneObject>>,
+    position: Point3D,
+}
+
+#[allow(dead_code)]
+impl Union {
+    pub fn new(children: Vec<Box<dyn SceneObject>>) -> Self {
+        Union { position: first_position(&children), children }
+    }
+
+    pub fn add_child<T>(&mut self, o: T) where T: SceneObject + 'static {
+        if self.children.is_empty() {
+            self.position = *o.get_position();
+        }
+        self.children.push(Box::new(o));
+    }
+
+    // The child with the smallest distance
+    fn active_child(&self, p: &Point3D) -> Option<(f64, &dyn SceneObject)> {
+        let mut active: Option<(f64, &dyn SceneObject)> = None;
+        for c in self.children.iter() {
+            let dist = c.signed_distance(p);
+            match active {
+                Some((d, _)) if d <= dist => {}
+                _ => active = Some((dist, c.as_ref())),
+            }
+        }
+        active
+    }
+}
+
+impl SceneObject for Union {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.active_child(p).map(|(d, _)| d).unwrap_or(f64::INFINITY)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.children.first().map(|c| c.get_surface_material()).unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.active_child(p)
+            .map(|(_, c)| c.get_surface_material_at(p))
+            .unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        min_step_scale(&self.children)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        union_bounds(&self.children)
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        let children = self.children.iter().map(|c| c.describe()).collect::<Option<Vec<_>>>()?;
+        Some(ObjectDescription::Union { children })
+    }
+}
+
+pub struct Intersection {
+    children: Vec<Box<dyn SceneObject>>,
+    position: Point3D,
+}
+
+#[allow(dead_code)]
+impl Intersection {
+    pub fn new(children: Vec<Box<dyn SceneObject>>) -> Self {
+        Intersection { position: first_position(&children), children }
+    }
+
+    pub fn add_child<T>(&mut self, o: T) where T: SceneObject + 'static {
+        if self.children.is_empty() {
+            self.position = *o.get_position();
+        }
+        self.children.push(Box::new(o));
+    }
+
+    // The child with the largest distance
+    fn active_child(&self, p: &Point3D) -> Option<(f64, &dyn SceneObject)> {
+        let mut active: Option<(f64, &dyn SceneObject)> = None;
+        for c in self.children.iter() {
+            let dist = c.signed_distance(p);
+            match active {
+                Some((d, _)) if d >= dist => {}
+                _ => active = Some((dist, c.as_ref())),
+            }
+        }
+        active
+    }
+}
+
+impl SceneObject for Intersection {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.active_child(p).map(|(d, _)| d).unwrap_or(f64::INFINITY)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.children.first().map(|c| c.get_surface_material()).unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.active_child(p)
+            .map(|(_, c)| c.get_surface_material_at(p))
+            .unwrap_or(DEFAULT_SURFACEMAT)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        min_step_scale(&self.children)
+    }
+
+    // Unbounded children don't limit the result, so only the bounded ones are intersected
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.children.iter().filter_map(|c| c.bounding_box()).reduce(|a, b| a.intersection(&b))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        let children = self.children.iter().map(|c| c.describe()).collect::<Option<Vec<_>>>()?;
+        Some(ObjectDescription::Intersection { children })
+    }
+}
+
+pub struct Subtraction {
+    base: Box<dyn SceneObject>,
+    subtracted: Vec<Box<dyn SceneObject>>, // Carved out of the base
+}
+
+#[allow(dead_code)]
+impl Subtraction {
+    pub fn new(base: Box<dyn SceneObject>, subtracted: Vec<Box<dyn SceneObject>>) -> Self {
+        Subtraction { base, subtracted }
+    }
+
+    pub fn add_subtracted<T>(&mut self, o: T) where T: SceneObject + 'static {
+        self.subtracted.push(Box::new(o));
+    }
+
+    // Either the base or the carving child whose inverted distance is largest
+    fn active_child(&self, p: &Point3D) -> (f64, &dyn SceneObject) {
+        let mut active = (self.base.signed_distance(p), self.base.as_ref());
+        for c in self.subtracted.iter() {
+            let dist = -c.signed_distance(p);
+            if dist > active.0 {
+                active = (dist, c.as_ref());
+            }
+        }
+        active
+    }
+}
+
+impl SceneObject for Subtraction {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.active_child(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.base.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.base.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.active_child(p).1.get_surface_material_at(p)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.base.get_step_scale().min(min_step_scale(&self.subtracted))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.base.bounding_box()
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        let subtracted = self.subtracted.iter().map(|c| c.describe()).collect::<Option<Vec<_>>>()?;
+        Some(ObjectDescription::Subtraction { base: Box::new(self.base.describe()?), subtracted })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::super::Vector3D;
+    use super::*;
+    use crate::ray_marcher::color_data_types::Color;
+    use crate::ray_marcher::threed_data_types::test_helpers::{blue, red};
+
+    fn two_spheres() -> Vec<Box<dyn SceneObject>> {
+        vec![
+            Box::new(Sphere::new(Point3D::new(-1.0, 0.0, 0.0), 1.5, red())),
+            Box::new(Sphere::new(Point3D::new(1.0, 0.0, 0.0), 1.5, blue())),
+        ]
+    }
+
+    #[test]
+    fn test_union_sdf() {
+        let u = Union::new(two_spheres());
+        assert_eq!(u.signed_distance(&Point3D::new(4.0, 0.0, 0.0)), 1.5);
+        assert_eq!(u.signed_distance(&Point3D::new(-4.0, 0.0, 0.0)), 1.5);
+    }
+
+    #[test]
+    fn test_union_material() {
+        let u = Union::new(two_spheres());
+        assert_eq!(u.get_surface_material_at(&Point3D::new(-3.0, 0.0, 0.0)).albedo, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(u.get_surface_material_at(&Point3D::new(3.0, 0.0, 0.0)).albedo, Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_empty_union_sdf() {
+        let u = Union::new(Vec::new());
+        assert_eq!(u.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_intersection_sdf() {
+        let i = Intersection::new(two_spheres());
+        // The lens between the spheres reaches 0.5 along the x axis
+        assert_eq!(i.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.5);
+        assert_eq!(i.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -0.5);
+    }
+
+    #[test]
+    fn test_intersection_material() {
+        let i = Intersection::new(two_spheres());
+        // Left of the lens the surface belongs to the right sphere
+        assert_eq!(i.get_surface_material_at(&Point3D::new(-0.5, 0.0, 0.0)).albedo, Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_subtraction_sdf() {
+        let s = Subtraction::new(
+            Box::new(AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), red())),
+            vec![Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 0.5, blue()))],
+        );
+        assert_eq!(s.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), 0.5);
+        assert_eq!(s.signed_distance(&Point3D::new(0.75, 0.0, 0.0)), -0.25);
+        assert_eq!(s.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_subtraction_material() {
+        let s = Subtraction::new(
+            Box::new(AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), red())),
+            vec![Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 0.5, blue()))],
+        );
+        assert_eq!(s.get_surface_material_at(&Point3D::new(0.1, 0.0, 0.0)).albedo, Color::new(0.0, 0.0, 1.0));
+        assert_eq!(s.get_surface_material_at(&Point3D::new(1.5, 0.0, 0.0)).albedo, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_subtraction_surface_normal() {
+        // Inside the carved hole the normal points back towards the center
+        let s = Subtraction::new(
+            Box::new(AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), red())),
+            vec![Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 0.5, blue()))],
+        );
+        let n = s.get_surface_normal(&Point3D::new(0.5, 0.0, 0.0), 0.0001);
+        assert!((Vector3D::new(-1.0, 0.0, 0.0) - n).length() < 0.001);
+    }
+
+    #[test]
+    fn test_csg_bounding_boxes() {
+        let union = Union::new(two_spheres());
+        assert_eq!(union.bounding_box(), Some(BoundingBox::new(Point3D::new(-2.5, -1.5, -1.5), Point3D::new(2.5, 1.5, 1.5))));
+        let intersection = Intersection::new(two_spheres());
+        assert_eq!(intersection.bounding_box(), Some(BoundingBox::new(Point3D::new(-0.5, -1.5, -1.5), Point3D::new(0.5, 1.5, 1.5))));
+        let mut spheres = two_spheres();
+        let base = spheres.remove(0);
+        let subtraction = Subtraction::new(base, spheres);
+        assert_eq!(subtraction.bounding_box(), Some(BoundingBox::new(Point3D::new(-2.5, -1.5, -1.5), Point3D::new(0.5, 1.5, 1.5))));
+
+        // A plane has no bounds, so a union with one has none either, but it can't widen an intersection
+        let floor = || Box::new(super::super::objects::Plane::new(Point3D::new(0.0, 0.0, 0.0), Vector3D::new(0.0, 1.0, 0.0), None));
+        let mut with_floor = two_spheres();
+        with_floor.push(floor());
+        assert!(Union::new(with_floor).bounding_box().is_none());
+        let cut = Intersection::new(vec![Box::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None)), floor()]);
+        assert_eq!(cut.bounding_box(), Some(BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0))));
+    }
+}
@@ -0,0 +1,431 @@
+use super::super::noise::Noise3D;
+use super::super::scene_file::description::{vec3, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::super::threed_data_types::Axis;
+use super::{Point3D, SceneObject, SurfaceMaterial};
+
+// Modifiers that deform the distance field of a child. Twisting, bending and
+// displacement stretch space, so their distances are no longer exact and they
+// report a step scale that keeps the marcher from stepping through the surface.
+
+// Rotates the point around `axis` (through `center`) by `angle` radians
+fn rotate_around(p: &Point3D, center: &Point3D, axis: Axis, angle: f64) -> Point3D {
+    let (u_axis, v_axis) = axis.perpendicular();
+    let mut q = *p - *center;
+    let (u, v) = (q.get_axis(u_axis), q.get_axis(v_axis));
+    let (s, c) = angle.sin_cos();
+    q.set_axis(u_axis, c * u - s * v);
+    q.set_axis(v_axis, s * u + c * v);
+    q + *center
+}
+
+pub struct Twist<T> where T: SceneObject {
+    object: T,
+    axis: Axis, // Runs through the child's position
+    rate: f64, // Radians of twist per unit along the axis
+    extent: f64, // Farthest the child's surface gets from the axis
+}
+
+#[allow(dead_code)]
+impl<T> Twist<T> where T: SceneObject {
+    pub fn new(object: T, axis: Axis, rate: f64, extent: f64) -> Self {
+        Twist { object, axis, rate, extent }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let center = self.object.get_position();
+        let along = p.get_axis(self.axis) - center.get_axis(self.axis);
+        rotate_around(p, center, self.axis, -self.rate * along)
+    }
+}
+
+impl<T> SceneObject for Twist<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    // A twist shears points at distance r from the axis by rate * r
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale() / (1.0 + (self.rate * self.extent).abs())
+    }
+
+    // Points are only ever rotated around the axis through the child's position
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.swept_around(self.object.get_position(), self.axis))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Twist { object: Box::new(self.object.describe()?), axis: self.axis, rate: self.rate, extent: self.extent })
+    }
+}
+
+pub struct Bend<T> where T: SceneObject {
+    object: T,
+    axis: Axis, // The child is bent around this axis
+    curvature: f64, // Radians of bend per unit along the first perpendicular axis
+    extent: f64, // Farthest the child's surface gets from its position along that axis
+}
+
+#[allow(dead_code)]
+impl<T> Bend<T> where T: SceneObject {
+    pub fn new(object: T, axis: Axis, curvature: f64, extent: f64) -> Self {
+        Bend { object, axis, curvature, extent }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let center = self.object.get_position();
+        let (u_axis, _) = self.axis.perpendicular();
+        let along = p.get_axis(u_axis) - center.get_axis(u_axis);
+        rotate_around(p, center, self.axis, -self.curvature * along)
+    }
+}
+
+impl<T> SceneObject for Bend<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale() / (1.0 + (self.curvature * self.extent).abs())
+    }
+
+    // Points are only ever rotated around the axis through the child's position
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.swept_around(self.object.get_position(), self.axis))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Bend { object: Box::new(self.object.describe()?), axis: self.axis, curvature: self.curvature, extent: self.extent })
+    }
+}
+
+pub struct Elongate<T> where T: SceneObject {
+    object: T,
+    half_lengths: Point3D, // How far the child is stretched from its position along each axis
+}
+
+#[allow(dead_code)]
+impl<T> Elongate<T> where T: SceneObject {
+    pub fn new(object: T, half_lengths: Point3D) -> Self {
+        Elongate { object, half_lengths }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let center = *self.object.get_position();
+        let q = *p - center;
+        let h = self.half_lengths;
+        let clamped = Point3D::new(q.x.clamp(-h.x, h.x), q.y.clamp(-h.y, h.y), q.z.clamp(-h.z, h.z));
+        q - clamped + center
+    }
+}
+
+impl<T> SceneObject for Elongate<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let b = self.object.bounding_box()?;
+        Some(BoundingBox::new(b.min - self.half_lengths.abs(), b.max + self.half_lengths.abs()))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Elongate { object: Box::new(self.object.describe()?), half_lengths: vec3(&self.half_lengths) })
+    }
+}
+
+pub struct Round<T> where T: SceneObject {
+    object: T,
+    radius: f64,
+}
+
+#[allow(dead_code)]
+impl<T> Round<T> where T: SceneObject {
+    pub fn new(object: T, radius: f64) -> Self {
+        Round { object, radius }
+    }
+}
+
+impl<T> SceneObject for Round<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(p) - self.radius
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(p)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.padded(self.radius.max(0.0)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Round { object: Box::new(self.object.describe()?), radius: self.radius })
+    }
+}
+
+pub struct Onion<T> where T: SceneObject {
+    object: T,
+    thickness: f64, // Half the width of the shell around the child's surface
+}
+
+#[allow(dead_code)]
+impl<T> Onion<T> where T: SceneObject {
+    pub fn new(object: T, thickness: f64) -> Self {
+        Onion { object, thickness }
+    }
+}
+
+impl<T> SceneObject for Onion<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(p).abs() - self.thickness
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(p)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.padded(self.thickness.abs()))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Onion { object: Box::new(self.object.describe()?), thickness: self.thickness })
+    }
+}
+
+pub type DisplacementFn = Box<dyn Fn(&Point3D) -> f64 + Send + Sync>;
+
+pub struct Displace<T> where T: SceneObject {
+    object: T,
+    displacement: DisplacementFn, // Added to the child's distance
+    lipschitz: f64, // Upper bound on how fast the displacement changes per unit of distance
+    max_displacement: Option<f64>, // Upper bound on the displacement's size, if known
+}
+
+#[allow(dead_code)]
+impl<T> Displace<T> where T: SceneObject {
+    pub fn new(object: T, displacement: DisplacementFn, lipschitz: f64) -> Self {
+        Displace { object, displacement, lipschitz, max_displacement: None }
+    }
+
+    // Lets the displaced object be bounded, without it the object is treated as unbounded
+    pub fn with_max_displacement(mut self, max_displacement: f64) -> Self {
+        self.max_displacement = Some(max_displacement.abs());
+        self
+    }
+
+    // Bumps the surface by up to `amplitude` in either direction
+    pub fn from_noise<N>(object: T, noise: N, amplitude: f64) -> Self where N: Noise3D + 'static {
+        let lipschitz = amplitude.abs() * noise.lipschitz();
+        Displace::new(object, Box::new(move |p: &Point3D| amplitude * noise.sample(p)), lipschitz).with_max_displacement(amplitude)
+    }
+}
+
+impl<T> SceneObject for Displace<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(p) + (self.displacement)(p)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(p)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale() / (1.0 + self.lipschitz.abs())
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.padded(self.max_displacement?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::noise::Perlin;
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_close, assert_safe_steps, fibonacci_directions, grid};
+
+    fn sample_points() -> Vec<Point3D> {
+        grid((8, 8, 0), 0.4, Point3D::new(0.0, 0.0, 0.3))
+    }
+
+    #[test]
+    fn test_twist_on_axis_unchanged() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 1.0), None);
+        let t = Twist::new(b, Axis::Y, 1.0, 2_f64.sqrt());
+        assert_close(t.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_twist_rotates_slices() {
+        // At height 1 a quarter-turn twist maps the X face onto the Z face
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 2.0, 0.5), None);
+        let t = Twist::new(b, Axis::Y, 90_f64.to_radians(), 2.5);
+        assert_close(t.signed_distance(&Point3D::new(0.0, 1.0, 2.5)), 0.5);
+    }
+
+    #[test]
+    fn test_twist_step_scale() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 1.0), None);
+        let t = Twist::new(b, Axis::Y, 1.5, 2_f64.sqrt());
+        assert!(t.get_step_scale() < 1.0);
+        assert_safe_steps(&t, &sample_points(), &fibonacci_directions(100));
+    }
+
+    #[test]
+    fn test_bend_step_scale() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 0.3, 0.3), None);
+        let bend = Bend::new(b, Axis::Z, 0.4, 2.0);
+        assert!(bend.get_step_scale() < 1.0);
+        assert_safe_steps(&bend, &sample_points(), &fibonacci_directions(100));
+    }
+
+    #[test]
+    fn test_elongate_sdf() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let e = Elongate::new(s, Point3D::new(2.0, 0.0, 0.0));
+        assert_close(e.signed_distance(&Point3D::new(5.0, 0.0, 0.0)), 2.0);
+        assert_close(e.signed_distance(&Point3D::new(1.5, 2.0, 0.0)), 1.0);
+        assert_eq!(e.get_step_scale(), 1.0);
+    }
+
+    #[test]
+    fn test_round_sdf() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), None);
+        let r = Round::new(b, 0.25);
+        assert_close(r.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 0.75);
+    }
+
+    #[test]
+    fn test_onion_sdf() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, None);
+        let o = Onion::new(s, 0.1);
+        assert_close(o.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), 1.9);
+        assert_close(o.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), -0.1);
+        assert_close(o.signed_distance(&Point3D::new(3.0, 0.0, 0.0)), 0.9);
+    }
+
+    #[test]
+    fn test_displace_sdf_and_step_scale() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, None);
+        let d = Displace::new(s, Box::new(|p: &Point3D| 0.2 * (3.0 * p.x).sin()), 0.6);
+        assert_close(d.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 1.0);
+        assert_close(d.get_step_scale(), 1.0 / 1.6);
+        assert_safe_steps(&d, &sample_points(), &fibonacci_directions(100));
+    }
+
+    #[test]
+    fn test_displace_from_noise() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, None);
+        let d = Displace::from_noise(s, Perlin::new(4), 0.3);
+        assert_close(d.get_step_scale(), 1.0 / (1.0 + 0.3 * Perlin::new(4).lipschitz()));
+        assert_safe_steps(&d, &sample_points(), &fibonacci_directions(100));
+    }
+
+    #[test]
+    fn test_nested_step_scale() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, None);
+        let d = Displace::new(s, Box::new(|p: &Point3D| 0.1 * p.y.sin()), 1.0);
+        let r = Round::new(d, 0.1);
+        assert_close(r.get_step_scale(), 0.5);
+    }
+
+    #[test]
+    fn test_deformation_bounding_boxes() {
+        let cube = || AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 0.5), None);
+        let size = |o: &dyn SceneObject| o.bounding_box().unwrap().size();
+        assert_eq!(size(&Round::new(cube(), 0.25)), Point3D::new(2.5, 4.5, 1.5));
+        assert_eq!(size(&Onion::new(cube(), 0.1)), Point3D::new(2.2, 4.2, 1.2));
+        assert_eq!(size(&Elongate::new(cube(), Point3D::new(1.0, 0.0, 0.0))), Point3D::new(4.0, 4.0, 1.0));
+        assert!(Displace::new(cube(), Box::new(|_p: &Point3D| 0.0), 0.0).bounding_box().is_none());
+        assert_eq!(size(&Displace::from_noise(cube(), Perlin::new(1), 0.5)), Point3D::new(3.0, 5.0, 2.0));
+
+        // Twisting turns the cross section, so it can reach as far as its corners
+        let twisted = Twist::new(cube(), Axis::Y, 1.0, 1.2);
+        let bounds = twisted.bounding_box().unwrap();
+        for xi in -12..=12 {
+            for yi in -12..=12 {
+                for zi in -12..=12 {
+                    let p = Point3D::new(xi as f64, yi as f64, zi as f64) * 0.2;
+                    if !bounds.contains(&p) {
+                        assert!(twisted.signed_distance(&p) > 0.0, "{:?} outside {:?}", p, bounds);
+                    }
+                }
+            }
+        }
+    }
+}
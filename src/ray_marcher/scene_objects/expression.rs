@@ -0,0 +1,404 @@
+pub mod syntax;
+pub mod builtins;
+
+use std::fmt;
+
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+use builtins::{Builtin, Type, Value, MAX_ARGUMENTS};
+use syntax::{BinaryOp, Expr, ExprKind};
+
+// Shapes written as a distance expression over the point `p`, parsed and type checked
+// once, e.g. `smin(sphere(p, 1), box(p - vec3(1, 0, 0), vec3(.5)), 0.2)`. Anything
+// that doesn't depend on `p` is folded into a constant before marching.
+
+#[derive(Debug, PartialEq)]
+pub enum ExpressionError {
+    Syntax { line: usize, column: usize, message: String },
+    Type { line: usize, column: usize, message: String }, // Parses, but can't be evaluated
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::Syntax { line, column, message } => write!(f, "syntax error at {}:{}: {}", line, column, message),
+            ExpressionError::Type { line, column, message } => write!(f, "type error at {}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+// Line and column, both from 1, of a byte offset into the source
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap().chars().count() + 1;
+    (line, column)
+}
+
+// An error message and where in the source it happened
+type TypeError = (usize, String);
+
+enum Node {
+    Const(Value),
+    Point,
+    Neg(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(&'static Builtin, Vec<Node>),
+    Component(Box<Node>, usize),
+}
+
+fn binary(op: BinaryOp, a: f64, b: f64) -> f64 {
+    match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Sub => a - b,
+        BinaryOp::Mul => a * b,
+        BinaryOp::Div => a / b,
+    }
+}
+
+// Floats are spread over every component when paired with a vec3
+fn spread(v: Value) -> Point3D {
+    match v {
+        Value::Float(f) => Point3D::new(f, f, f),
+        Value::Vec3(v) => v,
+    }
+}
+
+impl Node {
+    fn eval(&self, p: &Point3D) -> Value {
+        match self {
+            Node::Const(v) => *v,
+            Node::Point => Value::Vec3(*p),
+            Node::Neg(a) => match a.eval(p) {
+                Value::Float(f) => Value::Float(-f),
+                Value::Vec3(v) => Value::Vec3(v * -1.0),
+            },
+            Node::Binary(op, a, b) => match (a.eval(p), b.eval(p)) {
+                (Value::Float(x), Value::Float(y)) => Value::Float(binary(*op, x, y)),
+                (x, y) => {
+                    let (x, y) = (spread(x), spread(y));
+                    Value::Vec3(Point3D::new(binary(*op, x.x, y.x), binary(*op, x.y, y.y), binary(*op, x.z, y.z)))
+                }
+            },
+            Node::Call(builtin, args) => {
+                let mut values = [Value::Float(0.0); MAX_ARGUMENTS];
+                for (value, arg) in values.iter_mut().zip(args.iter()) {
+                    *value = arg.eval(p);
+                }
+                (builtin.eval)(&values[..args.len()])
+            }
+            Node::Component(a, i) => {
+                let v = a.eval(p).vec3();
+                Value::Float([v.x, v.y, v.z][*i])
+            }
+        }
+    }
+
+    fn is_const(&self) -> bool {
+        matches!(self, Node::Const(_))
+    }
+
+    // Evaluates the node now if none of its inputs depend on the point
+    fn folded(self) -> Node {
+        let constant = match &self {
+            Node::Neg(a) | Node::Component(a, _) => a.is_const(),
+            Node::Binary(_, a, b) => a.is_const() && b.is_const(),
+            Node::Call(_, args) => args.iter().all(Node::is_const),
+            Node::Const(_) | Node::Point => false,
+        };
+        if constant {
+            Node::Const(self.eval(&Point3D::new(0.0, 0.0, 0.0)))
+        } else {
+            self
+        }
+    }
+}
+
+fn list_types(types: &[Type]) -> String {
+    let names: Vec<String> = types.iter().map(|t| t.to_string()).collect();
+    format!("({})", names.join(", "))
+}
+
+fn or_list(items: &[String]) -> String {
+    match items.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+        _ => items.join(""),
+    }
+}
+
+fn compile(e: &Expr) -> Result<(Node, Type), TypeError> {
+    let compiled = match &e.kind {
+        ExprKind::Number(n) => (Node::Const(Value::Float(*n)), Type::Float),
+        ExprKind::Name(name) => {
+            if name == "p" {
+                (Node::Point, Type::Vec3)
+            } else if let Some(c) = builtins::constant(name) {
+                (Node::Const(Value::Float(c)), Type::Float)
+            } else if builtins::find(name).is_some() {
+                return Err((e.start, format!("`{}` is a function, call it with arguments", name)));
+            } else {
+                return Err((e.start, format!("unknown name `{}`, the point is `p`", name)));
+            }
+        }
+        ExprKind::Neg(a) => {
+            let (a, t) = compile(a)?;
+            (Node::Neg(Box::new(a)), t)
+        }
+        ExprKind::Binary(op, a, b) => {
+            let (a, ta) = compile(a)?;
+            let (b, tb) = compile(b)?;
+            let t = if ta == Type::Float && tb == Type::Float { Type::Float } else { Type::Vec3 };
+            (Node::Binary(*op, Box::new(a), Box::new(b)), t)
+        }
+        ExprKind::Component(a, name) => {
+            let start = a.start;
+            let (a, t) = compile(a)?;
+            if t != Type::Vec3 {
+                return Err((start, format!("`.{}` needs a vec3, found a float", name)));
+            }
+            let i = match name.as_str() {
+                "x" => 0,
+                "y" => 1,
+                "z" => 2,
+                _ => return Err((start, format!("a vec3 has no component `{}`, only x, y and z", name))),
+            };
+            (Node::Component(Box::new(a), i), Type::Float)
+        }
+        ExprKind::Call(name, args) => return call(name, args, e.start),
+    };
+    Ok((compiled.0.folded(), compiled.1))
+}
+
+fn call(name: &str, args: &[Expr], start: usize) -> Result<(Node, Type), TypeError> {
+    let builtin = match builtins::find(name) {
+        Some(b) => b,
+        None if name == "p" || builtins::constant(name).is_some() => {
+            return Err((start, format!("`{}` is a value, not a function", name)));
+        }
+        None => {
+            let hint = builtins::closest_name(name).map(|n| format!(", did you mean `{}`?", n)).unwrap_or_default();
+            return Err((start, format!("unknown function `{}`{}", name, hint)));
+        }
+    };
+    if builtin.signatures.iter().all(|s| s.params.len() != args.len()) {
+        let mut counts: Vec<String> = builtin.signatures.iter().map(|s| s.params.len().to_string()).collect();
+        counts.dedup();
+        let plural = if counts.len() == 1 && counts[0] == "1" { "argument" } else { "arguments" };
+        return Err((start, format!("`{}` expects {} {}, found {}", name, or_list(&counts), plural, args.len())));
+    }
+    let mut nodes = Vec::with_capacity(args.len());
+    let mut types = Vec::with_capacity(args.len());
+    for arg in args.iter() {
+        let (node, t) = compile(arg)?;
+        nodes.push(node);
+        types.push(t);
+    }
+    if builtin.name == "clamp" {
+        if let (Node::Const(Value::Float(lo)), Node::Const(Value::Float(hi))) = (&nodes[1], &nodes[2]) {
+            if lo.is_nan() || hi.is_nan() || lo > hi {
+                return Err((start, format!("`clamp` needs a lower bound no greater than its upper bound, found {} and {}", lo, hi)));
+            }
+        }
+    }
+    match builtin.signatures.iter().find(|s| s.params == types.as_slice()) {
+        Some(s) => Ok((Node::Call(builtin, nodes).folded(), s.result)),
+        None => {
+            let takes: Vec<String> = builtin.signatures.iter().map(|s| list_types(s.params)).collect();
+            Err((start, format!("no version of `{}` takes {}, it takes {}", name, list_types(&types), or_list(&takes))))
+        }
+    }
+}
+
+pub struct Expression {
+    source: String,
+    root: Node,
+    position: Point3D,
+    surface_material: SurfaceMaterial,
+    step_scale: f64,
+    bounds: Option<BoundingBox>,
+}
+
+#[allow(dead_code)]
+impl Expression {
+    pub fn new(source: &str, sm: Option<SurfaceMaterial>) -> Result<Self, ExpressionError> {
+        let expr = syntax::parse(source).map_err(|e| {
+            let (line, column) = line_column(source, e.offset);
+            ExpressionError::Syntax { line, column, message: e.message }
+        })?;
+        let type_error = |(offset, message): TypeError| {
+            let (line, column) = line_column(source, offset);
+            ExpressionError::Type { line, column, message }
+        };
+        let (root, t) = compile(&expr).map_err(type_error)?;
+        if t != Type::Float {
+            return Err(type_error((0, "a shape must give a distance, which is a float, but this gives a vec3".to_string())));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+            position: Point3D::new(0.0, 0.0, 0.0),
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            step_scale: 1.0,
+            bounds: None,
+        })
+    }
+
+    // For expressions that aren't exact distances, e.g. after scaling `p` unevenly
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    // The shape can't be bounded from the expression alone, so without this it is
+    // checked everywhere
+    pub fn with_bounds(mut self, bounds: BoundingBox) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    // Whether the distance doesn't depend on the point at all
+    pub fn is_constant(&self) -> bool {
+        self.root.is_const()
+    }
+}
+
+impl SceneObject for Expression {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.root.eval(p).float()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.step_scale
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Expression {
+            source: self.source.clone(),
+            step_scale: self.step_scale,
+            bounds: self.bounds.map(|b| (vec3(&b.min), vec3(&b.max))),
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::super::smooth::{smooth_min, BlendKind};
+    use super::*;
+
+    fn type_error(source: &str) -> String {
+        match Expression::new(source, None) {
+            Err(ExpressionError::Type { message, .. }) => message,
+            Err(e) => panic!("expected a type error, got {}", e),
+            Ok(_) => panic!("expected a type error"),
+        }
+    }
+
+    #[test]
+    fn test_matches_objects() {
+        let shape = Expression::new("smin(sphere(p, 1), box(p - vec3(1, 0, 0), vec3(.5)), 0.2)", None).unwrap();
+        let sphere = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let cube = AxisAlignedBox::new(Point3D::new(1.0, 0.0, 0.0), Point3D::new(0.5, 0.5, 0.5), None);
+        for i in 0..40 {
+            let t = i as f64 * 0.3;
+            let p = Point3D::new(t.sin() * 2.0, t.cos() * 1.5, (t * 0.7).sin());
+            let expected = smooth_min(sphere.signed_distance(&p), cube.signed_distance(&p), 0.2, BlendKind::Polynomial).0;
+            assert!((shape.signed_distance(&p) - expected).abs() < 1e-12, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_and_components() {
+        let shape = Expression::new("-(p.x - 2) * 3 + length(p * 2) / 4", None).unwrap();
+        let p = Point3D::new(1.0, 2.0, 2.0);
+        assert_eq!(shape.signed_distance(&p), 3.0 + 1.5);
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let shape = Expression::new("max(2 * pi, sqrt(4)) - 1", None).unwrap();
+        assert!(shape.is_constant());
+        assert_eq!(shape.signed_distance(&Point3D::new(5.0, 0.0, 0.0)), 2.0 * std::f64::consts::PI - 1.0);
+        let shape = Expression::new("sphere(p - vec3(0, 1 + 1, 0), 2 / 4)", None).unwrap();
+        match &shape.root {
+            Node::Call(_, args) => match &args[0] {
+                Node::Binary(_, _, offset) => assert!(matches!(**offset, Node::Const(Value::Vec3(v)) if v == Point3D::new(0.0, 2.0, 0.0))),
+                _ => panic!("expected p - vec3(..)"),
+            },
+            _ => panic!("expected a call"),
+        }
+    }
+
+    #[test]
+    fn test_type_errors() {
+        assert_eq!(type_error("min(1)"), "`min` expects 2 arguments, found 1");
+        assert_eq!(type_error("length(vec3(1, 2))"), "`vec3` expects 1 or 3 arguments, found 2");
+        assert_eq!(type_error("min(p, 1)"), "no version of `min` takes (vec3, float), it takes (float, float) or (vec3, vec3)");
+        assert_eq!(type_error("sphree(p, 1)"), "unknown function `sphree`, did you mean `sphere`?");
+        assert_eq!(type_error("sphere(q, 1)"), "unknown name `q`, the point is `p`");
+        assert_eq!(type_error("p.w"), "a vec3 has no component `w`, only x, y and z");
+        assert_eq!(type_error("p * 2"), "a shape must give a distance, which is a float, but this gives a vec3");
+        assert_eq!(type_error("length"), "`length` is a function, call it with arguments");
+    }
+
+    #[test]
+    fn test_clamp_bounds() {
+        let message = "`clamp` needs a lower bound no greater than its upper bound, found 2 and 0";
+        assert_eq!(type_error("clamp(1, 2, 0)"), message);
+        assert_eq!(type_error("clamp(p.x, 2, 0)"), message);
+        // Bounds that depend on the point can still cross
+        let shape = Expression::new("clamp(p.x, p.y, p.z)", None).unwrap();
+        assert_eq!(shape.signed_distance(&Point3D::new(5.0, 2.0, 1.0)), 1.0);
+        assert_eq!(shape.signed_distance(&Point3D::new(0.5, 0.0, 1.0)), 0.5);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let e = Expression::new("union(sphere(p, 1),\n  box(p, 1))", None).err().unwrap();
+        assert_eq!(e, ExpressionError::Type { line: 2, column: 3, message: "no version of `box` takes (vec3, float), it takes (vec3, vec3)".to_string() });
+        let e = Expression::new("sphere(p, 1", None).err().unwrap();
+        assert_eq!(e.to_string(), "syntax error at 1:12: expected `)` to close the arguments of `sphere`, found the end of the expression");
+        // Nesting is limited while parsing, so a deep expression is an error rather than a stack overflow
+        let nested = |n: usize| format!("{}sphere(p, 1){}", "(".repeat(n), ")".repeat(n));
+        assert!(matches!(Expression::new(&nested(20_000), None), Err(ExpressionError::Syntax { .. })));
+        let deep = Expression::new(&nested(250), None).unwrap();
+        assert_eq!(deep.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_describe() {
+        let bounds = BoundingBox::new(Point3D::new(-1.0, -1.0, -1.0), Point3D::new(1.0, 1.0, 1.0));
+        let shape = Expression::new("sphere(p, 1)", None).unwrap().with_step_scale(0.5).with_bounds(bounds);
+        assert_eq!(shape.get_step_scale(), 0.5);
+        assert_eq!(shape.bounding_box(), Some(bounds));
+        match shape.describe() {
+            Some(ObjectDescription::Expression { source, step_scale, bounds, material }) => {
+                assert_eq!(source, "sphere(p, 1)");
+                assert_eq!(step_scale, 0.5);
+                assert_eq!(bounds, Some(((-1.0, -1.0, -1.0), (1.0, 1.0, 1.0))));
+                assert!(material.is_none());
+            }
+            _ => panic!("expected an expression"),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::f64::consts::PI;
+use std::fmt;
+
+use super::super::smooth::{smooth_max, smooth_min, BlendKind};
+use super::Point3D;
+
+// The functions shape expressions can call, with the argument types each accepts
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Type {
+    Float,
+    Vec3,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Float => write!(f, "float"),
+            Type::Vec3 => write!(f, "vec3"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Float(f64),
+    Vec3(Point3D),
+}
+
+impl Value {
+    pub fn get_type(&self) -> Type {
+        match self {
+            Value::Float(_) => Type::Float,
+            Value::Vec3(_) => Type::Vec3,
+        }
+    }
+
+    // Type checking rules out the other kind, so these never see it
+    pub fn float(&self) -> f64 {
+        match self {
+            Value::Float(f) => *f,
+            Value::Vec3(_) => unreachable!("expected a float"),
+        }
+    }
+
+    pub fn vec3(&self) -> Point3D {
+        match self {
+            Value::Vec3(v) => *v,
+            Value::Float(_) => unreachable!("expected a vec3"),
+        }
+    }
+}
+
+pub const MAX_ARGUMENTS: usize = 4;
+
+use Type::{Float as F, Vec3 as V};
+
+pub struct Signature {
+    pub params: &'static [Type],
+    pub result: Type,
+}
+
+const fn sig(params: &'static [Type], result: Type) -> Signature {
+    Signature { params, result }
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub signatures: &'static [Signature],
+    pub eval: fn(&[Value]) -> Value,
+}
+
+fn map(v: Point3D, f: fn(f64) -> f64) -> Point3D {
+    Point3D::new(f(v.x), f(v.y), f(v.z))
+}
+
+fn zip(a: Point3D, b: Point3D, f: fn(f64, f64) -> f64) -> Point3D {
+    Point3D::new(f(a.x, b.x), f(a.y, b.y), f(a.z, b.z))
+}
+
+// Applies a float function to a float, or to each component of a vec3
+fn each(args: &[Value], f: fn(f64) -> f64) -> Value {
+    match args[0] {
+        Value::Float(x) => Value::Float(f(x)),
+        Value::Vec3(v) => Value::Vec3(map(v, f)),
+    }
+}
+
+fn each_pair(args: &[Value], f: fn(f64, f64) -> f64) -> Value {
+    match (args[0], args[1]) {
+        (Value::Float(a), Value::Float(b)) => Value::Float(f(a, b)),
+        (Value::Vec3(a), Value::Vec3(b)) => Value::Vec3(zip(a, b, f)),
+        (Value::Vec3(a), Value::Float(b)) => Value::Vec3(zip(a, Point3D::new(b, b, b), f)),
+        (Value::Float(_), Value::Vec3(_)) => unreachable!("no builtin takes a float then a vec3"),
+    }
+}
+
+fn modulo(a: f64, b: f64) -> f64 {
+    a - b * (a / b).floor()
+}
+
+fn rotate(v: Point3D, angle: f64, u: fn(&mut Point3D) -> (&mut f64, &mut f64)) -> Value {
+    let mut r = v;
+    let (s, c) = angle.sin_cos();
+    let (a, b) = u(&mut r);
+    let (x, y) = (*a, *b);
+    *a = c * x - s * y;
+    *b = s * x + c * y;
+    Value::Vec3(r)
+}
+
+fn sd_box(p: Point3D, b: Point3D) -> f64 {
+    let q = p.abs() - b;
+    q.max(0.0).length() + q.max_component().min(0.0)
+}
+
+fn float(f: f64) -> Value {
+    Value::Float(f)
+}
+
+pub static BUILTINS: &[Builtin] = &[
+    Builtin { name: "vec3", signatures: &[sig(&[F], V), sig(&[F, F, F], V)], eval: |a| match a.len() {
+        1 => Value::Vec3(Point3D::new(a[0].float(), a[0].float(), a[0].float())),
+        _ => Value::Vec3(Point3D::new(a[0].float(), a[1].float(), a[2].float())),
+    } },
+    Builtin { name: "length", signatures: &[sig(&[V], F)], eval: |a| float(a[0].vec3().length()) },
+    Builtin { name: "dot", signatures: &[sig(&[V, V], F)], eval: |a| float(a[0].vec3().dot(&a[1].vec3())) },
+    Builtin { name: "cross", signatures: &[sig(&[V, V], V)], eval: |a| Value::Vec3(a[0].vec3().cross(&a[1].vec3())) },
+    Builtin { name: "normalize", signatures: &[sig(&[V], V)], eval: |a| Value::Vec3(a[0].vec3() / a[0].vec3().length()) },
+    Builtin { name: "abs", signatures: &[sig(&[F], F), sig(&[V], V)], eval: |a| each(a, f64::abs) },
+    Builtin { name: "floor", signatures: &[sig(&[F], F), sig(&[V], V)], eval: |a| each(a, f64::floor) },
+    Builtin { name: "round", signatures: &[sig(&[F], F), sig(&[V], V)], eval: |a| each(a, f64::round) },
+    Builtin { name: "fract", signatures: &[sig(&[F], F), sig(&[V], V)], eval: |a| each(a, |x| x - x.floor()) },
+    Builtin { name: "sign", signatures: &[sig(&[F], F), sig(&[V], V)], eval: |a| each(a, |x| if x == 0.0 { 0.0 } else { x.signum() }) },
+    Builtin { name: "sqrt", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().sqrt()) },
+    Builtin { name: "sin", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().sin()) },
+    Builtin { name: "cos", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().cos()) },
+    Builtin { name: "tan", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().tan()) },
+    Builtin { name: "exp", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().exp()) },
+    Builtin { name: "ln", signatures: &[sig(&[F], F)], eval: |a| float(a[0].float().ln()) },
+    Builtin { name: "atan2", signatures: &[sig(&[F, F], F)], eval: |a| float(a[0].float().atan2(a[1].float())) },
+    Builtin { name: "pow", signatures: &[sig(&[F, F], F)], eval: |a| float(a[0].float().powf(a[1].float())) },
+    Builtin { name: "mod", signatures: &[sig(&[F, F], F), sig(&[V, V], V), sig(&[V, F], V)], eval: |a| each_pair(a, modulo) },
+    Builtin { name: "min", signatures: &[sig(&[F, F], F), sig(&[V, V], V)], eval: |a| each_pair(a, f64::min) },
+    Builtin { name: "max", signatures: &[sig(&[F, F], F), sig(&[V, V], V)], eval: |a| each_pair(a, f64::max) },
+    // Bounds that only cross at some points give `hi` there rather than panicking
+    Builtin { name: "clamp", signatures: &[sig(&[F, F, F], F), sig(&[V, F, F], V)], eval: |a| {
+        let (lo, hi) = (a[1].float(), a[2].float());
+        let clamp = |x: f64| x.max(lo).min(hi);
+        match a[0] {
+            Value::Float(x) => float(clamp(x)),
+            Value::Vec3(v) => Value::Vec3(Point3D::new(clamp(v.x), clamp(v.y), clamp(v.z))),
+        }
+    } },
+    Builtin { name: "mix", signatures: &[sig(&[F, F, F], F), sig(&[V, V, F], V)], eval: |a| {
+        let t = a[2].float();
+        match (a[0], a[1]) {
+            (Value::Float(x), Value::Float(y)) => float(x + (y - x) * t),
+            (x, y) => Value::Vec3(x.vec3() + (y.vec3() - x.vec3()) * t),
+        }
+    } },
+    // Distances to primitives centered on the origin of `p`
+    Builtin { name: "sphere", signatures: &[sig(&[V, F], F)], eval: |a| float(a[0].vec3().length() - a[1].float()) },
+    Builtin { name: "box", signatures: &[sig(&[V, V], F)], eval: |a| float(sd_box(a[0].vec3(), a[1].vec3())) },
+    Builtin { name: "round_box", signatures: &[sig(&[V, V, F], F)], eval: |a| float(sd_box(a[0].vec3(), a[1].vec3()) - a[2].float()) },
+    Builtin { name: "torus", signatures: &[sig(&[V, F, F], F)], eval: |a| {
+        // Lies in the XZ plane, like the Torus object
+        let p = a[0].vec3();
+        float((p.x.hypot(p.z) - a[1].float()).hypot(p.y) - a[2].float())
+    } },
+    Builtin { name: "capsule", signatures: &[sig(&[V, V, V, F], F)], eval: |a| {
+        let (p, start, end) = (a[0].vec3(), a[1].vec3(), a[2].vec3());
+        let (pa, ba) = (p - start, end - start);
+        let h = (pa.dot(&ba) / ba.dot(&ba)).clamp(0.0, 1.0);
+        float((pa - ba * h).length() - a[3].float())
+    } },
+    Builtin { name: "cylinder", signatures: &[sig(&[V, F, F], F)], eval: |a| {
+        // Capped, with its axis along Y
+        let p = a[0].vec3();
+        let (dx, dy) = (p.x.hypot(p.z) - a[1].float(), p.y.abs() - a[2].float());
+        float(dx.max(dy).min(0.0) + dx.max(0.0).hypot(dy.max(0.0)))
+    } },
+    Builtin { name: "octahedron", signatures: &[sig(&[V, F], F)], eval: |a| {
+        // A bound rather than the exact distance, which is safe to march
+        let p = a[0].vec3().abs();
+        float((p.x + p.y + p.z - a[1].float()) * (1.0 / 3_f64.sqrt()))
+    } },
+    Builtin { name: "plane", signatures: &[sig(&[V, V, F], F)], eval: |a| {
+        let n = a[1].vec3();
+        float(a[0].vec3().dot(&n) / n.length() + a[2].float())
+    } },
+    // Combining distances
+    Builtin { name: "union", signatures: &[sig(&[F, F], F)], eval: |a| float(a[0].float().min(a[1].float())) },
+    Builtin { name: "intersect", signatures: &[sig(&[F, F], F)], eval: |a| float(a[0].float().max(a[1].float())) },
+    Builtin { name: "subtract", signatures: &[sig(&[F, F], F)], eval: |a| float(a[0].float().max(-a[1].float())) },
+    Builtin { name: "smin", signatures: &[sig(&[F, F, F], F)], eval: |a| float(smooth_min(a[0].float(), a[1].float(), a[2].float(), BlendKind::Polynomial).0) },
+    Builtin { name: "smax", signatures: &[sig(&[F, F, F], F)], eval: |a| float(smooth_max(a[0].float(), a[1].float(), a[2].float(), BlendKind::Polynomial).0) },
+    // Moving the point before measuring, angles in radians
+    Builtin { name: "repeat", signatures: &[sig(&[V, V], V)], eval: |a| {
+        // A zero period leaves that axis alone
+        Value::Vec3(zip(a[0].vec3(), a[1].vec3(), |x, period| if period > 0.0 { x - period * (x / period).round() } else { x }))
+    } },
+    Builtin { name: "rotate_x", signatures: &[sig(&[V, F], V)], eval: |a| rotate(a[0].vec3(), a[1].float(), |v| (&mut v.y, &mut v.z)) },
+    Builtin { name: "rotate_y", signatures: &[sig(&[V, F], V)], eval: |a| rotate(a[0].vec3(), a[1].float(), |v| (&mut v.z, &mut v.x)) },
+    Builtin { name: "rotate_z", signatures: &[sig(&[V, F], V)], eval: |a| rotate(a[0].vec3(), a[1].float(), |v| (&mut v.x, &mut v.y)) },
+];
+
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+// Named values other than the point
+pub fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(PI),
+        "tau" => Some(2.0 * PI),
+        _ => None,
+    }
+}
+
+// The builtin with the closest name, to suggest when a name is misspelled
+pub fn closest_name(name: &str) -> Option<&'static str> {
+    BUILTINS
+        .iter()
+        .map(|b| (b.name, edit_distance(name, b.name)))
+        .filter(|(_, d)| *d <= 2)
+        .min_by_key(|(_, d)| *d)
+        .map(|(n, _)| n)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous + if ca == *cb { 0 } else { 1 };
+            previous = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn call(name: &str, args: &[Value]) -> Value {
+        (find(name).unwrap().eval)(args)
+    }
+
+    #[test]
+    fn test_signatures_fit_argument_limit() {
+        for b in BUILTINS.iter() {
+            assert!(b.signatures.iter().all(|s| s.params.len() <= MAX_ARGUMENTS), "{}", b.name);
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let v = |x, y, z| Value::Vec3(Point3D::new(x, y, z));
+        assert_eq!(call("vec3", &[Value::Float(2.0)]), v(2.0, 2.0, 2.0));
+        assert_eq!(call("min", &[v(1.0, 5.0, -1.0), v(2.0, 3.0, 0.0)]), v(1.0, 3.0, -1.0));
+        assert_eq!(call("mod", &[v(-1.0, 5.0, 2.0), Value::Float(3.0)]), v(2.0, 2.0, 2.0));
+        assert_eq!(call("box", &[v(3.0, 0.0, 0.0), v(1.0, 1.0, 1.0)]), Value::Float(2.0));
+        assert_eq!(call("torus", &[v(3.0, 0.0, 0.0), Value::Float(2.0), Value::Float(0.5)]), Value::Float(0.5));
+        assert_eq!(call("repeat", &[v(4.5, 1.0, 7.0), v(2.0, 0.0, 0.0)]), v(0.5, 1.0, 7.0));
+        match call("rotate_z", &[v(1.0, 0.0, 0.0), Value::Float(PI / 2.0)]) {
+            Value::Vec3(r) => assert!(r.distance_to(&Point3D::new(0.0, 1.0, 0.0)) < 1e-12),
+            _ => panic!("expected a vec3"),
+        }
+    }
+
+    #[test]
+    fn test_closest_name() {
+        assert_eq!(closest_name("sphree"), Some("sphere"));
+        assert_eq!(closest_name("lenght"), Some("length"));
+        assert_eq!(closest_name("banana"), None);
+    }
+}
@@ -0,0 +1,313 @@
+// Tokens and the syntax tree of shape expressions, e.g.
+// `smin(sphere(p, 1), box(p - vec3(1, 0, 0), vec3(.5)), 0.2)`. Offsets are byte offsets
+// into the source.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinaryOp {
+    pub fn symbol(&self) -> char {
+        match self {
+            BinaryOp::Add => '+',
+            BinaryOp::Sub => '-',
+            BinaryOp::Mul => '*',
+            BinaryOp::Div => '/',
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Number(f64),
+    Name(String),
+    Call(String, Vec<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Component(Box<Expr>, String), // `v.x`
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub start: usize, // Where the expression begins in the source
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub offset: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char), // One of + - * / ( ) , .
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number {}", n),
+            Token::Name(name) => format!("`{}`", name),
+            Token::Op(c) => format!("`{}`", c),
+            Token::End => "the end of the expression".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, SyntaxError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        let next_is_digit = bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit());
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || (c == '.' && next_is_digit) {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            // An exponent, as in 1e-3
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                let mut j = i + 1;
+                if j < bytes.len() && (bytes[j] == b'+' || bytes[j] == b'-') {
+                    j += 1;
+                }
+                if j < bytes.len() && bytes[j].is_ascii_digit() {
+                    i = j;
+                    while i < bytes.len() && bytes[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text = &source[start..i];
+            let value = text.parse::<f64>().map_err(|_| SyntaxError { offset: start, message: format!("`{}` is not a number", text) })?;
+            tokens.push((Token::Number(value), start));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(source[start..i].to_string()), start));
+        } else if "+-*/(),.".contains(c) {
+            tokens.push((Token::Op(c), i));
+            i += 1;
+        } else {
+            let c = source[i..].chars().next().unwrap();
+            return Err(SyntaxError { offset: i, message: format!("unexpected character `{}`", c) });
+        }
+    }
+    tokens.push((Token::End, source.len()));
+    Ok(tokens)
+}
+
+// Recursive descent over the grammar
+//   sum     = product (('+' | '-') product)*
+//   product = unary (('*' | '/') unary)*
+//   unary   = '-' unary | postfix
+//   postfix = primary ('.' name)*
+//   primary = number | name | name '(' (sum (',' sum)*)? ')' | '(' sum ')'
+// Every rule and every operator in a chain adds a level to the tree, the limit keeps
+// parsing, compiling and evaluating it from overflowing the stack.
+const MAX_DEPTH: usize = 256;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.next].clone();
+        if token.0 != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, c: char, context: &str) -> Result<(), SyntaxError> {
+        if *self.peek() == Token::Op(c) {
+            self.advance();
+            return Ok(());
+        }
+        Err(SyntaxError { offset: self.offset(), message: format!("expected `{}` {}, found {}", c, context, self.peek().describe()) })
+    }
+
+    fn enter(&mut self) -> Result<(), SyntaxError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(SyntaxError { offset: self.offset(), message: format!("the expression is nested more than {} levels deep", MAX_DEPTH) });
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn sum(&mut self) -> Result<Expr, SyntaxError> {
+        let depth = self.depth;
+        self.enter()?;
+        let mut left = self.product()?;
+        while let Token::Op(c @ ('+' | '-')) = *self.peek() {
+            self.advance();
+            self.enter()?;
+            let op = if c == '+' { BinaryOp::Add } else { BinaryOp::Sub };
+            let right = self.product()?;
+            let start = left.start;
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), start };
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expr, SyntaxError> {
+        let depth = self.depth;
+        let mut left = self.unary()?;
+        while let Token::Op(c @ ('*' | '/')) = *self.peek() {
+            self.advance();
+            self.enter()?;
+            let op = if c == '*' { BinaryOp::Mul } else { BinaryOp::Div };
+            let right = self.unary()?;
+            let start = left.start;
+            left = Expr { kind: ExprKind::Binary(op, Box::new(left), Box::new(right)), start };
+        }
+        self.depth = depth;
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, SyntaxError> {
+        if *self.peek() == Token::Op('-') {
+            let (_, start) = self.advance();
+            self.enter()?;
+            let operand = self.unary()?;
+            self.depth -= 1;
+            return Ok(Expr { kind: ExprKind::Neg(Box::new(operand)), start });
+        }
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr, SyntaxError> {
+        let depth = self.depth;
+        let mut e = self.primary()?;
+        while *self.peek() == Token::Op('.') {
+            self.advance();
+            self.enter()?;
+            match self.advance() {
+                (Token::Name(name), _) => {
+                    let start = e.start;
+                    e = Expr { kind: ExprKind::Component(Box::new(e), name), start };
+                }
+                (token, offset) => return Err(SyntaxError { offset, message: format!("expected a component after `.`, found {}", token.describe()) }),
+            }
+        }
+        self.depth = depth;
+        Ok(e)
+    }
+
+    fn primary(&mut self) -> Result<Expr, SyntaxError> {
+        let (token, start) = self.advance();
+        match token {
+            Token::Number(n) => Ok(Expr { kind: ExprKind::Number(n), start }),
+            Token::Name(name) => {
+                if *self.peek() != Token::Op('(') {
+                    return Ok(Expr { kind: ExprKind::Name(name), start });
+                }
+                self.advance();
+                let mut args = Vec::new();
+                if *self.peek() != Token::Op(')') {
+                    args.push(self.sum()?);
+                    while *self.peek() == Token::Op(',') {
+                        self.advance();
+                        args.push(self.sum()?);
+                    }
+                }
+                self.expect(')', &format!("to close the arguments of `{}`", name))?;
+                Ok(Expr { kind: ExprKind::Call(name, args), start })
+            }
+            Token::Op('(') => {
+                let inner = self.sum()?;
+                self.expect(')', "to close `(`")?;
+                Ok(inner)
+            }
+            token => Err(SyntaxError { offset: start, message: format!("expected a value, found {}", token.describe()) }),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Expr, SyntaxError> {
+    let mut parser = Parser { tokens: tokenize(source)?, next: 0, depth: 0 };
+    let e = parser.sum()?;
+    if *parser.peek() != Token::End {
+        return Err(SyntaxError { offset: parser.offset(), message: format!("expected an operator, found {}", parser.peek().describe()) });
+    }
+    Ok(e)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn number(n: f64, start: usize) -> Expr {
+        Expr { kind: ExprKind::Number(n), start }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let e = parse("1 + 2 * -.5").unwrap();
+        let product = Expr {
+            kind: ExprKind::Binary(BinaryOp::Mul, Box::new(number(2.0, 4)), Box::new(Expr { kind: ExprKind::Neg(Box::new(number(0.5, 9))), start: 8 })),
+            start: 4,
+        };
+        assert_eq!(e, Expr { kind: ExprKind::Binary(BinaryOp::Add, Box::new(number(1.0, 0)), Box::new(product)), start: 0 });
+    }
+
+    #[test]
+    fn test_calls_and_components() {
+        let e = parse("max(p.x, 1e-2)").unwrap();
+        match e.kind {
+            ExprKind::Call(name, args) => {
+                assert_eq!(name, "max");
+                assert_eq!(args[0].kind, ExprKind::Component(Box::new(Expr { kind: ExprKind::Name("p".to_string()), start: 4 }), "x".to_string()));
+                assert_eq!(args[1], number(0.01, 9));
+            }
+            _ => panic!("expected a call"),
+        }
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(parse("sphere(p, 1").unwrap_err(), SyntaxError { offset: 11, message: "expected `)` to close the arguments of `sphere`, found the end of the expression".to_string() });
+        assert_eq!(parse("1 + * 2").unwrap_err().offset, 4);
+        assert_eq!(parse("length(p) 2").unwrap_err().message, "expected an operator, found number 2");
+        assert_eq!(parse("p # 2").unwrap_err().message, "unexpected character `#`");
+        assert_eq!(parse("p.1").unwrap_err().offset, 1);
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |n: usize| format!("{}p.x{}", "(".repeat(n), ")".repeat(n));
+        assert!(parse(&nested(100)).is_ok());
+        let e = parse(&nested(20_000)).unwrap_err();
+        assert_eq!(e.message, "the expression is nested more than 256 levels deep");
+        assert!(e.offset < 300, "offset {}", e.offset);
+        // Long chains and stacked signs make deep trees too
+        assert!(parse(&vec!["1"; 1000].join(" + ")).is_err());
+        assert!(parse(&format!("{}1", "-".repeat(1000))).is_err());
+        assert!(parse(&format!("p{}", ".x".repeat(1000))).is_err());
+    }
+}
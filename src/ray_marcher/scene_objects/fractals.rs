@@ -0,0 +1,518 @@
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Fractal objects. These use distance estimators rather than exact distances, and
+// every one of them tracks an orbit trap while iterating so materials can be colored
+// by how the orbit of a point behaves.
+
+pub trait OrbitTrap {
+    fn orbit_trap(&self, p: &Point3D) -> f64; // Between 0 and 1
+}
+
+fn cube_around(position: &Point3D, half_width: f64) -> BoundingBox {
+    BoundingBox::new(*position - half_width, *position + half_width)
+}
+
+// Blends the base material towards the trap material by the orbit trap value
+fn trap_material(base: &SurfaceMaterial, trap: &Option<SurfaceMaterial>, value: f64) -> SurfaceMaterial {
+    match trap {
+        Some(t) => SurfaceMaterial::mix(base, t, value.clamp(0.0, 1.0)),
+        None => *base,
+    }
+}
+
+#[derive(Clone)]
+pub struct Mandelbulb {
+    position: Point3D,
+    size: f64, // The bulb spans roughly one size unit from its position
+    power: f64,
+    iterations: u32,
+    bailout: f64,
+    material: SurfaceMaterial,
+    trap_material: Option<SurfaceMaterial>,
+}
+
+#[allow(dead_code)]
+impl Mandelbulb {
+    pub fn new(pos: Point3D, size: f64, power: f64, iterations: u32, bailout: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Mandelbulb {
+            position: pos,
+            size,
+            power,
+            iterations,
+            bailout,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            trap_material: None,
+        }
+    }
+
+    pub fn with_trap_material(mut self, sm: SurfaceMaterial) -> Self {
+        self.trap_material = Some(sm);
+        self
+    }
+
+    // Returns the distance estimate and the orbit trap
+    fn estimate(&self, p: &Point3D) -> (f64, f64) {
+        let c = (*p - self.position) / self.size;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = f64::MAX;
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > self.bailout {
+                break;
+            }
+            trap = trap.min(r);
+            if r < 1e-12 {
+                z = c;
+                continue;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = Point3D::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) * zr + c;
+        }
+        let distance = if r < 1e-12 { 0.0 } else { 0.5 * r.ln() * r / dr };
+        (distance * self.size, trap.clamp(0.0, 1.0))
+    }
+}
+
+impl OrbitTrap for Mandelbulb {
+    fn orbit_trap(&self, p: &Point3D) -> f64 {
+        self.estimate(p).1
+    }
+}
+
+impl SceneObject for Mandelbulb {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.estimate(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        trap_material(&self.material, &self.trap_material, self.orbit_trap(p))
+    }
+
+    // With a power of at least 2 every point farther than 2 from the center escapes
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        if self.power < 2.0 {
+            return None;
+        }
+        Some(cube_around(&self.position, 2.0 * self.size))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Mandelbulb {
+            center: vec3(&self.position),
+            size: self.size,
+            power: self.power,
+            iterations: self.iterations,
+            bailout: self.bailout,
+            material: MaterialRef::describe(&self.material),
+            trap_material: self.trap_material.as_ref().map(MaterialRef::inline),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Julia {
+    position: Point3D,
+    size: f64,
+    constant: [f64; 4], // Quaternion added on every iteration
+    power: u32,
+    iterations: u32,
+    bailout: f64,
+    material: SurfaceMaterial,
+    trap_material: Option<SurfaceMaterial>,
+}
+
+fn quaternion_multiply(a: &[f64; 4], b: &[f64; 4]) -> [f64; 4] {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+fn quaternion_length(q: &[f64; 4]) -> f64 {
+    (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt()
+}
+
+#[allow(dead_code)]
+impl Julia {
+    pub fn new(pos: Point3D, size: f64, constant: [f64; 4], power: u32, iterations: u32, bailout: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Julia {
+            position: pos,
+            size,
+            constant,
+            power: power.max(2),
+            iterations,
+            bailout,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            trap_material: None,
+        }
+    }
+
+    pub fn with_trap_material(mut self, sm: SurfaceMaterial) -> Self {
+        self.trap_material = Some(sm);
+        self
+    }
+
+    // Returns the distance estimate and the orbit trap
+    fn estimate(&self, p: &Point3D) -> (f64, f64) {
+        let local = (*p - self.position) / self.size;
+        let mut z = [local.x, local.y, local.z, 0.0];
+        let mut dr = 1.0;
+        let mut r = quaternion_length(&z);
+        let mut trap = f64::MAX;
+        for _ in 0..self.iterations {
+            if r > self.bailout {
+                break;
+            }
+            trap = trap.min(r);
+            dr *= self.power as f64 * r.powi(self.power as i32 - 1);
+            let mut zn = z;
+            for _ in 1..self.power {
+                zn = quaternion_multiply(&zn, &z);
+            }
+            for (v, c) in zn.iter_mut().zip(self.constant.iter()) {
+                *v += c;
+            }
+            z = zn;
+            r = quaternion_length(&z);
+        }
+        let distance = if r < 1e-12 || dr < 1e-300 { 0.0 } else { 0.5 * r * r.ln() / dr };
+        (distance * self.size, trap.clamp(0.0, 1.0))
+    }
+}
+
+impl OrbitTrap for Julia {
+    fn orbit_trap(&self, p: &Point3D) -> f64 {
+        self.estimate(p).1
+    }
+}
+
+impl SceneObject for Julia {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.estimate(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        trap_material(&self.material, &self.trap_material, self.orbit_trap(p))
+    }
+
+    // Past 1 + |c| the orbit only grows, for any power of at least 2
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        if self.power < 2 {
+            return None;
+        }
+        Some(cube_around(&self.position, (1.0 + quaternion_length(&self.constant)) * self.size))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Julia {
+            center: vec3(&self.position),
+            size: self.size,
+            constant: self.constant,
+            power: self.power,
+            iterations: self.iterations,
+            bailout: self.bailout,
+            material: MaterialRef::describe(&self.material),
+            trap_material: self.trap_material.as_ref().map(MaterialRef::inline),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct MengerSponge {
+    position: Point3D,
+    size: f64, // Half the width of the outer cube
+    iterations: u32,
+    material: SurfaceMaterial,
+    trap_material: Option<SurfaceMaterial>,
+}
+
+#[allow(dead_code)]
+impl MengerSponge {
+    pub fn new(pos: Point3D, size: f64, iterations: u32, sm: Option<SurfaceMaterial>) -> Self {
+        MengerSponge {
+            position: pos,
+            size,
+            iterations,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            trap_material: None,
+        }
+    }
+
+    pub fn with_trap_material(mut self, sm: SurfaceMaterial) -> Self {
+        self.trap_material = Some(sm);
+        self
+    }
+
+    // Returns the distance estimate and the orbit trap, which is the depth of the
+    // hole that defines the surface relative to the iteration count
+    fn estimate(&self, p: &Point3D) -> (f64, f64) {
+        let local = (*p - self.position) / self.size;
+        let q = local.abs() - Point3D::new(1.0, 1.0, 1.0);
+        let mut d = q.max(0.0).length() + q.max_component().min(0.0);
+        let mut trap = 0.0;
+
+        let mut s = 1.0;
+        for level in 0..self.iterations {
+            let a = local * s;
+            let a = Point3D::new(a.x.rem_euclid(2.0), a.y.rem_euclid(2.0), a.z.rem_euclid(2.0)) - 1.0;
+            s *= 3.0;
+            let r = (a.abs() * -3.0 + 1.0).abs();
+            let da = r.x.max(r.y);
+            let db = r.y.max(r.z);
+            let dc = r.z.max(r.x);
+            let c = (da.min(db.min(dc)) - 1.0) / s;
+            if c > d {
+                d = c;
+                trap = (level + 1) as f64 / self.iterations as f64;
+            }
+        }
+        (d * self.size, trap)
+    }
+}
+
+impl OrbitTrap for MengerSponge {
+    fn orbit_trap(&self, p: &Point3D) -> f64 {
+        self.estimate(p).1
+    }
+}
+
+impl SceneObject for MengerSponge {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.estimate(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        trap_material(&self.material, &self.trap_material, self.orbit_trap(p))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(cube_around(&self.position, self.size))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::MengerSponge {
+            center: vec3(&self.position),
+            size: self.size,
+            iterations: self.iterations,
+            material: MaterialRef::describe(&self.material),
+            trap_material: self.trap_material.as_ref().map(MaterialRef::inline),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct SierpinskiTetrahedron {
+    position: Point3D,
+    size: f64, // Distance from the center to each corner along each axis
+    iterations: u32,
+    bailout: f64,
+    material: SurfaceMaterial,
+    trap_material: Option<SurfaceMaterial>,
+}
+
+#[allow(dead_code)]
+impl SierpinskiTetrahedron {
+    pub fn new(pos: Point3D, size: f64, iterations: u32, bailout: f64, sm: Option<SurfaceMaterial>) -> Self {
+        SierpinskiTetrahedron {
+            position: pos,
+            size,
+            iterations,
+            bailout,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            trap_material: None,
+        }
+    }
+
+    pub fn with_trap_material(mut self, sm: SurfaceMaterial) -> Self {
+        self.trap_material = Some(sm);
+        self
+    }
+
+    // Returns the distance estimate and the orbit trap
+    fn estimate(&self, p: &Point3D) -> (f64, f64) {
+        let mut z = (*p - self.position) / self.size;
+        let mut scale = 1.0;
+        let mut trap = f64::MAX;
+        for _ in 0..self.iterations {
+            if z.length() > self.bailout {
+                break;
+            }
+            // Fold towards the corner at (1, 1, 1), then grow that corner's copy
+            if z.x + z.y < 0.0 {
+                z = Point3D::new(-z.y, -z.x, z.z);
+            }
+            if z.x + z.z < 0.0 {
+                z = Point3D::new(-z.z, z.y, -z.x);
+            }
+            if z.y + z.z < 0.0 {
+                z = Point3D::new(z.x, -z.z, -z.y);
+            }
+            z = z * 2.0 - 1.0;
+            scale *= 2.0;
+            trap = trap.min(z.length());
+        }
+        // Distance to the tetrahedron with corners (1,1,1), (1,-1,-1), (-1,1,-1), (-1,-1,1)
+        let tetrahedron = ((z.x + z.y).abs() - z.z).max((z.x - z.y).abs() + z.z) - 1.0;
+        let distance = tetrahedron / 3_f64.sqrt() / scale;
+        (distance * self.size, (trap / 3_f64.sqrt()).clamp(0.0, 1.0))
+    }
+}
+
+impl OrbitTrap for SierpinskiTetrahedron {
+    fn orbit_trap(&self, p: &Point3D) -> f64 {
+        self.estimate(p).1
+    }
+}
+
+impl SceneObject for SierpinskiTetrahedron {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.estimate(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        trap_material(&self.material, &self.trap_material, self.orbit_trap(p))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(cube_around(&self.position, self.size))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::SierpinskiTetrahedron {
+            center: vec3(&self.position),
+            size: self.size,
+            iterations: self.iterations,
+            bailout: self.bailout,
+            material: MaterialRef::describe(&self.material),
+            trap_material: self.trap_material.as_ref().map(MaterialRef::inline),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+    use crate::ray_marcher::color_data_types::Color;
+
+    #[test]
+    fn test_mandelbulb_sdf_outside() {
+        let m = Mandelbulb::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8.0, 12, 2.0, None);
+        let d = m.signed_distance(&Point3D::new(3.0, 0.0, 0.0));
+        assert!(d > 0.0 && d < 3.0);
+    }
+
+    #[test]
+    fn test_mandelbulb_sdf_inside() {
+        let m = Mandelbulb::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8.0, 12, 2.0, None);
+        assert!(m.signed_distance(&Point3D::new(0.0, 0.0, 0.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_mandelbulb_size_scales_distance() {
+        let small = Mandelbulb::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8.0, 12, 2.0, None);
+        let large = Mandelbulb::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 8.0, 12, 2.0, None);
+        let d_small = small.signed_distance(&Point3D::new(0.0, 2.0, 0.5));
+        let d_large = large.signed_distance(&Point3D::new(0.0, 4.0, 1.0));
+        assert_close(d_large, 2.0 * d_small);
+    }
+
+    #[test]
+    fn test_julia_sdf_outside() {
+        let j = Julia::new(Point3D::new(0.0, 0.0, 0.0), 1.0, [-0.2, 0.6, 0.2, 0.0], 2, 12, 4.0, None);
+        let d = j.signed_distance(&Point3D::new(0.0, 0.0, 3.0));
+        assert!(d > 0.0 && d < 3.0);
+    }
+
+    #[test]
+    fn test_menger_sdf_corner() {
+        let m = MengerSponge::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 3, None);
+        assert_close(m.signed_distance(&Point3D::new(2.0, 0.8, 0.8)), 1.0);
+    }
+
+    #[test]
+    fn test_menger_sdf_tunnel() {
+        // The center tunnel runs all the way through the sponge
+        let m = MengerSponge::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 3, None);
+        assert!(m.signed_distance(&Point3D::new(0.0, 0.0, 0.0)) > 0.0);
+        assert!(m.signed_distance(&Point3D::new(0.9, 0.0, 0.0)) > 0.0);
+    }
+
+    #[test]
+    fn test_sierpinski_sdf_corner() {
+        let s = SierpinskiTetrahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8, 4.0, None);
+        let d = s.signed_distance(&Point3D::new(2.0, 2.0, 2.0));
+        assert!(d > 0.0 && d <= 3_f64.sqrt() + 1e-9);
+    }
+
+    #[test]
+    fn test_sierpinski_sdf_center_hole() {
+        let s = SierpinskiTetrahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8, 4.0, None);
+        // The removed middle octahedron has its faces 1 / sqrt(3) from the center
+        assert_close(s.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), 1.0 / 3_f64.sqrt());
+        assert!(s.signed_distance(&Point3D::new(0.99, 0.99, 0.99)) < 1e-2);
+    }
+
+    #[test]
+    fn test_orbit_trap_range() {
+        let m = Mandelbulb::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8.0, 12, 2.0, None);
+        let s = SierpinskiTetrahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 8, 4.0, None);
+        for p in [Point3D::new(0.3, 0.5, -0.2), Point3D::new(2.0, 1.0, 0.0)] {
+            let t = m.orbit_trap(&p);
+            assert!((0.0..=1.0).contains(&t));
+            let t = s.orbit_trap(&p);
+            assert!((0.0..=1.0).contains(&t));
+        }
+    }
+
+    #[test]
+    fn test_orbit_trap_material() {
+        let base = SurfaceMaterial::diffuse(Color::new(1.0, 0.0, 0.0));
+        let trap = SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0));
+        let m = MengerSponge::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 3, Some(base)).with_trap_material(trap);
+        // The outer face is never carved, a tunnel wall belongs to the first hole
+        assert_eq!(m.get_surface_material_at(&Point3D::new(1.0, 0.8, 0.8)).albedo, Color::new(1.0, 0.0, 0.0));
+        assert!(m.get_surface_material_at(&Point3D::new(0.0, 0.34, 0.0)).albedo.b() > 0.0);
+    }
+}
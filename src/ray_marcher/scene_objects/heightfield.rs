@@ -0,0 +1,240 @@
+use std::path::{Path, PathBuf};
+
+use image::GrayImage;
+
+use super::super::noise::Noise3D;
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Terrain rising from a flat base. Heights come from a grayscale image or a function,
+// both give values between 0 and 1 across the terrain that are scaled by
+// `vertical_scale`. The height difference above a point is only a distance if the
+// terrain is flat, so it is divided by sqrt(1 + L^2) where L bounds the slope.
+
+// Takes coordinates between 0 and 1 across the x and z extents, returns a height between 0 and 1
+pub type HeightFn = Box<dyn Fn(f64, f64) -> f64 + Send + Sync>;
+
+enum HeightSource {
+    Samples { heights: Vec<f64>, width: usize, depth: usize },
+    Function(HeightFn),
+}
+
+pub struct Heightfield {
+    position: Point3D, // Center of the base
+    extents: (f64, f64), // Half sizes along x and z
+    vertical_scale: f64,
+    source: HeightSource,
+    slope: f64, // Bound on the world space slope of the surface
+    surface_material: SurfaceMaterial,
+    image: Option<PathBuf>, // The file the heights were loaded from, scene files refer to it
+}
+
+#[allow(dead_code)]
+impl Heightfield {
+    // Pixel columns run along x and rows along z, black is the base and white is
+    // `vertical_scale` above it
+    pub fn from_image(pos: Point3D, extents: (f64, f64), vertical_scale: f64, image: &GrayImage, sm: Option<SurfaceMaterial>) -> Self {
+        let (width, depth) = (image.width() as usize, image.height() as usize);
+        let heights: Vec<f64> = image.pixels().map(|p| p.0[0] as f64 / 255.0).collect();
+
+        // Bilinear interpolation is never steeper than the largest step between
+        // neighbouring pixels along each axis
+        let spacing_x = 2.0 * extents.0 / (width.max(2) - 1) as f64;
+        let spacing_z = 2.0 * extents.1 / (depth.max(2) - 1) as f64;
+        let mut step_x: f64 = 0.0;
+        let mut step_z: f64 = 0.0;
+        for z in 0..depth {
+            for x in 0..width {
+                let h = heights[z * width + x];
+                if x + 1 < width {
+                    step_x = step_x.max((heights[z * width + x + 1] - h).abs());
+                }
+                if z + 1 < depth {
+                    step_z = step_z.max((heights[(z + 1) * width + x] - h).abs());
+                }
+            }
+        }
+        let slope = (step_x * vertical_scale / spacing_x).hypot(step_z * vertical_scale / spacing_z);
+
+        Heightfield {
+            position: pos,
+            extents,
+            vertical_scale,
+            source: HeightSource::Samples { heights, width, depth },
+            slope,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            image: None,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(pos: Point3D, extents: (f64, f64), vertical_scale: f64, path: P, sm: Option<SurfaceMaterial>) -> Result<Self, image::ImageError> {
+        let terrain = Heightfield::from_image(pos, extents, vertical_scale, &image::open(path.as_ref())?.to_luma8(), sm);
+        Ok(Heightfield { image: Some(path.as_ref().to_path_buf()), ..terrain })
+    }
+
+    // `lipschitz` bounds how fast the function changes per unit of its own 0 to 1
+    // coordinates. Underestimating it lets rays tunnel through steep slopes.
+    pub fn from_fn(pos: Point3D, extents: (f64, f64), vertical_scale: f64, height: HeightFn, lipschitz: f64, sm: Option<SurfaceMaterial>) -> Self {
+        let slope = lipschitz * vertical_scale / (2.0 * extents.0.min(extents.1));
+        Heightfield {
+            position: pos,
+            extents,
+            vertical_scale,
+            source: HeightSource::Function(height),
+            slope,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            image: None,
+        }
+    }
+
+    // Samples the noise at world space x and z relative to the position, on the plane
+    // y = 0, so its frequency sets the size of the hills
+    pub fn from_noise<N>(pos: Point3D, extents: (f64, f64), vertical_scale: f64, noise: N, sm: Option<SurfaceMaterial>) -> Self where N: Noise3D + 'static {
+        // Heights are half the noise, so half its slope
+        let slope = 0.5 * noise.lipschitz() * vertical_scale;
+        let height: HeightFn = Box::new(move |u, v| {
+            let local = Point3D::new((u - 0.5) * 2.0 * extents.0, 0.0, (v - 0.5) * 2.0 * extents.1);
+            0.5 + 0.5 * noise.sample(&local)
+        });
+        Heightfield {
+            position: pos,
+            extents,
+            vertical_scale,
+            source: HeightSource::Function(height),
+            slope,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            image: None,
+        }
+    }
+
+    pub fn get_image(&self) -> Option<&Path> {
+        self.image.as_deref()
+    }
+
+    pub fn get_slope_bound(&self) -> f64 {
+        self.slope
+    }
+
+    // Height above the base at world coordinates, points past the edges take the
+    // height of the nearest edge
+    pub fn height_at(&self, x: f64, z: f64) -> f64 {
+        let u = ((x - self.position.x) / (2.0 * self.extents.0) + 0.5).clamp(0.0, 1.0);
+        let v = ((z - self.position.z) / (2.0 * self.extents.1) + 0.5).clamp(0.0, 1.0);
+        let h = match &self.source {
+            HeightSource::Function(f) => f(u, v).clamp(0.0, 1.0),
+            HeightSource::Samples { heights, width, depth } => {
+                let gx = u * (*width - 1) as f64;
+                let gz = v * (*depth - 1) as f64;
+                let x0 = (gx.floor() as usize).min(width.saturating_sub(2));
+                let z0 = (gz.floor() as usize).min(depth.saturating_sub(2));
+                let x1 = (x0 + 1).min(width - 1);
+                let z1 = (z0 + 1).min(depth - 1);
+                let (fx, fz) = (gx - x0 as f64, gz - z0 as f64);
+                let sample = |x: usize, z: usize| heights[z * width + x];
+                let near = sample(x0, z0) * (1.0 - fx) + sample(x1, z0) * fx;
+                let far = sample(x0, z1) * (1.0 - fx) + sample(x1, z1) * fx;
+                near * (1.0 - fz) + far * fz
+            }
+        };
+        h * self.vertical_scale
+    }
+}
+
+impl SceneObject for Heightfield {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let surface = (p.y - self.position.y - self.height_at(p.x, p.z)) / (1.0 + self.slope * self.slope).sqrt();
+
+        // Clipped to the box the terrain can occupy
+        let local = *p - self.position - Point3D::new(0.0, self.vertical_scale / 2.0, 0.0);
+        let q = local.abs() - Point3D::new(self.extents.0, self.vertical_scale / 2.0, self.extents.1);
+        let bounds = q.max(0.0).length() + q.max_component().min(0.0);
+
+        surface.max(bounds)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let min = self.position - Point3D::new(self.extents.0, 0.0, self.extents.1);
+        let max = self.position + Point3D::new(self.extents.0, self.vertical_scale, self.extents.1);
+        Some(BoundingBox::new(min, max))
+    }
+
+    // Only terrain loaded from an image can be written, functions and noise can't
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Heightfield {
+            image: self.image.as_ref()?.to_string_lossy().into_owned(),
+            center: vec3(&self.position),
+            extents: self.extents,
+            vertical_scale: self.vertical_scale,
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::noise::{FractalKind, FractalNoise, Perlin};
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_safe_steps, fibonacci_directions, grid};
+    use image::Luma;
+
+    // A single ridge along z, 0 at the edges and 1 in the middle column
+    fn ridge_image() -> GrayImage {
+        GrayImage::from_fn(3, 3, |x, _| if x == 1 { Luma([255]) } else { Luma([0]) })
+    }
+
+    fn sine_terrain() -> Heightfield {
+        let height: HeightFn = Box::new(|u, v| 0.5 + 0.5 * (u * 20.0).sin() * (v * 20.0).cos());
+        // |d/du| and |d/dv| are at most 10, so the gradient is at most 10 * sqrt(2)
+        Heightfield::from_fn(Point3D::new(0.0, 0.0, 0.0), (5.0, 5.0), 2.0, height, 10.0 * 2_f64.sqrt(), None)
+    }
+
+    fn noise_terrain() -> Heightfield {
+        let noise = FractalNoise::new(Perlin::new(8), FractalKind::Fbm, 4).with_frequency(0.4);
+        Heightfield::from_noise(Point3D::new(0.0, 0.0, 0.0), (5.0, 5.0), 2.0, noise, None)
+    }
+
+    #[test]
+    fn test_heightfield_image_heights() {
+        let terrain = Heightfield::from_image(Point3D::new(0.0, -1.0, 0.0), (1.0, 1.0), 4.0, &ridge_image(), None);
+        assert_eq!(terrain.height_at(0.0, 0.0), 4.0);
+        assert_eq!(terrain.height_at(-1.0, 0.3), 0.0);
+        assert_eq!(terrain.height_at(0.5, 0.0), 2.0);
+        assert_eq!(terrain.height_at(9.0, 0.0), 0.0);
+        // A rise of 4 over a run of 1 between pixels
+        assert_eq!(terrain.get_slope_bound(), 4.0);
+    }
+
+    #[test]
+    fn test_heightfield_flat_distance() {
+        let flat = Heightfield::from_fn(Point3D::new(0.0, 0.0, 0.0), (10.0, 10.0), 1.0, Box::new(|_, _| 0.5), 0.0, None);
+        assert_eq!(flat.signed_distance(&Point3D::new(1.0, 3.5, 2.0)), 3.0);
+        assert_eq!(flat.signed_distance(&Point3D::new(1.0, 0.25, 2.0)), -0.25);
+        assert_eq!(flat.signed_distance(&Point3D::new(13.0, 0.25, 0.0)), 3.0);
+    }
+
+    #[test]
+    fn test_heightfield_never_overestimates() {
+        for terrain in [sine_terrain(), noise_terrain()] {
+            assert_safe_steps(&terrain, &grid((12, 3, 12), 0.4, Point3D::new(0.01, 1.5, -0.02)), &fibonacci_directions(100));
+        }
+    }
+
+    #[test]
+    fn test_heightfield_steep_image_is_conservative() {
+        let terrain = Heightfield::from_image(Point3D::new(0.0, 0.0, 0.0), (1.0, 1.0), 4.0, &ridge_image(), None);
+        // Beside the ridge, straight up is not the closest direction
+        let p = Point3D::new(-0.9, 0.5, 0.0);
+        let d = terrain.signed_distance(&p);
+        assert!(d > 0.0);
+        assert!(d < 0.5 - terrain.height_at(p.x, p.z));
+    }
+}
@@ -0,0 +1,608 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::super::bvh::Bvh;
+use super::super::scene_file::description::{MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Triangle meshes loaded from OBJ or STL files. Distances are exact point to triangle
+// distances, found through a BVH over the triangles, and the sign comes from the
+// angle weighted pseudo-normal of the closest feature (face, edge or vertex). That test
+// only works on closed, consistently wound meshes, so anything else is rejected on load.
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(std::io::Error),
+    Format(String),                              // Not a file we know how to read
+    Parse { line: usize, message: String },      // Line numbers start at 1
+    MalformedFace { face: usize, message: String },
+    NonManifoldEdge { a: usize, b: usize, faces: usize }, // Edges must be shared by exactly two faces
+    InconsistentWinding { a: usize, b: usize },
+    Empty,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Io(e) => write!(f, "could not read mesh: {}", e),
+            MeshError::Format(message) => write!(f, "unsupported mesh: {}", message),
+            MeshError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            MeshError::MalformedFace { face, message } => write!(f, "face {}: {}", face, message),
+            MeshError::NonManifoldEdge { a, b, faces } => {
+                write!(f, "edge between vertices {} and {} is used by {} faces, expected 2", a, b, faces)
+            }
+            MeshError::InconsistentWinding { a, b } => {
+                write!(f, "faces on both sides of the edge between vertices {} and {} are wound the same way", a, b)
+            }
+            MeshError::Empty => write!(f, "mesh has no faces"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MeshError {
+    fn from(e: std::io::Error) -> Self {
+        MeshError::Io(e)
+    }
+}
+
+// Which part of a triangle a closest point landed on. Edge `i` runs from corner `i`
+// to corner `i + 1`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Feature {
+    Face,
+    Edge(usize),
+    Vertex(usize),
+}
+
+pub struct Mesh {
+    vertices: Vec<Point3D>,
+    triangles: Vec<[usize; 3]>,
+    face_normals: Vec<Point3D>,
+    edge_normals: Vec<[Point3D; 3]>,
+    vertex_normals: Vec<Point3D>,
+    bvh: Bvh,
+    bounds: BoundingBox,
+    position: Point3D,
+    surface_material: SurfaceMaterial,
+    source: Option<PathBuf>, // The file it was loaded from, scene files refer to it
+}
+
+#[allow(dead_code)]
+impl Mesh {
+    pub fn new(vertices: Vec<Point3D>, mut triangles: Vec<[usize; 3]>, sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        if triangles.is_empty() {
+            return Err(MeshError::Empty);
+        }
+        for (face, t) in triangles.iter().enumerate() {
+            if let Some(i) = t.iter().find(|i| **i >= vertices.len()) {
+                return Err(MeshError::MalformedFace { face, message: format!("vertex {} does not exist", i) });
+            }
+            if t[0] == t[1] || t[1] == t[2] || t[2] == t[0] {
+                return Err(MeshError::MalformedFace { face, message: "repeats a vertex".to_string() });
+            }
+            let [a, b, c] = t.map(|i| vertices[i]);
+            if (b - a).cross(&(c - a)).length() == 0.0 {
+                return Err(MeshError::MalformedFace { face, message: "has no area".to_string() });
+            }
+        }
+
+        // Every edge needs one face on each side, walking it in opposite directions
+        let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+        for (face, t) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (t[i], t[(i + 1) % 3]);
+                edges.entry((a.min(b), a.max(b))).or_default().push((face, a < b));
+            }
+        }
+        for ((a, b), faces) in edges.iter() {
+            if faces.len() != 2 {
+                return Err(MeshError::NonManifoldEdge { a: *a, b: *b, faces: faces.len() });
+            }
+            if faces[0].1 == faces[1].1 {
+                return Err(MeshError::InconsistentWinding { a: *a, b: *b });
+            }
+        }
+
+        // Consistent winding can still be inside out, a negative volume means it is
+        let volume: f64 = triangles
+            .iter()
+            .map(|t| vertices[t[0]].dot(&vertices[t[1]].cross(&vertices[t[2]])))
+            .sum();
+        if volume < 0.0 {
+            for t in triangles.iter_mut() {
+                t.swap(1, 2);
+            }
+        }
+
+        let face_normals: Vec<Point3D> = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| vertices[i]);
+                (b - a).cross(&(c - a)).normalize()
+            })
+            .collect();
+
+        let edge_normals = triangles
+            .iter()
+            .map(|t| {
+                [0, 1, 2].map(|i| {
+                    let (a, b) = (t[i], t[(i + 1) % 3]);
+                    edges[&(a.min(b), a.max(b))]
+                        .iter()
+                        .fold(Point3D::new(0.0, 0.0, 0.0), |n, (face, _)| n + face_normals[*face])
+                })
+            })
+            .collect();
+
+        let mut vertex_normals = vec![Point3D::new(0.0, 0.0, 0.0); vertices.len()];
+        for (face, t) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let corner = vertices[t[i]];
+                let mut e1 = vertices[t[(i + 1) % 3]] - corner;
+                let mut e2 = vertices[t[(i + 2) % 3]] - corner;
+                let angle = e1.normalize().dot(&e2.normalize()).clamp(-1.0, 1.0).acos();
+                vertex_normals[t[i]] += face_normals[face] * angle;
+            }
+        }
+
+        let boxes: Vec<BoundingBox> = triangles
+            .iter()
+            .map(|t| BoundingBox::from_points(t.map(|i| vertices[i]).iter()))
+            .collect();
+        let bounds = boxes.iter().fold(BoundingBox::empty(), |b, t| b.union(t));
+
+        Ok(Mesh {
+            bvh: Bvh::new(&boxes),
+            position: bounds.center(),
+            bounds,
+            vertices,
+            triangles,
+            face_normals,
+            edge_normals,
+            vertex_normals,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            source: None,
+        })
+    }
+
+    // Picks the format from the file extension
+    pub fn load<P: AsRef<Path>>(path: P, sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("obj") => Mesh::from_obj(path, sm),
+            Some("stl") => Mesh::from_stl(path, sm),
+            _ => Err(MeshError::Format(format!("{} is not an .obj or .stl file", path.display()))),
+        }
+    }
+
+    pub fn from_obj<P: AsRef<Path>>(path: P, sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        let mesh = Mesh::parse_obj(&fs::read_to_string(path.as_ref())?, sm)?;
+        Ok(Mesh { source: Some(path.as_ref().to_path_buf()), ..mesh })
+    }
+
+    pub fn from_stl<P: AsRef<Path>>(path: P, sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        let mesh = Mesh::parse_stl(&fs::read(path.as_ref())?, sm)?;
+        Ok(Mesh { source: Some(path.as_ref().to_path_buf()), ..mesh })
+    }
+
+    // Reads vertices and faces, anything else in the file (normals, texture
+    // coordinates, groups, materials) is ignored. Polygons are split into fans.
+    pub fn parse_obj(source: &str, sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        for (n, line) in source.lines().enumerate() {
+            let line_number = n + 1;
+            let parse_error = |message: String| MeshError::Parse { line: line_number, message };
+            let line = line.split('#').next().unwrap_or("");
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let coords = tokens
+                        .take(3)
+                        .map(|t| t.parse::<f64>().map_err(|_| parse_error(format!("invalid coordinate '{}'", t))))
+                        .collect::<Result<Vec<f64>, MeshError>>()?;
+                    if coords.len() != 3 {
+                        return Err(parse_error("vertex needs 3 coordinates".to_string()));
+                    }
+                    vertices.push(Point3D::new(coords[0], coords[1], coords[2]));
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for token in tokens {
+                        // Only the position index matters, in "v/vt/vn" forms
+                        let index = token
+                            .split('/')
+                            .next()
+                            .and_then(|i| i.parse::<i64>().ok())
+                            .ok_or_else(|| parse_error(format!("invalid face index '{}'", token)))?;
+                        let resolved = if index < 0 { vertices.len() as i64 + index } else { index - 1 };
+                        if index == 0 || resolved < 0 || resolved >= vertices.len() as i64 {
+                            return Err(parse_error(format!("face index {} does not refer to a vertex", index)));
+                        }
+                        face.push(resolved as usize);
+                    }
+                    if face.len() < 3 {
+                        return Err(parse_error(format!("face needs at least 3 vertices, found {}", face.len())));
+                    }
+                    for i in 1..face.len() - 1 {
+                        triangles.push([face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Mesh::new(vertices, triangles, sm)
+    }
+
+    // STL stores every triangle with its own corners, so identical corners are
+    // welded back together before the mesh is built
+    pub fn parse_stl(bytes: &[u8], sm: Option<SurfaceMaterial>) -> Result<Self, MeshError> {
+        let corners = if is_binary_stl(bytes) {
+            parse_binary_stl(bytes)
+        } else {
+            let source = std::str::from_utf8(bytes)
+                .map_err(|_| MeshError::Format("neither a binary nor an ASCII STL file".to_string()))?;
+            parse_ascii_stl(source)?
+        };
+
+        let mut welded: HashMap<[u64; 3], usize> = HashMap::new();
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        for triangle in corners {
+            triangles.push(triangle.map(|p| {
+                // Adding zero turns -0.0 into 0.0 so both weld together
+                let key = [p.x + 0.0, p.y + 0.0, p.z + 0.0].map(f64::to_bits);
+                *welded.entry(key).or_insert_with(|| {
+                    vertices.push(p);
+                    vertices.len() - 1
+                })
+            }));
+        }
+        Mesh::new(vertices, triangles, sm)
+    }
+
+    pub fn get_bounds(&self) -> &BoundingBox {
+        &self.bounds
+    }
+
+    pub fn get_source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn triangle_distance(&self, p: &Point3D, triangle: usize) -> (f64, Point3D, Feature) {
+        let [a, b, c] = self.triangles[triangle].map(|i| self.vertices[i]);
+        let (closest, feature) = closest_point_on_triangle(p, &a, &b, &c);
+        (p.distance_to(&closest), closest, feature)
+    }
+}
+
+impl SceneObject for Mesh {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let (triangle, _) = match self.bvh.closest(p, |t| self.triangle_distance(p, t).0) {
+            Some(found) => found,
+            None => return f64::INFINITY,
+        };
+        let (distance, closest, feature) = self.triangle_distance(p, triangle);
+        let pseudo_normal = match feature {
+            Feature::Face => self.face_normals[triangle],
+            Feature::Edge(i) => self.edge_normals[triangle][i],
+            Feature::Vertex(i) => self.vertex_normals[self.triangles[triangle][i]],
+        };
+        if (*p - closest).dot(&pseudo_normal) < 0.0 {
+            -distance
+        } else {
+            distance
+        }
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.bounds)
+    }
+
+    // Only meshes read from a file can be written, the file itself is not
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Mesh {
+            path: self.source.as_ref()?.to_string_lossy().into_owned(),
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+// Closest point on triangle abc, from Ericson's Real-Time Collision Detection
+fn closest_point_on_triangle(p: &Point3D, a: &Point3D, b: &Point3D, c: &Point3D) -> (Point3D, Feature) {
+    let ab = *b - *a;
+    let ac = *c - *a;
+    let ap = *p - *a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (*a, Feature::Vertex(0));
+    }
+
+    let bp = *p - *b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (*b, Feature::Vertex(1));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return (*a + ab * (d1 / (d1 - d3)), Feature::Edge(0));
+    }
+
+    let cp = *p - *c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (*c, Feature::Vertex(2));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return (*a + ac * (d2 / (d2 - d6)), Feature::Edge(2));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (*b + (*c - *b) * w, Feature::Edge(1));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    (*a + ab * (vb * denom) + ac * (vc * denom), Feature::Face)
+}
+
+// Binary files have an 80 byte header, a triangle count and 50 bytes per triangle.
+// ASCII files also start with "solid" so the size is the reliable check.
+fn is_binary_stl(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    bytes.len() == 84 + count * 50
+}
+
+fn parse_binary_stl(bytes: &[u8]) -> Vec<[Point3D; 3]> {
+    let read_f32 = |at: usize| f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]) as f64;
+    bytes[84..]
+        .chunks_exact(50)
+        .enumerate()
+        .map(|(n, _)| {
+            let start = 84 + n * 50 + 12; // Skip the stored normal
+            [0, 1, 2].map(|corner| {
+                let at = start + corner * 12;
+                Point3D::new(read_f32(at), read_f32(at + 4), read_f32(at + 8))
+            })
+        })
+        .collect()
+}
+
+fn parse_ascii_stl(source: &str) -> Result<Vec<[Point3D; 3]>, MeshError> {
+    if !source.trim_start().starts_with("solid") {
+        return Err(MeshError::Format("neither a binary nor an ASCII STL file".to_string()));
+    }
+    let mut triangles = Vec::new();
+    let mut corners = Vec::new();
+    for (n, line) in source.lines().enumerate() {
+        let parse_error = |message: String| MeshError::Parse { line: n + 1, message };
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("outer") => corners.clear(),
+            Some("vertex") => {
+                let coords = tokens
+                    .map(|t| t.parse::<f64>().map_err(|_| parse_error(format!("invalid coordinate '{}'", t))))
+                    .collect::<Result<Vec<f64>, MeshError>>()?;
+                if coords.len() != 3 {
+                    return Err(parse_error("vertex needs 3 coordinates".to_string()));
+                }
+                corners.push(Point3D::new(coords[0], coords[1], coords[2]));
+            }
+            Some("endloop") => {
+                if corners.len() != 3 {
+                    return Err(parse_error(format!("facet needs 3 vertices, found {}", corners.len())));
+                }
+                triangles.push([corners[0], corners[1], corners[2]]);
+            }
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CUBE_OBJ: &str = "\
+# A cube with half extents of 1
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2/1 3/2 7/3 6/4
+";
+
+    const TETRAHEDRON: [[[f64; 3]; 3]; 4] = [
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    ];
+
+    fn tetrahedron_ascii_stl() -> String {
+        let mut s = "solid tetra\n".to_string();
+        for t in TETRAHEDRON.iter() {
+            s += "  facet normal 0 0 0\n    outer loop\n";
+            for v in t.iter() {
+                s += &format!("      vertex {} {} {}\n", v[0], v[1], v[2]);
+            }
+            s += "    endloop\n  endfacet\n";
+        }
+        s + "endsolid tetra\n"
+    }
+
+    fn tetrahedron_binary_stl() -> Vec<u8> {
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&(TETRAHEDRON.len() as u32).to_le_bytes());
+        for t in TETRAHEDRON.iter() {
+            bytes.extend_from_slice(&[0; 12]);
+            for v in t.iter() {
+                for c in v.iter() {
+                    bytes.extend_from_slice(&(*c as f32).to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&[0; 2]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_mesh_cube_distances() {
+        let cube = Mesh::parse_obj(CUBE_OBJ, None).unwrap();
+        assert_eq!(cube.triangle_count(), 12);
+        assert!((cube.signed_distance(&Point3D::new(0.0, 0.0, 0.0)) + 1.0).abs() < 1e-12);
+        assert!((cube.signed_distance(&Point3D::new(0.5, 0.2, 0.0)) + 0.5).abs() < 1e-12);
+        assert!((cube.signed_distance(&Point3D::new(3.0, 0.0, 0.0)) - 2.0).abs() < 1e-12);
+        assert!((cube.signed_distance(&Point3D::new(2.0, 2.0, 0.0)) - 2_f64.sqrt()).abs() < 1e-12);
+        assert!((cube.signed_distance(&Point3D::new(2.0, -2.0, 2.0)) - 3_f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mesh_matches_box_sdf() {
+        let cube = Mesh::parse_obj(CUBE_OBJ, None).unwrap();
+        let reference = super::super::objects::AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), None);
+        for xi in -4..=4 {
+            for yi in -4..=4 {
+                for zi in -4..=4 {
+                    let p = Point3D::new(xi as f64 * 0.45 + 0.01, yi as f64 * 0.45, zi as f64 * 0.45 - 0.02);
+                    assert!((cube.signed_distance(&p) - reference.signed_distance(&p)).abs() < 1e-9, "{:?}", p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mesh_inside_out_is_flipped() {
+        let reversed: String = CUBE_OBJ
+            .lines()
+            .map(|l| {
+                if let Some(face) = l.strip_prefix("f ") {
+                    format!("f {}\n", face.split_whitespace().rev().collect::<Vec<_>>().join(" "))
+                } else {
+                    format!("{}\n", l)
+                }
+            })
+            .collect();
+        let cube = Mesh::parse_obj(&reversed, None).unwrap();
+        assert!(cube.signed_distance(&Point3D::new(0.0, 0.0, 0.0)) < 0.0);
+        assert!(cube.signed_distance(&Point3D::new(0.0, 3.0, 0.0)) > 0.0);
+    }
+
+    #[test]
+    fn test_mesh_negative_indices() {
+        let obj = "v 0 0 0\nv 0 1 0\nv 1 0 0\nv 0 0 1\nf -4 -3 -2\nf 1 3 4\nf 1 4 2\nf 3 2 4\n";
+        let mesh = Mesh::parse_obj(obj, None).unwrap();
+        assert!((mesh.signed_distance(&Point3D::new(0.1, 0.1, 0.1)) + 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mesh_malformed_face() {
+        match Mesh::parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n", None) {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(line, 3),
+            _ => panic!("expected a parse error"),
+        }
+        match Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 7\n", None) {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(line, 5),
+            _ => panic!("expected a parse error"),
+        }
+        match Mesh::parse_obj("v 0 0 x\n", None) {
+            Err(MeshError::Parse { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn test_mesh_non_manifold() {
+        let open: String = CUBE_OBJ.lines().filter(|l| !l.starts_with("f 5")).map(|l| format!("{}\n", l)).collect();
+        assert!(matches!(Mesh::parse_obj(&open, None), Err(MeshError::NonManifoldEdge { faces: 1, .. })));
+    }
+
+    #[test]
+    fn test_mesh_inconsistent_winding() {
+        let flipped = CUBE_OBJ.replace("f 5 6 7 8", "f 8 7 6 5");
+        assert!(matches!(Mesh::parse_obj(&flipped, None), Err(MeshError::InconsistentWinding { .. })));
+    }
+
+    #[test]
+    fn test_mesh_empty() {
+        assert!(matches!(Mesh::parse_obj("v 0 0 0\n", None), Err(MeshError::Empty)));
+    }
+
+    #[test]
+    fn test_mesh_ascii_and_binary_stl() {
+        let ascii = Mesh::parse_stl(tetrahedron_ascii_stl().as_bytes(), None).unwrap();
+        let binary = Mesh::parse_stl(&tetrahedron_binary_stl(), None).unwrap();
+        for mesh in [ascii, binary] {
+            assert_eq!(mesh.vertices.len(), 4);
+            assert!((mesh.signed_distance(&Point3D::new(0.1, 0.2, 0.1)) + 0.1).abs() < 1e-12);
+            assert!((mesh.signed_distance(&Point3D::new(1.0, 1.0, 1.0)) - 2.0 / 3_f64.sqrt()).abs() < 1e-12);
+            assert!((mesh.signed_distance(&Point3D::new(-1.0, 0.2, 0.2)) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_mesh_bad_stl() {
+        assert!(matches!(Mesh::parse_stl(&[0xff, 0xfe, 0x00], None), Err(MeshError::Format(_))));
+        let short = "solid x\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n";
+        assert!(matches!(Mesh::parse_stl(short.as_bytes(), None), Err(MeshError::Parse { line: 6, .. })));
+    }
+
+    #[test]
+    fn test_mesh_load_from_file() {
+        let path = std::env::temp_dir().join(format!("ray_marcher_mesh_test_{}.obj", std::process::id()));
+        fs::write(&path, CUBE_OBJ).unwrap();
+        let cube = Mesh::load(&path, None);
+        fs::remove_file(&path).unwrap();
+        let cube = cube.unwrap();
+        assert_eq!(cube.get_position(), &Point3D::new(0.0, 0.0, 0.0));
+        assert_eq!(cube.get_source(), Some(path.as_path()));
+        assert!(Mesh::parse_obj(CUBE_OBJ, None).unwrap().describe().is_none());
+        assert!(matches!(Mesh::load("missing.obj", None), Err(MeshError::Io(_))));
+        assert!(matches!(Mesh::load("model.fbx", None), Err(MeshError::Format(_))));
+    }
+}
@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::bvh::Bvh;
+use super::super::scene_file::description::{vec3, MaterialRef, MetaballDescription, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Blobs made of weighted centers. Each center adds weight * K(r / radius) to a field and
+// the surface is where the field reaches the threshold. The field isn't a distance, so
+// how far it is from the threshold is divided by a bound on its gradient, which keeps
+// the estimate from ever overshooting the surface.
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FalloffKernel {
+    InverseSquare, // 1 / (1 + u^2), never reaches zero
+    Wyvill,        // (1 - u^2)^3, zero past the radius so far centers can be skipped
+    Exponential,   // e^(-u^2), never reaches zero
+}
+
+impl FalloffKernel {
+    pub fn value(&self, u: f64) -> f64 {
+        match self {
+            FalloffKernel::InverseSquare => 1.0 / (1.0 + u * u),
+            FalloffKernel::Wyvill => {
+                if u >= 1.0 {
+                    0.0
+                } else {
+                    let a = 1.0 - u * u;
+                    a * a * a
+                }
+            }
+            FalloffKernel::Exponential => (-u * u).exp(),
+        }
+    }
+
+    // How steeply the kernel falls at u
+    pub fn slope(&self, u: f64) -> f64 {
+        match self {
+            FalloffKernel::InverseSquare => 2.0 * u / ((1.0 + u * u) * (1.0 + u * u)),
+            FalloffKernel::Wyvill => {
+                if u >= 1.0 {
+                    0.0
+                } else {
+                    6.0 * u * (1.0 - u * u) * (1.0 - u * u)
+                }
+            }
+            FalloffKernel::Exponential => 2.0 * u * (-u * u).exp(),
+        }
+    }
+
+    // Where the slope peaks. It only rises before and only falls after.
+    fn steepest_at(&self) -> f64 {
+        match self {
+            FalloffKernel::InverseSquare => 1.0 / 3_f64.sqrt(),
+            FalloffKernel::Wyvill => 1.0 / 5_f64.sqrt(),
+            FalloffKernel::Exponential => 1.0 / 2_f64.sqrt(),
+        }
+    }
+
+    // Steepest slope of the kernel over u
+    pub fn max_slope(&self) -> f64 {
+        self.slope(self.steepest_at())
+    }
+
+    // Steepest slope of the kernel for u between `low` and `high`
+    pub fn max_slope_between(&self, low: f64, high: f64) -> f64 {
+        self.slope(self.steepest_at().clamp(low, high))
+    }
+
+    // A c with slope(u) <= c / u^3 for every u, which bounds the pull of balls too far
+    // away to visit one by one
+    pub fn tail_slope(&self) -> f64 {
+        match self {
+            FalloffKernel::InverseSquare => 2.0,                 // Approached as u grows
+            FalloffKernel::Wyvill => 0.375,                      // At u = 1 / sqrt(2)
+            FalloffKernel::Exponential => 8.0 * (-2_f64).exp(), // At u = sqrt(2)
+        }
+    }
+
+    // Smallest u past which the kernel stays below `level`
+    pub fn reach(&self, level: f64) -> f64 {
+        if level >= 1.0 {
+            return 0.0;
+        }
+        match self {
+            FalloffKernel::InverseSquare => (1.0 / level - 1.0).sqrt(),
+            FalloffKernel::Wyvill => (1.0 - level.max(0.0).cbrt()).sqrt(),
+            FalloffKernel::Exponential => (-level.ln()).sqrt(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metaball {
+    pub center: Point3D,
+    pub radius: f64,
+    pub weight: f64, // Negative weights carve into the blob
+}
+
+impl Metaball {
+    pub fn new(center: Point3D, radius: f64, weight: f64) -> Self {
+        Metaball { center, radius, weight }
+    }
+
+    // How fast this ball's field can change per unit of distance
+    fn gradient_bound(&self, kernel: FalloffKernel) -> f64 {
+        self.weight.abs() * kernel.max_slope() / self.radius
+    }
+}
+
+pub struct Metaballs {
+    balls: Vec<Metaball>,
+    kernel: FalloffKernel,
+    threshold: f64,
+    bvh: Bvh, // Over the balls' radii
+    tail_weight: f64, // Sum of |weight| * radius^2, scales the pull of far balls
+    max_radius: f64,
+    position: Point3D,
+    surface_material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, kernel: FalloffKernel, threshold: f64, sm: Option<SurfaceMaterial>) -> Self {
+        assert!(threshold > 0.0, "metaball threshold must be positive, got {}", threshold);
+        let mut m = Metaballs {
+            balls: Vec::new(),
+            kernel,
+            threshold,
+            bvh: Bvh::new(&[]),
+            tail_weight: 0.0,
+            max_radius: 0.0,
+            position: Point3D::new(0.0, 0.0, 0.0),
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        };
+        m.set_balls(balls);
+        m
+    }
+
+    // One ball of the same radius and a weight of 1 per particle
+    pub fn from_particles(particles: &[Point3D], radius: f64, kernel: FalloffKernel, threshold: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Metaballs::new(particles.iter().map(|p| Metaball::new(*p, radius, 1.0)).collect(), kernel, threshold, sm)
+    }
+
+    pub fn set_balls(&mut self, balls: Vec<Metaball>) {
+        let boxes: Vec<BoundingBox> = balls
+            .iter()
+            .map(|b| BoundingBox::new(b.center - b.radius, b.center + b.radius))
+            .collect();
+        self.bvh = Bvh::new(&boxes);
+        self.tail_weight = balls.iter().map(|b| b.weight.abs() * b.radius * b.radius).sum();
+        self.max_radius = balls.iter().map(|b| b.radius).fold(0.0, f64::max);
+        if !balls.is_empty() {
+            let sum = balls.iter().fold(Point3D::new(0.0, 0.0, 0.0), |s, b| s + b.center);
+            self.position = sum / balls.len() as f64;
+        }
+        self.balls = balls;
+    }
+
+    // Moves every ball to the matching particle, keeping radii and weights. When the
+    // number of particles changes the balls are rebuilt with `radius` and a weight of 1.
+    pub fn set_particles(&mut self, particles: &[Point3D], radius: f64) {
+        let balls = if particles.len() == self.balls.len() {
+            particles.iter().zip(self.balls.iter()).map(|(p, b)| Metaball::new(*p, b.radius, b.weight)).collect()
+        } else {
+            particles.iter().map(|p| Metaball::new(*p, radius, 1.0)).collect()
+        };
+        self.set_balls(balls);
+    }
+
+    pub fn get_balls(&self) -> &Vec<Metaball> {
+        &self.balls
+    }
+
+    fn contribution(&self, ball: &Metaball, p: &Point3D) -> f64 {
+        ball.weight * self.kernel.value(p.distance_to(&ball.center) / ball.radius)
+    }
+
+    pub fn field(&self, p: &Point3D) -> f64 {
+        match self.kernel {
+            FalloffKernel::Wyvill => {
+                let mut total = 0.0;
+                self.bvh.visit_within(p, 0.0, |i| total += self.contribution(&self.balls[i], p));
+                total
+            }
+            _ => self.balls.iter().map(|b| self.contribution(b, p)).sum(),
+        }
+    }
+}
+
+impl SceneObject for Metaballs {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        if self.balls.is_empty() {
+            return f64::INFINITY;
+        }
+        // How far p is from the nearest ball's sphere
+        let (_, outside) = self
+            .bvh
+            .closest(p, |i| p.distance_to(&self.balls[i].center) - self.balls[i].radius)
+            .unwrap();
+        match self.kernel {
+            FalloffKernel::Wyvill => {
+                // Nothing outside every ball's radius, so that distance is safe too
+                if outside > 0.0 {
+                    return outside;
+                }
+                // Only balls reachable within one step can steepen the field, so steps
+                // are capped and the gradient bound only sums those balls
+                let cap = self.max_radius;
+                let mut field = 0.0;
+                let mut gradient = 0.0;
+                self.bvh.visit_within(p, cap, |i| {
+                    let ball = &self.balls[i];
+                    field += self.contribution(ball, p);
+                    gradient += ball.gradient_bound(self.kernel);
+                });
+                // Only zero-weight balls in reach, so the field is flat for a whole
+                // capped step
+                if gradient == 0.0 {
+                    return cap.copysign(self.threshold - field);
+                }
+                ((self.threshold - field) / gradient).clamp(-cap, cap)
+            }
+            // Every weight is zero, so the field is flat everywhere
+            _ if self.tail_weight == 0.0 => outside.abs().max(self.max_radius).copysign(self.threshold),
+            _ => {
+                // Every ball pulls on the field, but a global gradient bound grows with
+                // their number. Steps are capped instead: balls that stay `far` from the
+                // whole step only add the kernel's c / u^3 tail, with `far` picked so that
+                // tail alone still allows a full step, and every ball in reach is bounded
+                // by its steepest slope over the distances the step can cover.
+                let cap = self.max_radius.max(outside);
+                let tail = self.kernel.tail_slope() * self.tail_weight;
+                let far = (tail * cap / self.threshold).cbrt().max(cap);
+                let mut gradient = tail / (far * far * far);
+                self.bvh.visit_within(p, cap + far, |i| {
+                    let ball = &self.balls[i];
+                    let d = p.distance_to(&ball.center);
+                    let slope = self.kernel.max_slope_between((d - cap).max(0.0) / ball.radius, (d + cap) / ball.radius);
+                    gradient += ball.weight.abs() / ball.radius * slope;
+                });
+                ((self.threshold - self.field(p)) / gradient).clamp(-cap, cap)
+            }
+        }
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    // Only balls with positive weights can raise the field. Once every one of them is
+    // past the kernel's reach for threshold / total weight, their sum is below it.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let positive: Vec<&Metaball> = self.balls.iter().filter(|b| b.weight > 0.0).collect();
+        let total: f64 = positive.iter().map(|b| b.weight).sum();
+        if total < self.threshold {
+            return Some(BoundingBox::empty());
+        }
+        let reach = self.kernel.reach(self.threshold / total);
+        Some(positive.iter().fold(BoundingBox::empty(), |bounds, b| {
+            bounds.union(&BoundingBox::new(b.center - reach * b.radius, b.center + reach * b.radius))
+        }))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        let balls = self.balls.iter().map(|b| MetaballDescription { center: vec3(&b.center), radius: b.radius, weight: b.weight }).collect();
+        Some(ObjectDescription::Metaballs {
+            balls,
+            kernel: self.kernel,
+            threshold: self.threshold,
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_safe_steps, fibonacci_directions, grid};
+
+    fn cloud() -> Vec<Point3D> {
+        (0..40)
+            .map(|i| {
+                let t = i as f64 * 0.7;
+                Point3D::new(t.cos() * 1.5, (t * 0.3).sin() * 1.2, t.sin() * 1.5)
+            })
+            .collect()
+    }
+
+    fn sample_points() -> Vec<Point3D> {
+        grid((8, 8, 6), 0.4, Point3D::new(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn test_kernel_slopes() {
+        for kernel in [FalloffKernel::InverseSquare, FalloffKernel::Wyvill, FalloffKernel::Exponential] {
+            let h = 1e-6;
+            let steepest = (0..3000)
+                .map(|i| i as f64 * 0.001)
+                .map(|u| (kernel.value(u + h) - kernel.value(u)).abs() / h)
+                .fold(0.0, f64::max);
+            assert!(steepest <= kernel.max_slope());
+            assert!(steepest > kernel.max_slope() - 1e-3);
+            for u in (1..5000).map(|i| i as f64 * 0.002) {
+                assert!(kernel.slope(u) * u * u * u <= kernel.tail_slope() + 1e-12);
+                assert!(kernel.max_slope_between(u, u + 0.3) >= kernel.slope(u + 0.15));
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_ball_surface() {
+        // 1 / (1 + u^2) = 0.5 at u = 1, so the surface sits at the radius
+        let m = Metaballs::new(vec![Metaball::new(Point3D::new(1.0, 0.0, 0.0), 2.0, 1.0)], FalloffKernel::InverseSquare, 0.5, None);
+        assert!(m.signed_distance(&Point3D::new(3.0, 0.0, 0.0)).abs() < 1e-12);
+        assert!(m.signed_distance(&Point3D::new(1.0, 0.0, 0.0)) < 0.0);
+        assert!(m.signed_distance(&Point3D::new(1.0, 4.0, 0.0)) > 0.0);
+    }
+
+    #[test]
+    fn test_balls_merge() {
+        let balls = vec![
+            Metaball::new(Point3D::new(-0.5, 0.0, 0.0), 1.0, 1.0),
+            Metaball::new(Point3D::new(0.5, 0.0, 0.0), 1.0, 1.0),
+        ];
+        let m = Metaballs::new(balls, FalloffKernel::Wyvill, 0.5, None);
+        // The midpoint is outside either ball on its own but inside the pair
+        assert!(FalloffKernel::Wyvill.value(0.5) < 0.5);
+        assert!(m.signed_distance(&Point3D::new(0.0, 0.0, 0.0)) < 0.0);
+    }
+
+    #[test]
+    fn test_negative_weight_carves() {
+        let balls = vec![
+            Metaball::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.0),
+            Metaball::new(Point3D::new(0.0, 0.0, 0.0), 0.5, -2.0),
+        ];
+        let m = Metaballs::new(balls, FalloffKernel::Exponential, 0.3, None);
+        assert!(m.signed_distance(&Point3D::new(0.0, 0.0, 0.0)) > 0.0);
+        assert!(m.signed_distance(&Point3D::new(1.0, 0.0, 0.0)) < 0.0);
+        assert_safe_steps(&m, &sample_points(), &fibonacci_directions(24));
+    }
+
+    #[test]
+    fn test_metaballs_never_overestimate() {
+        for kernel in [FalloffKernel::InverseSquare, FalloffKernel::Wyvill, FalloffKernel::Exponential] {
+            assert_safe_steps(&Metaballs::from_particles(&cloud(), 0.8, kernel, 0.4, None), &sample_points(), &fibonacci_directions(24));
+        }
+    }
+
+    #[test]
+    fn test_many_balls_converge() {
+        // 3375 balls: a gradient bound summed over all of them would take steps too small
+        // to ever reach the surface
+        let centers = grid((7, 7, 7), 1.0, Point3D::new(0.0, 0.0, 0.0));
+        let dir = Point3D::new(1.0, 0.0, 0.0);
+        for (kernel, radius, threshold) in [(FalloffKernel::InverseSquare, 0.1, 1.0), (FalloffKernel::Exponential, 0.25, 0.5)] {
+            let m = Metaballs::from_particles(&centers, radius, kernel, threshold, None);
+            let origin = Point3D::new(-12.0, 0.02, 0.01);
+            let mut t = 0.0;
+            let mut steps = 0;
+            loop {
+                let d = m.signed_distance(&(origin + dir * t));
+                if d < 1e-6 {
+                    break;
+                }
+                t += d;
+                steps += 1;
+                assert!(steps < 200, "{:?} still marching at t = {}", kernel, t);
+            }
+            // Just in front of the ball at x = -7
+            assert!(t > 4.5 && t < 5.0, "{:?} stopped at {}", kernel, t);
+        }
+    }
+
+    #[test]
+    fn test_wyvill_field_matches_brute_force() {
+        let m = Metaballs::from_particles(&cloud(), 0.8, FalloffKernel::Wyvill, 0.4, None);
+        for i in 0..50 {
+            let t = i as f64 * 0.37;
+            let p = Point3D::new(t.sin() * 2.0, t.cos(), (t * 1.3).sin() * 2.0);
+            let brute: f64 = m.balls.iter().map(|b| m.contribution(b, &p)).sum();
+            assert!((m.field(&p) - brute).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_set_particles_keeps_balls() {
+        let balls = vec![Metaball::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5), Metaball::new(Point3D::new(1.0, 0.0, 0.0), 1.0, -1.0)];
+        let mut m = Metaballs::new(balls, FalloffKernel::Wyvill, 0.3, None);
+        m.set_particles(&[Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, 2.0, 0.0)], 3.0);
+        assert_eq!((m.balls[0].radius, m.balls[0].weight), (2.0, 0.5));
+        assert_eq!((m.balls[1].radius, m.balls[1].weight), (1.0, -1.0));
+        assert_eq!(m.balls[1].center, Point3D::new(0.0, 2.0, 0.0));
+        m.set_particles(&[Point3D::new(0.0, 0.0, 0.0)], 3.0);
+        assert_eq!((m.balls[0].radius, m.balls[0].weight), (3.0, 1.0));
+    }
+
+    #[test]
+    fn test_zero_weight_balls() {
+        let balls = vec![Metaball::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 0.0), Metaball::new(Point3D::new(4.0, 0.0, 0.0), 1.0, 0.0)];
+        for kernel in [FalloffKernel::InverseSquare, FalloffKernel::Wyvill, FalloffKernel::Exponential] {
+            let m = Metaballs::new(balls.clone(), kernel, 0.5, None);
+            for p in [Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.5, 0.0, 0.0), Point3D::new(2.0, 0.0, 0.0)] {
+                let d = m.signed_distance(&p);
+                assert!(d > 0.0 && d.is_finite(), "{:?} gave {} at {:?}", kernel, d, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wyvill_far_distance() {
+        let m = Metaballs::from_particles(&[Point3D::new(0.0, 0.0, 0.0)], 1.0, FalloffKernel::Wyvill, 0.5, None);
+        assert_eq!(m.signed_distance(&Point3D::new(5.0, 0.0, 0.0)), 4.0);
+    }
+
+    #[test]
+    fn test_metaballs_bounding_box() {
+        for kernel in [FalloffKernel::InverseSquare, FalloffKernel::Wyvill, FalloffKernel::Exponential] {
+            let m = Metaballs::from_particles(&cloud(), 0.8, kernel, 0.4, None);
+            let b = m.bounding_box().unwrap();
+            for xi in -30..=30 {
+                for yi in -30..=30 {
+                    let p = Point3D::new(xi as f64 * 0.5, yi as f64 * 0.5, (xi + yi) as f64 * 0.1);
+                    if !b.contains(&p) {
+                        assert!(m.field(&p) < m.threshold, "{:?} outside {:?}", p, b);
+                    }
+                }
+            }
+        }
+        let faint = Metaballs::from_particles(&[Point3D::new(0.0, 0.0, 0.0)], 1.0, FalloffKernel::Wyvill, 2.0, None);
+        assert!(faint.bounding_box().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_particles() {
+        let mut m = Metaballs::from_particles(&cloud(), 0.8, FalloffKernel::Wyvill, 0.4, None);
+        m.set_particles(&[Point3D::new(10.0, 0.0, 0.0)], 1.0);
+        assert_eq!(m.get_balls().len(), 1);
+        assert_eq!(m.get_position(), &Point3D::new(10.0, 0.0, 0.0));
+        assert!(m.signed_distance(&Point3D::new(10.0, 0.0, 0.0)) < 0.0);
+    }
+}
@@ -1,5 +1,7 @@
 // use std::cmp;
-use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, Vector3D, DEFAULT_SURFACEMAT};
 
 #[derive(Clone)]
 pub struct Sphere {
@@ -31,12 +33,538 @@ impl SceneObject for Sphere {
     fn get_surface_material(&self) -> SurfaceMaterial {
         self.material.clone()
     }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - self.radius, self.position + self.radius))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Sphere { center: vec3(&self.position), radius: self.radius, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct AxisAlignedBox {
+    half_extents: Point3D, // Distance from the center to each face
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl AxisAlignedBox {
+    pub fn new(pos: Point3D, half_extents: Point3D, sm: Option<SurfaceMaterial>) -> Self {
+        AxisAlignedBox {
+            half_extents,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for AxisAlignedBox {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let q = (*p - self.position).abs() - self.half_extents;
+        q.max(0.0).length() + q.max_component().min(0.0)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - self.half_extents, self.position + self.half_extents))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Box { center: vec3(&self.position), half_extents: vec3(&self.half_extents), material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct RoundedBox {
+    half_extents: Point3D, // Extents of the box before the edges are rounded
+    radius: f64,
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl RoundedBox {
+    pub fn new(pos: Point3D, half_extents: Point3D, radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        RoundedBox {
+            half_extents,
+            radius,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for RoundedBox {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let q = (*p - self.position).abs() - self.half_extents;
+        q.max(0.0).length() + q.max_component().min(0.0) - self.radius
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let h = self.half_extents + self.radius;
+        Some(BoundingBox::new(self.position - h, self.position + h))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::RoundedBox { center: vec3(&self.position), half_extents: vec3(&self.half_extents), radius: self.radius, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct Torus {
+    major_radius: f64, // Distance from the center to the middle of the tube
+    minor_radius: f64, // Radius of the tube
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Torus {
+    pub fn new(pos: Point3D, major_radius: f64, minor_radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Torus {
+            major_radius,
+            minor_radius,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Torus {
+    // The torus lies in the XZ plane
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        let qx = (local.x * local.x + local.z * local.z).sqrt() - self.major_radius;
+        (qx * qx + local.y * local.y).sqrt() - self.minor_radius
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (r, t) = (self.major_radius + self.minor_radius, self.minor_radius);
+        Some(BoundingBox::new(self.position - Point3D::new(r, t, r), self.position + Point3D::new(r, t, r)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Torus { center: vec3(&self.position), major_radius: self.major_radius, minor_radius: self.minor_radius, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct Capsule {
+    start: Point3D,
+    end: Point3D,
+    radius: f64,
+    position: Point3D, // Midpoint between the two end caps
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Capsule {
+    pub fn new(start: Point3D, end: Point3D, radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Capsule {
+            start,
+            end,
+            radius,
+            position: (start + end) / 2.0,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Capsule {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let pa = *p - self.start;
+        let ba = self.end - self.start;
+        let h = (pa.dot(&ba) / ba.dot(&ba)).clamp(0.0, 1.0);
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::from_points([self.start, self.end].iter()).padded(self.radius))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Capsule { start: vec3(&self.start), end: vec3(&self.end), radius: self.radius, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct CappedCylinder {
+    radius: f64,
+    half_height: f64,
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl CappedCylinder {
+    pub fn new(pos: Point3D, radius: f64, half_height: f64, sm: Option<SurfaceMaterial>) -> Self {
+        CappedCylinder {
+            radius,
+            half_height,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for CappedCylinder {
+    // The cylinder's axis runs along Y
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        let dx = (local.x * local.x + local.z * local.z).sqrt() - self.radius;
+        let dy = local.y.abs() - self.half_height;
+        dx.max(dy).min(0.0) + (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - Point3D::new(self.radius, self.half_height, self.radius), self.position + Point3D::new(self.radius, self.half_height, self.radius)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::CappedCylinder { center: vec3(&self.position), radius: self.radius, half_height: self.half_height, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct InfiniteCylinder {
+    radius: f64,
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl InfiniteCylinder {
+    pub fn new(pos: Point3D, radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        InfiniteCylinder {
+            radius,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for InfiniteCylinder {
+    // The cylinder's axis runs along Y
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        (local.x * local.x + local.z * local.z).sqrt() - self.radius
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::InfiniteCylinder { center: vec3(&self.position), radius: self.radius, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct Cone {
+    half_height: f64,
+    base_radius: f64,
+    top_radius: f64, // Zero for a pointed cone
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Cone {
+    pub fn new(pos: Point3D, height: f64, radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Cone::new_capped(pos, height, radius, 0.0, sm)
+    }
+
+    pub fn new_capped(pos: Point3D, height: f64, base_radius: f64, top_radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Cone {
+            half_height: height / 2.0,
+            base_radius,
+            top_radius,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Cone {
+    // The cone's axis runs along Y with the base at the bottom
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        let (h, r1, r2) = (self.half_height, self.base_radius, self.top_radius);
+        let qx = (local.x * local.x + local.z * local.z).sqrt();
+        let qy = local.y;
+
+        let cap_radius = if qy < 0.0 { r1 } else { r2 };
+        let (ca_x, ca_y) = (qx - qx.min(cap_radius), qy.abs() - h);
+        let (k2_x, k2_y) = (r2 - r1, 2.0 * h);
+        let t = (((r2 - qx) * k2_x + (h - qy) * k2_y) / (k2_x * k2_x + k2_y * k2_y)).clamp(0.0, 1.0);
+        let (cb_x, cb_y) = (qx - r2 + k2_x * t, qy - h + k2_y * t);
+
+        let sign = if cb_x < 0.0 && ca_y < 0.0 { -1.0 } else { 1.0 };
+        sign * (ca_x * ca_x + ca_y * ca_y).min(cb_x * cb_x + cb_y * cb_y).sqrt()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let r = self.base_radius.max(self.top_radius);
+        Some(BoundingBox::new(self.position - Point3D::new(r, self.half_height, r), self.position + Point3D::new(r, self.half_height, r)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Cone {
+            center: vec3(&self.position),
+            height: self.half_height * 2.0,
+            base_radius: self.base_radius,
+            top_radius: self.top_radius,
+            material: MaterialRef::describe(&self.material),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Ellipsoid {
+    radii: Point3D,
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Ellipsoid {
+    pub fn new(pos: Point3D, radii: Point3D, sm: Option<SurfaceMaterial>) -> Self {
+        Ellipsoid {
+            radii,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Ellipsoid {
+    // Not exact: the distance in the unit sphere space is scaled by the smallest radius,
+    // which never overestimates the distance to the real surface
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        ((local / self.radii).length() - 1.0) * self.radii.min_component()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - self.radii, self.position + self.radii))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Ellipsoid { center: vec3(&self.position), radii: vec3(&self.radii), material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct HexagonalPrism {
+    radius: f64, // Distance from the center to the middle of a side
+    half_length: f64,
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl HexagonalPrism {
+    pub fn new(pos: Point3D, radius: f64, half_length: f64, sm: Option<SurfaceMaterial>) -> Self {
+        HexagonalPrism {
+            radius,
+            half_length,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for HexagonalPrism {
+    // The prism's axis runs along Z
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let (kx, ky, kz) = (-0.866_025_403_8, 0.5, 0.577_350_269);
+        let mut q = (*p - self.position).abs();
+        let fold = 2.0 * (kx * q.x + ky * q.y).min(0.0);
+        q.x -= fold * kx;
+        q.y -= fold * ky;
+
+        let edge_x = q.x - q.x.clamp(-kz * self.radius, kz * self.radius);
+        let edge_y = q.y - self.radius;
+        let dx = (edge_x * edge_x + edge_y * edge_y).sqrt() * edge_y.signum();
+        let dy = q.z - self.half_length;
+        dx.max(dy).min(0.0) + (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        // The corners are farther out than the sides
+        let r = self.radius * 2.0 / 3_f64.sqrt();
+        Some(BoundingBox::new(self.position - Point3D::new(r, r, self.half_length), self.position + Point3D::new(r, r, self.half_length)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::HexagonalPrism { center: vec3(&self.position), radius: self.radius, half_length: self.half_length, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct Octahedron {
+    size: f64, // Distance from the center to each vertex
+    position: Point3D,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Octahedron {
+    pub fn new(pos: Point3D, size: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Octahedron {
+            size,
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Octahedron {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let p = (*p - self.position).abs();
+        let s = self.size;
+        let m = p.x + p.y + p.z - s;
+        let q = if 3.0 * p.x < m {
+            p
+        } else if 3.0 * p.y < m {
+            Point3D::new(p.y, p.z, p.x)
+        } else if 3.0 * p.z < m {
+            Point3D::new(p.z, p.x, p.y)
+        } else {
+            return m * 0.577_350_27;
+        };
+        let k = (0.5 * (q.z - q.y + s)).clamp(0.0, s);
+        Point3D::new(q.x, q.y - s + k, q.z - k).length()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.position - self.size, self.position + self.size))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Octahedron { center: vec3(&self.position), size: self.size, material: MaterialRef::describe(&self.material) })
+    }
+}
+
+#[derive(Clone)]
+pub struct Plane {
+    normal: Vector3D,
+    position: Point3D, // Any point on the plane
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl Plane {
+    pub fn new(pos: Point3D, normal: Vector3D, sm: Option<SurfaceMaterial>) -> Self {
+        Plane {
+            normal: normal.get_norm(),
+            position: pos,
+            material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+        }
+    }
+}
+
+impl SceneObject for Plane {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        (*p - self.position).dot(&self.normal.to_point())
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.material
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Plane { point: vec3(&self.position), normal: vec3(&self.normal.to_point()), material: MaterialRef::describe(&self.material) })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::super::Vector3D;
     use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_close, assert_within};
 
     #[test]
     fn test_sphere_sdf_x() {
@@ -113,4 +641,183 @@ mod test {
                 < 0.001
         );
     }
+    #[test]
+    fn test_box_sdf_x() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 3.0), None);
+        assert_eq!(b.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.0);
+    }
+    #[test]
+    fn test_box_sdf_corner() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), None);
+        assert_close(b.signed_distance(&Point3D::new(2.0, 2.0, 1.0)), 2_f64.sqrt());
+    }
+    #[test]
+    fn test_box_sdf_inner() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 3.0), None);
+        assert_eq!(b.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -1.0);
+    }
+    #[test]
+    fn test_rounded_box_sdf_x() {
+        let b = RoundedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), 0.5, None);
+        assert_eq!(b.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 0.5);
+    }
+    #[test]
+    fn test_rounded_box_sdf_corner() {
+        let b = RoundedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), 0.5, None);
+        assert_close(b.signed_distance(&Point3D::new(2.0, 2.0, 2.0)), 3_f64.sqrt() - 0.5);
+    }
+    #[test]
+    fn test_torus_sdf_center() {
+        let t = Torus::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5, None);
+        assert_eq!(t.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), 1.5);
+    }
+    #[test]
+    fn test_torus_sdf_y() {
+        let t = Torus::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5, None);
+        assert_eq!(t.signed_distance(&Point3D::new(2.0, 1.0, 0.0)), 0.5);
+    }
+    #[test]
+    fn test_torus_sdf_inner() {
+        let t = Torus::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5, None);
+        assert_eq!(t.signed_distance(&Point3D::new(0.0, 0.0, 2.0)), -0.5);
+    }
+    #[test]
+    fn test_capsule_sdf_side() {
+        let c = Capsule::new(Point3D::new(0.0, -1.0, 0.0), Point3D::new(0.0, 1.0, 0.0), 0.5, None);
+        assert_eq!(c.signed_distance(&Point3D::new(2.0, 0.5, 0.0)), 1.5);
+    }
+    #[test]
+    fn test_capsule_sdf_cap() {
+        let c = Capsule::new(Point3D::new(0.0, -1.0, 0.0), Point3D::new(0.0, 1.0, 0.0), 0.5, None);
+        assert_eq!(c.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 1.5);
+    }
+    #[test]
+    fn test_capped_cylinder_sdf_side() {
+        let c = CappedCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_eq!(c.signed_distance(&Point3D::new(0.0, 0.0, 3.0)), 2.0);
+    }
+    #[test]
+    fn test_capped_cylinder_sdf_cap() {
+        let c = CappedCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_eq!(c.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 1.0);
+    }
+    #[test]
+    fn test_capped_cylinder_sdf_edge() {
+        let c = CappedCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_close(c.signed_distance(&Point3D::new(2.0, 3.0, 0.0)), 2_f64.sqrt());
+    }
+    #[test]
+    fn test_infinite_cylinder_sdf() {
+        let c = InfiniteCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        assert_eq!(c.signed_distance(&Point3D::new(3.0, 1000.0, 0.0)), 2.0);
+    }
+    #[test]
+    fn test_cone_sdf_below_base() {
+        let c = Cone::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.0, None);
+        assert_eq!(c.signed_distance(&Point3D::new(0.0, -3.0, 0.0)), 2.0);
+    }
+    #[test]
+    fn test_cone_sdf_above_tip() {
+        let c = Cone::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.0, None);
+        assert_eq!(c.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 2.0);
+    }
+    #[test]
+    fn test_cone_sdf_side() {
+        // The slanted side runs from (1, -1) to (0, 1) in the radial/height plane
+        let c = Cone::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.0, None);
+        let expected = (2.0 * 1.0 + 1.0 * 0.0 - 1.0) / 5_f64.sqrt();
+        assert_close(c.signed_distance(&Point3D::new(1.0, 0.0, 0.0)), expected);
+    }
+    #[test]
+    fn test_cone_sdf_inner() {
+        let c = Cone::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 1.0, None);
+        assert!(c.signed_distance(&Point3D::new(0.0, -0.5, 0.0)) < 0.0);
+    }
+    #[test]
+    fn test_ellipsoid_sdf_axes() {
+        let e = Ellipsoid::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 3.0), None);
+        assert_eq!(e.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.0);
+        assert_close(e.signed_distance(&Point3D::new(0.0, 2.0, 0.0)), 0.0);
+    }
+    #[test]
+    fn test_ellipsoid_sdf_bounded() {
+        // Along the long axis the true distance is 1, the estimate must not exceed it
+        let e = Ellipsoid::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 3.0), None);
+        let d = e.signed_distance(&Point3D::new(0.0, 0.0, 4.0));
+        assert!(d > 0.0 && d <= 1.0);
+    }
+    #[test]
+    fn test_hex_prism_sdf_side() {
+        let h = HexagonalPrism::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_close(h.signed_distance(&Point3D::new(0.0, 3.0, 0.0)), 2.0);
+    }
+    #[test]
+    fn test_hex_prism_sdf_end() {
+        let h = HexagonalPrism::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_close(h.signed_distance(&Point3D::new(0.0, 0.0, 3.0)), 1.0);
+    }
+    #[test]
+    fn test_hex_prism_sdf_inner() {
+        let h = HexagonalPrism::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        assert_close(h.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -1.0);
+    }
+    #[test]
+    fn test_octahedron_sdf_vertex() {
+        let o = Octahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        assert_close(o.signed_distance(&Point3D::new(2.0, 0.0, 0.0)), 1.0);
+    }
+    #[test]
+    fn test_octahedron_sdf_face() {
+        let o = Octahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        // The distance scales by a rounded 1 / sqrt(3)
+        assert_within(o.signed_distance(&Point3D::new(1.0, 1.0, 1.0)), 2.0 / 3_f64.sqrt(), 1e-6);
+    }
+    #[test]
+    fn test_octahedron_sdf_inner() {
+        let o = Octahedron::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        assert_within(o.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -1.0 / 3_f64.sqrt(), 1e-6);
+    }
+    #[test]
+    fn test_plane_sdf() {
+        let pl = Plane::new(Point3D::new(0.0, -1.0, 0.0), Vector3D::new(0.0, 2.0, 0.0), None);
+        assert_eq!(pl.signed_distance(&Point3D::new(5.0, 1.0, -5.0)), 2.0);
+        assert_eq!(pl.signed_distance(&Point3D::new(5.0, -3.0, -5.0)), -2.0);
+    }
+    #[test]
+    fn test_primitive_material() {
+        let sm = SurfaceMaterial::new(crate::ray_marcher::color_data_types::Color::new(0.0, 1.0, 0.0), 0.25, 0.75);
+        let t = Torus::new(Point3D::new(0.0, 0.0, 0.0), 2.0, 0.5, Some(sm));
+        assert_eq!(t.get_surface_material().metallic, 0.25);
+    }    #[test]
+    fn test_primitive_bounding_boxes() {
+        let c = Point3D::new(1.0, -2.0, 0.5);
+        let bounded: Vec<Box<dyn SceneObject>> = vec![
+            Box::new(Sphere::new(c, 1.5, None)),
+            Box::new(AxisAlignedBox::new(c, Point3D::new(1.0, 2.0, 0.5), None)),
+            Box::new(RoundedBox::new(c, Point3D::new(1.0, 2.0, 0.5), 0.25, None)),
+            Box::new(Torus::new(c, 2.0, 0.5, None)),
+            Box::new(Capsule::new(c, Point3D::new(3.0, 1.0, -1.0), 0.5, None)),
+            Box::new(CappedCylinder::new(c, 1.0, 2.0, None)),
+            Box::new(Cone::new_capped(c, 2.0, 1.0, 0.5, None)),
+            Box::new(Ellipsoid::new(c, Point3D::new(1.0, 2.0, 3.0), None)),
+            Box::new(HexagonalPrism::new(c, 1.0, 2.0, None)),
+            Box::new(Octahedron::new(c, 2.0, None)),
+        ];
+        for o in bounded.iter() {
+            let b = o.bounding_box().unwrap();
+            // Nothing outside the box is inside the object
+            for xi in -20..=20 {
+                for yi in -20..=20 {
+                    for zi in -20..=20 {
+                        let p = c + Point3D::new(xi as f64, yi as f64, zi as f64) * 0.2;
+                        if !b.contains(&p) {
+                            assert!(o.signed_distance(&p) > 0.0, "{:?} outside {:?}", p, b);
+                        }
+                    }
+                }
+            }
+        }
+        assert!(Plane::new(c, Vector3D::new(0.0, 1.0, 0.0), None).bounding_box().is_none());
+        assert!(InfiniteCylinder::new(c, 1.0, None).bounding_box().is_none());
+    }
 }
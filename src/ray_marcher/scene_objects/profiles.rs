@@ -0,0 +1,606 @@
+use std::f64::consts::TAU;
+use std::ops::{Add, Mul, Sub};
+
+use super::super::scene_file::description::{vec2, vec3, MaterialRef, ObjectDescription, ProfileDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::super::threed_data_types::Axis;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// 2D signed distance profiles and the operators that lift them into 3D objects, by
+// revolving them around an axis or extruding them along one. Both keep the distances
+// exact as long as the profile's are.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point2D {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point2D {
+    pub fn new(x: f64, y: f64) -> Self {
+        Point2D { x, y }
+    }
+
+    pub fn dot(&self, p: &Self) -> f64 {
+        self.x * p.x + self.y * p.y
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn cross(&self, p: &Self) -> f64 { // z of the 3D cross product
+        self.x * p.y - self.y * p.x
+    }
+}
+
+impl Add for Point2D {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Point2D::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl Sub for Point2D {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Point2D::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl Mul<f64> for Point2D {
+    type Output = Self;
+    fn mul(self, rhs: f64) -> Self {
+        Point2D::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+pub trait Profile: Send + Sync {
+    fn signed_distance(&self, p: &Point2D) -> f64;
+    fn bounds(&self) -> Option<(Point2D, Point2D)> { // Corners of a rectangle holding the shape, None if unbounded
+        None
+    }
+    fn describe(&self) -> Option<ProfileDescription> { // How the profile is written to a scene file, None if it can't be
+        None
+    }
+}
+
+impl Profile for Box<dyn Profile> {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        self.as_ref().signed_distance(p)
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        self.as_ref().bounds()
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        self.as_ref().describe()
+    }
+}
+
+// The rectangle around some points, grown by `pad` on every side
+pub fn bounds_around<I>(points: I, pad: f64) -> (Point2D, Point2D) where I: IntoIterator<Item = Point2D> {
+    let mut min = Point2D::new(f64::INFINITY, f64::INFINITY);
+    let mut max = Point2D::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for p in points {
+        min = Point2D::new(min.x.min(p.x), min.y.min(p.y));
+        max = Point2D::new(max.x.max(p.x), max.y.max(p.y));
+    }
+    (min - Point2D::new(pad, pad), max + Point2D::new(pad, pad))
+}
+
+#[derive(Clone, Debug)]
+pub struct Circle {
+    center: Point2D,
+    radius: f64,
+}
+
+#[allow(dead_code)]
+impl Circle {
+    pub fn new(center: Point2D, radius: f64) -> Self {
+        Circle { center, radius }
+    }
+}
+
+impl Profile for Circle {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        (*p - self.center).length() - self.radius
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around([self.center], self.radius))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::Circle { center: vec2(&self.center), radius: self.radius })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rectangle {
+    center: Point2D,
+    half_extents: Point2D,
+}
+
+#[allow(dead_code)]
+impl Rectangle {
+    pub fn new(center: Point2D, half_extents: Point2D) -> Self {
+        Rectangle { center, half_extents }
+    }
+}
+
+impl Profile for Rectangle {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        let q = Point2D::new((p.x - self.center.x).abs() - self.half_extents.x, (p.y - self.center.y).abs() - self.half_extents.y);
+        Point2D::new(q.x.max(0.0), q.y.max(0.0)).length() + q.x.max(q.y).min(0.0)
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some((self.center - self.half_extents, self.center + self.half_extents))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::Rectangle { center: vec2(&self.center), half_extents: vec2(&self.half_extents) })
+    }
+}
+
+// Any simple polygon, convex or not, in either winding order
+#[derive(Clone, Debug)]
+pub struct Polygon {
+    vertices: Vec<Point2D>,
+}
+
+#[allow(dead_code)]
+impl Polygon {
+    pub fn new(vertices: Vec<Point2D>) -> Self {
+        assert!(vertices.len() >= 3, "a polygon needs at least 3 vertices, got {}", vertices.len());
+        Polygon { vertices }
+    }
+}
+
+impl Profile for Polygon {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        let v = &self.vertices;
+        let mut d = (*p - v[0]).dot(&(*p - v[0]));
+        let mut sign = 1.0;
+        let mut j = v.len() - 1;
+        for i in 0..v.len() {
+            let e = v[j] - v[i];
+            let w = *p - v[i];
+            let b = w - e * (w.dot(&e) / e.dot(&e)).clamp(0.0, 1.0);
+            d = d.min(b.dot(&b));
+            // Flip for every edge a ray to the right of the point crosses
+            let c = [p.y >= v[i].y, p.y < v[j].y, e.x * w.y > e.y * w.x];
+            if c.iter().all(|c| *c) || c.iter().all(|c| !*c) {
+                sign = -sign;
+            }
+            j = i;
+        }
+        sign * d.sqrt()
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around(self.vertices.iter().cloned(), 0.0))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::Polygon { vertices: self.vertices.iter().map(vec2).collect() })
+    }
+}
+
+// A line segment thickened by a radius, rounded at both ends
+#[derive(Clone, Debug)]
+pub struct Segment {
+    start: Point2D,
+    end: Point2D,
+    radius: f64,
+}
+
+#[allow(dead_code)]
+impl Segment {
+    pub fn new(start: Point2D, end: Point2D, radius: f64) -> Self {
+        Segment { start, end, radius }
+    }
+}
+
+fn segment_distance(p: &Point2D, a: &Point2D, b: &Point2D) -> f64 {
+    let pa = *p - *a;
+    let ba = *b - *a;
+    let len = ba.dot(&ba);
+    let h = if len > 0.0 { (pa.dot(&ba) / len).clamp(0.0, 1.0) } else { 0.0 };
+    (pa - ba * h).length()
+}
+
+impl Profile for Segment {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        segment_distance(p, &self.start, &self.end) - self.radius
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around([self.start, self.end], self.radius))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::Segment { start: vec2(&self.start), end: vec2(&self.end), radius: self.radius })
+    }
+}
+
+// A quadratic Bezier curve from `start` to `end` pulled towards `control`, thickened by a radius
+#[derive(Clone, Debug)]
+pub struct QuadraticBezier {
+    start: Point2D,
+    control: Point2D,
+    end: Point2D,
+    radius: f64,
+}
+
+#[allow(dead_code)]
+impl QuadraticBezier {
+    pub fn new(start: Point2D, control: Point2D, end: Point2D, radius: f64) -> Self {
+        QuadraticBezier { start, control, end, radius }
+    }
+
+    pub fn point_at(&self, t: f64) -> Point2D {
+        self.start * ((1.0 - t) * (1.0 - t)) + self.control * (2.0 * t * (1.0 - t)) + self.end * (t * t)
+    }
+
+    // Distance to the curve itself, the closest parameter solves a cubic
+    fn curve_distance(&self, p: &Point2D) -> f64 {
+        let a = self.control - self.start;
+        let b = self.start - self.control * 2.0 + self.end;
+        if b.dot(&b) < 1e-12 {
+            // Control point halfway between the ends, the curve is a straight line
+            return segment_distance(p, &self.start, &self.end);
+        }
+        let c = a * 2.0;
+        let d = self.start - *p;
+        let kk = 1.0 / b.dot(&b);
+        let kx = kk * a.dot(&b);
+        let ky = kk * (2.0 * a.dot(&a) + d.dot(&b)) / 3.0;
+        let kz = kk * d.dot(&a);
+        let pp = ky - kx * kx;
+        let q = kx * (2.0 * kx * kx - 3.0 * ky) + kz;
+        let h = q * q + 4.0 * pp * pp * pp;
+        let distance_at = |t: f64| {
+            let t = t.clamp(0.0, 1.0);
+            (d + (c + b * t) * t).length()
+        };
+        if h >= 0.0 {
+            // One real root
+            let h = h.sqrt();
+            let x = ((h - q) / 2.0, (-h - q) / 2.0);
+            distance_at(x.0.cbrt() + x.1.cbrt() - kx)
+        } else {
+            // Three real roots, the middle one is never the closest
+            let z = (-pp).sqrt();
+            let v = (q / (pp * z * 2.0)).clamp(-1.0, 1.0).acos() / 3.0;
+            let m = v.cos();
+            let n = v.sin() * 3_f64.sqrt();
+            distance_at((m + m) * z - kx).min(distance_at((-n - m) * z - kx))
+        }
+    }
+}
+
+impl Profile for QuadraticBezier {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        self.curve_distance(p) - self.radius
+    }
+
+    // The curve never leaves the triangle of its control points
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around([self.start, self.control, self.end], self.radius))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::QuadraticBezier { start: vec2(&self.start), control: vec2(&self.control), end: vec2(&self.end), radius: self.radius })
+    }
+}
+
+// Part of a circle thickened by a radius. It starts at angle `start` (radians, counter
+// clockwise from +x) and sweeps `sweep` radians, clockwise when negative.
+#[derive(Clone, Debug)]
+pub struct Arc {
+    center: Point2D,
+    arc_radius: f64,
+    start: f64,
+    sweep: f64,
+    radius: f64,
+}
+
+#[allow(dead_code)]
+impl Arc {
+    pub fn new(center: Point2D, arc_radius: f64, start: f64, sweep: f64, radius: f64) -> Self {
+        // Stored counter clockwise so only the start changes with the direction
+        let (start, sweep) = if sweep < 0.0 { (start + sweep, -sweep) } else { (start, sweep) };
+        Arc { center, arc_radius, start, sweep: sweep.min(TAU), radius }
+    }
+
+    pub fn point_at(&self, angle: f64) -> Point2D {
+        self.center + Point2D::new(angle.cos(), angle.sin()) * self.arc_radius
+    }
+}
+
+impl Profile for Arc {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        let q = *p - self.center;
+        let around = (q.y.atan2(q.x) - self.start).rem_euclid(TAU);
+        let curve = if around <= self.sweep {
+            (q.length() - self.arc_radius).abs()
+        } else {
+            // Past the ends the closest point is one of them
+            (*p - self.point_at(self.start)).length().min((*p - self.point_at(self.start + self.sweep)).length())
+        };
+        curve - self.radius
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around([self.center], self.arc_radius + self.radius))
+    }
+
+    fn describe(&self) -> Option<ProfileDescription> {
+        Some(ProfileDescription::Arc {
+            center: vec2(&self.center),
+            arc_radius: self.arc_radius,
+            start: self.start.to_degrees(),
+            sweep: self.sweep.to_degrees(),
+            radius: self.radius,
+        })
+    }
+}
+
+// Spins a profile around `axis` through `pos`. The profile's x is the distance from the
+// axis and its y runs along the axis, so it should sit at x >= 0.
+pub struct Revolve<P> where P: Profile {
+    profile: P,
+    position: Point3D,
+    axis: Axis,
+    surface_material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl<P> Revolve<P> where P: Profile {
+    pub fn new(profile: P, pos: Point3D, axis: Axis, sm: Option<SurfaceMaterial>) -> Self {
+        Revolve { profile, position: pos, axis, surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT) }
+    }
+}
+
+impl<P> SceneObject for Revolve<P> where P: Profile {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        let (u_axis, v_axis) = self.axis.perpendicular();
+        let radial = local.get_axis(u_axis).hypot(local.get_axis(v_axis));
+        self.profile.signed_distance(&Point2D::new(radial, local.get_axis(self.axis)))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (min, max) = self.profile.bounds()?;
+        let reach = min.x.abs().max(max.x.abs());
+        let (u_axis, v_axis) = self.axis.perpendicular();
+        let mut b = BoundingBox::new(self.position, self.position);
+        for a in [u_axis, v_axis] {
+            b.min.set_axis(a, self.position.get_axis(a) - reach);
+            b.max.set_axis(a, self.position.get_axis(a) + reach);
+        }
+        b.min.set_axis(self.axis, self.position.get_axis(self.axis) + min.y);
+        b.max.set_axis(self.axis, self.position.get_axis(self.axis) + max.y);
+        Some(b)
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Revolve {
+            profile: self.profile.describe()?,
+            center: vec3(&self.position),
+            axis: self.axis,
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+// Sweeps a profile `half_depth` to either side of `pos` along `axis`. The profile's x and
+// y follow the two perpendicular axes in right-handed order (for Z that is X then Y).
+pub struct Extrude<P> where P: Profile {
+    profile: P,
+    position: Point3D,
+    axis: Axis,
+    half_depth: f64,
+    surface_material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl<P> Extrude<P> where P: Profile {
+    pub fn new(profile: P, pos: Point3D, axis: Axis, half_depth: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Extrude { profile, position: pos, axis, half_depth, surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT) }
+    }
+
+    pub fn get_profile(&self) -> &P {
+        &self.profile
+    }
+
+    pub fn get_axis(&self) -> Axis {
+        self.axis
+    }
+
+    pub fn get_half_depth(&self) -> f64 {
+        self.half_depth
+    }
+}
+
+impl<P> SceneObject for Extrude<P> where P: Profile {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = *p - self.position;
+        let (u_axis, v_axis) = self.axis.perpendicular();
+        let across = self.profile.signed_distance(&Point2D::new(local.get_axis(u_axis), local.get_axis(v_axis)));
+        let along = local.get_axis(self.axis).abs() - self.half_depth;
+        across.max(along).min(0.0) + Point2D::new(across.max(0.0), along.max(0.0)).length()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (min, max) = self.profile.bounds()?;
+        let (u_axis, v_axis) = self.axis.perpendicular();
+        let mut b = BoundingBox::new(self.position, self.position);
+        for (a, lo, hi) in [(u_axis, min.x, max.x), (v_axis, min.y, max.y), (self.axis, -self.half_depth, self.half_depth)] {
+            b.min.set_axis(a, self.position.get_axis(a) + lo);
+            b.max.set_axis(a, self.position.get_axis(a) + hi);
+        }
+        Some(b)
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Extrude {
+            profile: self.profile.describe()?,
+            center: vec3(&self.position),
+            axis: self.axis,
+            half_depth: self.half_depth,
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, CappedCylinder, Torus};
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_close, grid};
+
+    fn sample_points() -> Vec<Point3D> {
+        grid((5, 5, 5), 0.55, Point3D::new(0.01, 0.0, -0.02))
+    }
+
+    #[test]
+    fn test_circle_and_rectangle() {
+        let c = Circle::new(Point2D::new(1.0, 0.0), 1.0);
+        assert_close(c.signed_distance(&Point2D::new(1.0, 0.0)), -1.0);
+        assert_close(c.signed_distance(&Point2D::new(4.0, 0.0)), 2.0);
+        let r = Rectangle::new(Point2D::new(0.0, 0.0), Point2D::new(2.0, 1.0));
+        assert_close(r.signed_distance(&Point2D::new(0.0, 0.5)), -0.5);
+        assert_close(r.signed_distance(&Point2D::new(5.0, 5.0)), 5.0);
+    }
+
+    #[test]
+    fn test_polygon_matches_rectangle() {
+        let square = Polygon::new(vec![Point2D::new(-2.0, -1.0), Point2D::new(2.0, -1.0), Point2D::new(2.0, 1.0), Point2D::new(-2.0, 1.0)]);
+        let reversed = Polygon::new(square.vertices.iter().rev().cloned().collect());
+        let r = Rectangle::new(Point2D::new(0.0, 0.0), Point2D::new(2.0, 1.0));
+        for p in sample_points() {
+            let q = Point2D::new(p.x, p.y);
+            assert_close(square.signed_distance(&q), r.signed_distance(&q));
+            assert_close(reversed.signed_distance(&q), r.signed_distance(&q));
+        }
+    }
+
+    #[test]
+    fn test_concave_polygon() {
+        // An L shape, the notch at the top right is outside
+        let l = Polygon::new(vec![
+            Point2D::new(0.0, 0.0), Point2D::new(2.0, 0.0), Point2D::new(2.0, 1.0),
+            Point2D::new(1.0, 1.0), Point2D::new(1.0, 2.0), Point2D::new(0.0, 2.0),
+        ]);
+        assert_close(l.signed_distance(&Point2D::new(1.5, 1.5)), 0.5);
+        assert_close(l.signed_distance(&Point2D::new(0.5, 1.5)), -0.5);
+        assert_close(l.signed_distance(&Point2D::new(1.5, 0.25)), -0.25);
+    }
+
+    #[test]
+    fn test_segment() {
+        let s = Segment::new(Point2D::new(0.0, 0.0), Point2D::new(2.0, 0.0), 0.5);
+        assert_close(s.signed_distance(&Point2D::new(1.0, 2.0)), 1.5);
+        assert_close(s.signed_distance(&Point2D::new(-1.0, 0.0)), 0.5);
+        assert_close(s.signed_distance(&Point2D::new(1.0, 0.0)), -0.5);
+    }
+
+    #[test]
+    fn test_arc() {
+        // The upper half of a unit circle, drawn clockwise from the left
+        let arc = Arc::new(Point2D::new(0.0, 0.0), 1.0, std::f64::consts::PI, -std::f64::consts::PI, 0.1);
+        assert_close(arc.signed_distance(&Point2D::new(0.0, 3.0)), 1.9);
+        assert_close(arc.signed_distance(&Point2D::new(0.0, 0.0)), 0.9);
+        assert_close(arc.signed_distance(&Point2D::new(0.6, 0.8)), -0.1);
+        // Below the ends, the closest points are the ends themselves
+        assert_close(arc.signed_distance(&Point2D::new(1.0, -2.0)), 1.9);
+        assert_close(arc.signed_distance(&Point2D::new(0.0, -1.0)), 2_f64.sqrt() - 0.1);
+        let full = Arc::new(Point2D::new(0.0, 0.0), 1.0, 0.0, 10.0, 0.0);
+        assert_close(full.signed_distance(&Point2D::new(0.0, -3.0)), 2.0);
+    }
+
+    #[test]
+    fn test_bezier_matches_sampled_curve() {
+        let curves = [
+            QuadraticBezier::new(Point2D::new(-2.0, 0.0), Point2D::new(0.0, 3.0), Point2D::new(2.0, 0.0), 0.0),
+            QuadraticBezier::new(Point2D::new(0.0, 0.0), Point2D::new(3.0, 0.5), Point2D::new(-1.0, 1.0), 0.0),
+            QuadraticBezier::new(Point2D::new(-1.0, -1.0), Point2D::new(0.0, 0.0), Point2D::new(1.0, 1.0), 0.0),
+        ];
+        for curve in curves.iter() {
+            let samples: Vec<Point2D> = (0..=5000).map(|i| curve.point_at(i as f64 / 5000.0)).collect();
+            for p in sample_points() {
+                let q = Point2D::new(p.x, p.y);
+                let brute = samples.iter().map(|s| (q - *s).length()).fold(f64::INFINITY, f64::min);
+                let d = curve.signed_distance(&q);
+                assert!(d <= brute + 1e-9 && d > brute - 1e-3, "{} vs {} at {:?}", d, brute, q);
+            }
+        }
+    }
+
+    #[test]
+    fn test_revolve_circle_is_torus() {
+        let revolved = Revolve::new(Circle::new(Point2D::new(2.0, 0.0), 0.5), Point3D::new(0.0, 1.0, 0.0), Axis::Y, None);
+        let torus = Torus::new(Point3D::new(0.0, 1.0, 0.0), 2.0, 0.5, None);
+        for p in sample_points() {
+            assert_close(revolved.signed_distance(&p), torus.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_revolve_rectangle_is_cylinder() {
+        let revolved = Revolve::new(Rectangle::new(Point2D::new(0.0, 0.0), Point2D::new(1.0, 2.0)), Point3D::new(0.0, 0.0, 0.0), Axis::Y, None);
+        let cylinder = CappedCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        for p in sample_points() {
+            assert_close(revolved.signed_distance(&p), cylinder.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_extrude_rectangle_is_box() {
+        let extruded = Extrude::new(Rectangle::new(Point2D::new(0.0, 0.0), Point2D::new(1.0, 2.0)), Point3D::new(0.5, 0.0, 0.0), Axis::Z, 1.5, None);
+        let b = AxisAlignedBox::new(Point3D::new(0.5, 0.0, 0.0), Point3D::new(1.0, 2.0, 1.5), None);
+        for p in sample_points() {
+            assert_close(extruded.signed_distance(&p), b.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_extrude_circle_is_cylinder() {
+        let extruded = Extrude::new(Circle::new(Point2D::new(0.0, 0.0), 1.0), Point3D::new(0.0, 0.0, 0.0), Axis::Y, 2.0, None);
+        let cylinder = CappedCylinder::new(Point3D::new(0.0, 0.0, 0.0), 1.0, 2.0, None);
+        for p in sample_points() {
+            assert_close(extruded.signed_distance(&p), cylinder.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_profile_object_bounding_boxes() {
+        use crate::ray_marcher::threed_data_types::bounding_box::BoundingBox;
+        let ring = Revolve::new(Circle::new(Point2D::new(2.0, 0.5), 0.5), Point3D::new(0.0, 1.0, 0.0), Axis::Y, None);
+        assert_eq!(ring.bounding_box(), Some(BoundingBox::new(Point3D::new(-2.5, 1.0, -2.5), Point3D::new(2.5, 2.0, 2.5))));
+        let slab = Extrude::new(Rectangle::new(Point2D::new(1.0, 0.0), Point2D::new(1.0, 2.0)), Point3D::new(0.0, 0.0, 0.0), Axis::Z, 0.5, None);
+        assert_eq!(slab.bounding_box(), Some(BoundingBox::new(Point3D::new(0.0, -2.0, -0.5), Point3D::new(2.0, 2.0, 0.5))));
+        let (min, max) = Arc::new(Point2D::new(0.0, 0.0), 1.0, 0.0, 1.0, 0.25).bounds().unwrap();
+        assert_eq!((min, max), (Point2D::new(-1.25, -1.25), Point2D::new(1.25, 1.25)));
+    }
+}
@@ -0,0 +1,358 @@
+use std::f64::consts::PI;
+
+use super::super::scene_file::description::{vec3, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::super::threed_data_types::Axis;
+use super::{Point3D, SceneObject, SurfaceMaterial};
+
+// Domain operators that fold query points back onto a single child, so one object
+// can be instanced many times at the cost of one distance evaluation. The child has
+// to fit inside its own cell (or sector), otherwise the folded distance can overstep.
+
+pub struct Repeat<T> where T: SceneObject {
+    object: T,
+    period: Point3D, // Cell size along each axis, zero disables repetition on that axis
+}
+
+#[allow(dead_code)]
+impl<T> Repeat<T> where T: SceneObject {
+    pub fn new(object: T, period: Point3D) -> Self {
+        Repeat { object, period }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let center = *self.object.get_position();
+        let mut q = *p - center;
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let s = self.period.get_axis(axis);
+            if s > 0.0 {
+                let v = q.get_axis(axis);
+                q.set_axis(axis, v - s * (v / s).round());
+            }
+        }
+        q + center
+    }
+}
+
+impl<T> SceneObject for Repeat<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    // Repeating forever along any axis makes the copies unbounded
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        if self.period.max_component() > 0.0 {
+            return None;
+        }
+        self.object.bounding_box()
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Repeat { object: Box::new(self.object.describe()?), period: vec3(&self.period) })
+    }
+}
+
+pub struct LimitedRepeat<T> where T: SceneObject {
+    object: T,
+    period: Point3D,
+    count: (u32, u32, u32), // Number of copies along each axis, starting at the child
+}
+
+#[allow(dead_code)]
+impl<T> LimitedRepeat<T> where T: SceneObject {
+    pub fn new(object: T, period: Point3D, count: (u32, u32, u32)) -> Self {
+        LimitedRepeat { object, period, count }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let center = *self.object.get_position();
+        let mut q = *p - center;
+        for (axis, n) in [(Axis::X, self.count.0), (Axis::Y, self.count.1), (Axis::Z, self.count.2)] {
+            let s = self.period.get_axis(axis);
+            if s > 0.0 && n > 0 {
+                let v = q.get_axis(axis);
+                let cell = (v / s).round().clamp(0.0, (n - 1) as f64);
+                q.set_axis(axis, v - s * cell);
+            }
+        }
+        q + center
+    }
+}
+
+impl<T> SceneObject for LimitedRepeat<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut b = self.object.bounding_box()?;
+        for (axis, n) in [(Axis::X, self.count.0), (Axis::Y, self.count.1), (Axis::Z, self.count.2)] {
+            let s = self.period.get_axis(axis);
+            if s > 0.0 && n > 0 {
+                b.max.set_axis(axis, b.max.get_axis(axis) + s * (n - 1) as f64);
+            }
+        }
+        Some(b)
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::LimitedRepeat { object: Box::new(self.object.describe()?), period: vec3(&self.period), count: self.count })
+    }
+}
+
+pub struct Mirror<T> where T: SceneObject {
+    object: T,
+    center: Point3D, // A point on every mirror plane
+    axes: Vec<Axis>, // Each axis mirrors across the plane it is normal to
+}
+
+#[allow(dead_code)]
+impl<T> Mirror<T> where T: SceneObject {
+    pub fn new(object: T, center: Point3D, axes: &[Axis]) -> Self {
+        Mirror { object, center, axes: axes.to_vec() }
+    }
+
+    // Reflects the point onto the side of each plane that holds the child
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let mut q = *p - self.center;
+        let child = *self.object.get_position() - self.center;
+        for axis in self.axes.iter() {
+            let side = if child.get_axis(*axis) < 0.0 { -1.0 } else { 1.0 };
+            q.set_axis(*axis, q.get_axis(*axis).abs() * side);
+        }
+        q + self.center
+    }
+}
+
+impl<T> SceneObject for Mirror<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.center
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut b = self.object.bounding_box()?;
+        for axis in self.axes.iter() {
+            let c = self.center.get_axis(*axis);
+            let mut reflected = b;
+            reflected.min.set_axis(*axis, 2.0 * c - b.max.get_axis(*axis));
+            reflected.max.set_axis(*axis, 2.0 * c - b.min.get_axis(*axis));
+            b = b.union(&reflected);
+        }
+        Some(b)
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Mirror { object: Box::new(self.object.describe()?), center: vec3(&self.center), axes: self.axes.clone() })
+    }
+}
+
+pub struct PolarRepeat<T> where T: SceneObject {
+    object: T,
+    center: Point3D, // A point on the rotation axis
+    axis: Axis,
+    count: u32, // Number of copies around the axis
+}
+
+#[allow(dead_code)]
+impl<T> PolarRepeat<T> where T: SceneObject {
+    // The child is kept in the sector centered on the first perpendicular axis
+    // (+Y around X, +Z around Y, +X around Z) and copied around the full circle
+    pub fn new(object: T, center: Point3D, axis: Axis, count: u32) -> Self {
+        PolarRepeat { object, center, axis, count: count.max(1) }
+    }
+
+    fn fold(&self, p: &Point3D) -> Point3D {
+        let (u_axis, v_axis) = self.axis.perpendicular();
+        let mut q = *p - self.center;
+        let (u, v) = (q.get_axis(u_axis), q.get_axis(v_axis));
+
+        let sector = 2.0 * PI / self.count as f64;
+        let angle = v.atan2(u);
+        let folded = angle - sector * (angle / sector).round();
+        let radius = (u * u + v * v).sqrt();
+        q.set_axis(u_axis, radius * folded.cos());
+        q.set_axis(v_axis, radius * folded.sin());
+        q + self.center
+    }
+}
+
+impl<T> SceneObject for PolarRepeat<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(&self.fold(p))
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.center
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.fold(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.object.bounding_box()?.swept_around(&self.center, self.axis))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::PolarRepeat { object: Box::new(self.object.describe()?), center: vec3(&self.center), axis: self.axis, count: self.count })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+
+    #[test]
+    fn test_repeat_equivalent_cells() {
+        let s = Sphere::new(Point3D::new(1.0, 0.0, 0.0), 0.5, None);
+        let r = Repeat::new(s, Point3D::new(4.0, 3.0, 0.0));
+        let p = Point3D::new(1.7, 0.4, 0.2);
+        let d = r.signed_distance(&p);
+        assert_close(r.signed_distance(&(p + Point3D::new(4.0, 0.0, 0.0))), d);
+        assert_close(r.signed_distance(&(p + Point3D::new(-40.0, 9.0, 0.0))), d);
+    }
+
+    #[test]
+    fn test_repeat_disabled_axis() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 0.5, None);
+        let r = Repeat::new(s, Point3D::new(4.0, 0.0, 0.0));
+        assert_close(r.signed_distance(&Point3D::new(0.0, 8.0, 0.0)), 7.5);
+    }
+
+    #[test]
+    fn test_limited_repeat_equivalent_cells() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.5, 0.5, 0.5), None);
+        let r = LimitedRepeat::new(b, Point3D::new(2.0, 2.0, 2.0), (3, 1, 1));
+        let p = Point3D::new(0.3, 0.9, 0.0);
+        let d = r.signed_distance(&p);
+        assert_close(r.signed_distance(&(p + Point3D::new(2.0, 0.0, 0.0))), d);
+        assert_close(r.signed_distance(&(p + Point3D::new(4.0, 0.0, 0.0))), d);
+    }
+
+    #[test]
+    fn test_limited_repeat_stops_after_count() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 0.5, None);
+        let r = LimitedRepeat::new(s, Point3D::new(2.0, 2.0, 2.0), (3, 1, 1));
+        // The last copy sits at x = 4, nothing is placed at x = 6 or x = -2
+        assert_close(r.signed_distance(&Point3D::new(6.0, 0.0, 0.0)), 1.5);
+        assert_close(r.signed_distance(&Point3D::new(-2.0, 0.0, 0.0)), 1.5);
+        assert_close(r.signed_distance(&Point3D::new(0.0, 2.0, 0.0)), 1.5);
+    }
+
+    #[test]
+    fn test_mirror_equivalent_sides() {
+        let s = Sphere::new(Point3D::new(3.0, 1.0, 0.0), 1.0, None);
+        let m = Mirror::new(s, Point3D::new(0.0, 0.0, 0.0), &[Axis::X]);
+        let p = Point3D::new(2.5, 2.0, 0.5);
+        let mirrored = Point3D::new(-2.5, 2.0, 0.5);
+        assert_close(m.signed_distance(&mirrored), m.signed_distance(&p));
+        assert_close(m.signed_distance(&Point3D::new(-3.0, 1.0, 0.0)), -1.0);
+    }
+
+    #[test]
+    fn test_mirror_unmirrored_axis() {
+        let s = Sphere::new(Point3D::new(3.0, 1.0, 0.0), 1.0, None);
+        let m = Mirror::new(s, Point3D::new(0.0, 0.0, 0.0), &[Axis::X]);
+        assert_close(m.signed_distance(&Point3D::new(3.0, -1.0, 0.0)), 1.0);
+    }
+
+    #[test]
+    fn test_polar_repeat_equivalent_sectors() {
+        let s = Sphere::new(Point3D::new(3.0, 0.0, 0.0), 0.5, None);
+        let r = PolarRepeat::new(s, Point3D::new(0.0, 0.0, 0.0), Axis::Z, 6);
+        let p = Point3D::new(2.6, 0.3, 0.1);
+        let angle = 60_f64.to_radians();
+        let rotated = Point3D::new(
+            p.x * angle.cos() - p.y * angle.sin(),
+            p.x * angle.sin() + p.y * angle.cos(),
+            p.z,
+        );
+        assert_close(r.signed_distance(&rotated), r.signed_distance(&p));
+    }
+
+    #[test]
+    fn test_polar_repeat_copies() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 3.0), 0.5, None);
+        let r = PolarRepeat::new(s, Point3D::new(0.0, 0.0, 0.0), Axis::Y, 4);
+        for p in [Point3D::new(3.0, 0.0, 0.0), Point3D::new(0.0, 0.0, -3.0), Point3D::new(-3.0, 0.0, 0.0)] {
+            assert_close(r.signed_distance(&p), -0.5);
+        }
+    }
+
+    #[test]
+    fn test_repetition_bounding_boxes() {
+        use crate::ray_marcher::threed_data_types::bounding_box::BoundingBox;
+        let ball = || Sphere::new(Point3D::new(1.0, 0.0, 0.0), 0.5, None);
+        assert!(Repeat::new(ball(), Point3D::new(3.0, 0.0, 0.0)).bounding_box().is_none());
+        assert_eq!(Repeat::new(ball(), Point3D::new(0.0, 0.0, 0.0)).bounding_box(), ball().bounding_box());
+
+        let row = LimitedRepeat::new(ball(), Point3D::new(2.0, 0.0, 0.0), (4, 1, 1));
+        assert_eq!(row.bounding_box(), Some(BoundingBox::new(Point3D::new(0.5, -0.5, -0.5), Point3D::new(7.5, 0.5, 0.5))));
+
+        let mirrored = Mirror::new(ball(), Point3D::new(0.0, 0.0, 0.0), &[Axis::X]);
+        assert_eq!(mirrored.bounding_box(), Some(BoundingBox::new(Point3D::new(-1.5, -0.5, -0.5), Point3D::new(1.5, 0.5, 0.5))));
+
+        let ring = PolarRepeat::new(Sphere::new(Point3D::new(0.0, 2.0, 0.0), 0.5, None), Point3D::new(0.0, 0.0, 0.0), Axis::X, 6);
+        let bounds = ring.bounding_box().unwrap();
+        assert_close(bounds.max.x, 0.5);
+        assert!(bounds.max.y >= 2.5 && bounds.min.z <= -2.5);
+    }
+}
@@ -0,0 +1,328 @@
+use serde::{Deserialize, Serialize};
+
+use super::super::scene_file::description::ObjectDescription;
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial};
+
+// Smooth counterparts of the CSG combinators. The blend radius `k` controls how far
+// the two surfaces melt into each other, and materials are mixed across that region.
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlendKind {
+    Polynomial,
+    Exponential,
+}
+
+// Returns the blended minimum of two distances and how much `b` contributes to it
+pub fn smooth_min(a: f64, b: f64, k: f64, kind: BlendKind) -> (f64, f64) {
+    if k <= 0.0 {
+        return if a <= b { (a, 0.0) } else { (b, 1.0) };
+    }
+    match kind {
+        BlendKind::Polynomial => {
+            let h = (k - (a - b).abs()).max(0.0) / k;
+            let ratio = (0.5 + 0.5 * (a - b) / k).clamp(0.0, 1.0);
+            (a.min(b) - h * h * k * 0.25, ratio)
+        }
+        BlendKind::Exponential => {
+            // Shifted by the minimum so the exponentials can't overflow
+            let m = a.min(b);
+            let ea = (-(a - m) / k).exp();
+            let eb = (-(b - m) / k).exp();
+            (m - k * (ea + eb).ln(), eb / (ea + eb))
+        }
+    }
+}
+
+// Most that smooth_min can fall below the smaller of its two distances
+fn blend_reach(k: f64, kind: BlendKind) -> f64 {
+    if k <= 0.0 {
+        return 0.0;
+    }
+    match kind {
+        BlendKind::Polynomial => k * 0.25,
+        BlendKind::Exponential => k * 2_f64.ln(),
+    }
+}
+
+// Returns the blended maximum of two distances and how much `b` contributes to it
+pub fn smooth_max(a: f64, b: f64, k: f64, kind: BlendKind) -> (f64, f64) {
+    let (d, ratio) = smooth_min(-a, -b, k, kind);
+    (-d, ratio)
+}
+
+pub struct SmoothUnion {
+    a: Box<dyn SceneObject>,
+    b: Box<dyn SceneObject>,
+    k: f64,
+    kind: BlendKind,
+}
+
+#[allow(dead_code)]
+impl SmoothUnion {
+    pub fn new(a: Box<dyn SceneObject>, b: Box<dyn SceneObject>, k: f64, kind: BlendKind) -> Self {
+        SmoothUnion { a, b, k, kind }
+    }
+
+    fn blend(&self, p: &Point3D) -> (f64, f64) {
+        smooth_min(self.a.signed_distance(p), self.b.signed_distance(p), self.k, self.kind)
+    }
+}
+
+impl SceneObject for SmoothUnion {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.blend(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.a.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.a.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        let ratio = self.blend(p).1;
+        SurfaceMaterial::mix(&self.a.get_surface_material_at(p), &self.b.get_surface_material_at(p), ratio)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.a.get_step_scale().min(self.b.get_step_scale())
+    }
+
+    // The blend can bulge out past both children
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (a, b) = (self.a.bounding_box()?, self.b.bounding_box()?);
+        Some(a.union(&b).padded(blend_reach(self.k, self.kind)))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::SmoothUnion { a: Box::new(self.a.describe()?), b: Box::new(self.b.describe()?), k: self.k, kind: self.kind })
+    }
+}
+
+pub struct SmoothIntersection {
+    a: Box<dyn SceneObject>,
+    b: Box<dyn SceneObject>,
+    k: f64,
+    kind: BlendKind,
+}
+
+#[allow(dead_code)]
+impl SmoothIntersection {
+    pub fn new(a: Box<dyn SceneObject>, b: Box<dyn SceneObject>, k: f64, kind: BlendKind) -> Self {
+        SmoothIntersection { a, b, k, kind }
+    }
+
+    fn blend(&self, p: &Point3D) -> (f64, f64) {
+        smooth_max(self.a.signed_distance(p), self.b.signed_distance(p), self.k, self.kind)
+    }
+}
+
+impl SceneObject for SmoothIntersection {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.blend(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.a.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.a.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        let ratio = self.blend(p).1;
+        SurfaceMaterial::mix(&self.a.get_surface_material_at(p), &self.b.get_surface_material_at(p), ratio)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.a.get_step_scale().min(self.b.get_step_scale())
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        match (self.a.bounding_box(), self.b.bounding_box()) {
+            (Some(a), Some(b)) => Some(a.intersection(&b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::SmoothIntersection { a: Box::new(self.a.describe()?), b: Box::new(self.b.describe()?), k: self.k, kind: self.kind })
+    }
+}
+
+pub struct SmoothSubtraction {
+    base: Box<dyn SceneObject>,
+    subtracted: Box<dyn SceneObject>, // Carved out of the base
+    k: f64,
+    kind: BlendKind,
+}
+
+#[allow(dead_code)]
+impl SmoothSubtraction {
+    pub fn new(base: Box<dyn SceneObject>, subtracted: Box<dyn SceneObject>, k: f64, kind: BlendKind) -> Self {
+        SmoothSubtraction { base, subtracted, k, kind }
+    }
+
+    fn blend(&self, p: &Point3D) -> (f64, f64) {
+        smooth_max(self.base.signed_distance(p), -self.subtracted.signed_distance(p), self.k, self.kind)
+    }
+}
+
+impl SceneObject for SmoothSubtraction {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.blend(p).0
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.base.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.base.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        let ratio = self.blend(p).1;
+        SurfaceMaterial::mix(&self.base.get_surface_material_at(p), &self.subtracted.get_surface_material_at(p), ratio)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.base.get_step_scale().min(self.subtracted.get_step_scale())
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.base.bounding_box()
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::SmoothSubtraction {
+            base: Box::new(self.base.describe()?),
+            subtracted: Box::new(self.subtracted.describe()?),
+            k: self.k,
+            kind: self.kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::*;
+    use crate::ray_marcher::color_data_types::Color;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_safe_steps, blue, fibonacci_directions, grid, red};
+
+    fn sphere_pair() -> (Box<dyn SceneObject>, Box<dyn SceneObject>) {
+        (
+            Box::new(Sphere::new(Point3D::new(-1.0, 0.0, 0.0), 1.0, red())),
+            Box::new(Sphere::new(Point3D::new(1.2, 0.3, 0.0), 1.0, blue())),
+        )
+    }
+
+    fn cut_pair() -> (Box<dyn SceneObject>, Box<dyn SceneObject>) {
+        (
+            Box::new(AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), red())),
+            Box::new(Sphere::new(Point3D::new(1.0, 0.5, 0.0), 0.8, blue())),
+        )
+    }
+
+    fn sample_points() -> Vec<Point3D> {
+        grid((6, 6, 3), 0.5, Point3D::new(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn test_smooth_min_below_min() {
+        for kind in [BlendKind::Polynomial, BlendKind::Exponential] {
+            let (d, _) = smooth_min(1.0, 1.2, 0.5, kind);
+            assert!(d < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_smooth_min_far_from_blend() {
+        let (d, ratio) = smooth_min(1.0, 3.0, 0.5, BlendKind::Polynomial);
+        assert_eq!(d, 1.0);
+        assert_eq!(ratio, 0.0);
+    }
+
+    #[test]
+    fn test_smooth_min_zero_radius() {
+        let (d, ratio) = smooth_min(2.0, 1.0, 0.0, BlendKind::Exponential);
+        assert_eq!(d, 1.0);
+        assert_eq!(ratio, 1.0);
+    }
+
+    #[test]
+    fn test_smooth_union_bounded_by_union() {
+        let (a, b) = sphere_pair();
+        let u = SmoothUnion::new(a, b, 0.5, BlendKind::Polynomial);
+        let p = Point3D::new(0.1, 0.1, 0.0);
+        let hard = Sphere::new(Point3D::new(-1.0, 0.0, 0.0), 1.0, None)
+            .signed_distance(&p)
+            .min(Sphere::new(Point3D::new(1.2, 0.3, 0.0), 1.0, None).signed_distance(&p));
+        assert!(u.signed_distance(&p) <= hard);
+    }
+
+    #[test]
+    fn test_smooth_union_polynomial_never_overestimates() {
+        let (a, b) = sphere_pair();
+        assert_safe_steps(&SmoothUnion::new(a, b, 0.8, BlendKind::Polynomial), &sample_points(), &fibonacci_directions(200));
+    }
+
+    #[test]
+    fn test_smooth_union_exponential_never_overestimates() {
+        let (a, b) = sphere_pair();
+        assert_safe_steps(&SmoothUnion::new(a, b, 0.3, BlendKind::Exponential), &sample_points(), &fibonacci_directions(200));
+    }
+
+    #[test]
+    fn test_smooth_subtraction_never_overestimates() {
+        for kind in [BlendKind::Polynomial, BlendKind::Exponential] {
+            let (a, b) = cut_pair();
+            assert_safe_steps(&SmoothSubtraction::new(a, b, 0.4, kind), &sample_points(), &fibonacci_directions(200));
+        }
+    }
+
+    #[test]
+    fn test_smooth_intersection_never_overestimates() {
+        for kind in [BlendKind::Polynomial, BlendKind::Exponential] {
+            let (a, b) = sphere_pair();
+            assert_safe_steps(&SmoothIntersection::new(a, b, 0.4, kind), &sample_points(), &fibonacci_directions(200));
+        }
+    }
+
+    #[test]
+    fn test_smooth_union_material_gradient() {
+        let (a, b) = sphere_pair();
+        let u = SmoothUnion::new(a, b, 1.0, BlendKind::Polynomial);
+        let left = u.get_surface_material_at(&Point3D::new(-2.0, 0.0, 0.0)).albedo;
+        let middle = u.get_surface_material_at(&Point3D::new(0.1, 0.15, 0.0)).albedo;
+        let right = u.get_surface_material_at(&Point3D::new(2.2, 0.3, 0.0)).albedo;
+        assert_eq!(left, Color::new(1.0, 0.0, 0.0));
+        assert_eq!(right, Color::new(0.0, 0.0, 1.0));
+        assert!(middle.r() > 0.0 && middle.b() > 0.0);
+    }
+
+    #[test]
+    fn test_smooth_union_bounds_hold_the_blend() {
+        for kind in [BlendKind::Polynomial, BlendKind::Exponential] {
+            let (a, b) = sphere_pair();
+            let u = SmoothUnion::new(a, b, 0.8, kind);
+            let bounds = u.bounding_box().unwrap();
+            for xi in -16..=16 {
+                for yi in -16..=16 {
+                    let p = Point3D::new(xi as f64 * 0.2, yi as f64 * 0.2, 0.1);
+                    if !bounds.contains(&p) {
+                        assert!(u.signed_distance(&p) > 0.0, "{:?} outside {:?}", p, bounds);
+                    }
+                }
+            }
+        }
+        let (base, cutter) = cut_pair();
+        let base_bounds = base.bounding_box();
+        assert_eq!(SmoothSubtraction::new(base, cutter, 0.5, BlendKind::Polynomial).bounding_box(), base_bounds);
+    }
+}
@@ -0,0 +1,333 @@
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription};
+use super::super::threed_data_types::Axis;
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::profiles::{bounds_around, Arc, Extrude, Point2D, Profile, Segment};
+use super::{Point3D, SceneObject, SurfaceMaterial};
+
+// Text built from a small stroke font. Every glyph is a handful of line segments and
+// circular arcs, thickened into strokes and extruded, so labels can be placed in a
+// scene like any other object.
+
+// Glyph cells are 1 tall from the baseline to the top of the capitals
+const GLYPH_WIDTH: f64 = 0.6;
+const ADVANCE: f64 = 0.8;
+const LINE_HEIGHT: f64 = 1.5;
+// Stroke width as a fraction of the glyph height
+pub const DEFAULT_WEIGHT: f64 = 0.12;
+
+// Lines as (x0, y0, x1, y1), arcs as (center x, center y, radius, start, sweep) with
+// the angles in degrees
+enum FontStroke {
+    Line(f64, f64, f64, f64),
+    Arc(f64, f64, f64, f64, f64),
+}
+
+use FontStroke::{Arc as A, Line as L};
+
+const QUESTION: &[FontStroke] = &[A(0.3, 0.75, 0.25, -90.0, 270.0), L(0.3, 0.5, 0.3, 0.3), L(0.3, 0.0, 0.3, 0.0)];
+
+// Lowercase letters use the capitals
+fn font_strokes(c: char) -> Option<&'static [FontStroke]> {
+    let strokes: &'static [FontStroke] = match c.to_ascii_uppercase() {
+        ' ' => &[],
+        'A' => &[L(0.0, 0.0, 0.3, 1.0), L(0.3, 1.0, 0.6, 0.0), L(0.105, 0.35, 0.495, 0.35)],
+        'B' => &[
+            L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.35, 1.0), A(0.35, 0.75, 0.25, -90.0, 180.0),
+            L(0.0, 0.5, 0.35, 0.5), L(0.0, 0.0, 0.35, 0.0), A(0.35, 0.25, 0.25, -90.0, 180.0),
+        ],
+        'C' => &[A(0.3, 0.7, 0.3, 20.0, 160.0), L(0.0, 0.7, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 160.0)],
+        'D' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.1, 1.0), L(0.0, 0.0, 0.1, 0.0), A(0.1, 0.5, 0.5, -90.0, 180.0)],
+        'E' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.6, 1.0), L(0.0, 0.5, 0.45, 0.5), L(0.0, 0.0, 0.6, 0.0)],
+        'F' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.6, 1.0), L(0.0, 0.5, 0.45, 0.5)],
+        'G' => &[
+            A(0.3, 0.7, 0.3, 20.0, 160.0), L(0.0, 0.7, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0),
+            L(0.6, 0.3, 0.6, 0.45), L(0.35, 0.45, 0.6, 0.45),
+        ],
+        'H' => &[L(0.0, 0.0, 0.0, 1.0), L(0.6, 0.0, 0.6, 1.0), L(0.0, 0.5, 0.6, 0.5)],
+        'I' => &[L(0.3, 0.0, 0.3, 1.0), L(0.1, 1.0, 0.5, 1.0), L(0.1, 0.0, 0.5, 0.0)],
+        'J' => &[L(0.6, 1.0, 0.6, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0)],
+        'K' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 0.4, 0.6, 1.0), L(0.2, 0.6, 0.6, 0.0)],
+        'L' => &[L(0.0, 1.0, 0.0, 0.0), L(0.0, 0.0, 0.6, 0.0)],
+        'M' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.3, 0.4), L(0.3, 0.4, 0.6, 1.0), L(0.6, 1.0, 0.6, 0.0)],
+        'N' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.6, 0.0), L(0.6, 0.0, 0.6, 1.0)],
+        'O' => &[A(0.3, 0.7, 0.3, 0.0, 180.0), L(0.0, 0.7, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0), L(0.6, 0.3, 0.6, 0.7)],
+        'P' => &[L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.35, 1.0), A(0.35, 0.75, 0.25, -90.0, 180.0), L(0.0, 0.5, 0.35, 0.5)],
+        'Q' => &[
+            A(0.3, 0.7, 0.3, 0.0, 180.0), L(0.0, 0.7, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0),
+            L(0.6, 0.3, 0.6, 0.7), L(0.35, 0.25, 0.6, 0.0),
+        ],
+        'R' => &[
+            L(0.0, 0.0, 0.0, 1.0), L(0.0, 1.0, 0.35, 1.0), A(0.35, 0.75, 0.25, -90.0, 180.0),
+            L(0.0, 0.5, 0.35, 0.5), L(0.3, 0.5, 0.6, 0.0),
+        ],
+        'S' => &[A(0.3, 0.75, 0.25, 0.0, 270.0), A(0.3, 0.25, 0.25, 90.0, -270.0)],
+        'T' => &[L(0.0, 1.0, 0.6, 1.0), L(0.3, 1.0, 0.3, 0.0)],
+        'U' => &[L(0.0, 1.0, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0), L(0.6, 0.3, 0.6, 1.0)],
+        'V' => &[L(0.0, 1.0, 0.3, 0.0), L(0.3, 0.0, 0.6, 1.0)],
+        'W' => &[L(0.0, 1.0, 0.15, 0.0), L(0.15, 0.0, 0.3, 0.6), L(0.3, 0.6, 0.45, 0.0), L(0.45, 0.0, 0.6, 1.0)],
+        'X' => &[L(0.0, 0.0, 0.6, 1.0), L(0.0, 1.0, 0.6, 0.0)],
+        'Y' => &[L(0.0, 1.0, 0.3, 0.5), L(0.6, 1.0, 0.3, 0.5), L(0.3, 0.5, 0.3, 0.0)],
+        'Z' => &[L(0.0, 1.0, 0.6, 1.0), L(0.6, 1.0, 0.0, 0.0), L(0.0, 0.0, 0.6, 0.0)],
+        '0' => &[
+            A(0.3, 0.7, 0.3, 0.0, 180.0), L(0.0, 0.7, 0.0, 0.3), A(0.3, 0.3, 0.3, 180.0, 180.0),
+            L(0.6, 0.3, 0.6, 0.7), L(0.05, 0.15, 0.55, 0.85),
+        ],
+        '1' => &[L(0.1, 0.8, 0.3, 1.0), L(0.3, 1.0, 0.3, 0.0), L(0.1, 0.0, 0.5, 0.0)],
+        '2' => &[A(0.3, 0.7, 0.3, -40.0, 220.0), L(0.53, 0.507, 0.0, 0.0), L(0.0, 0.0, 0.6, 0.0)],
+        '3' => &[A(0.3, 0.75, 0.25, -90.0, 270.0), A(0.3, 0.25, 0.25, -180.0, 270.0)],
+        '4' => &[L(0.45, 0.0, 0.45, 1.0), L(0.45, 1.0, 0.0, 0.35), L(0.0, 0.35, 0.6, 0.35)],
+        '5' => &[L(0.6, 1.0, 0.0, 1.0), L(0.0, 1.0, 0.0, 0.6), L(0.0, 0.6, 0.3, 0.6), A(0.3, 0.3, 0.3, -140.0, 230.0)],
+        '6' => &[A(0.3, 0.3, 0.3, 0.0, 360.0), L(0.0, 0.3, 0.0, 0.7), A(0.3, 0.7, 0.3, 30.0, 150.0)],
+        '7' => &[L(0.0, 1.0, 0.6, 1.0), L(0.6, 1.0, 0.2, 0.0)],
+        '8' => &[A(0.3, 0.78, 0.22, 0.0, 360.0), A(0.3, 0.28, 0.28, 0.0, 360.0)],
+        '9' => &[A(0.3, 0.7, 0.3, 0.0, 360.0), L(0.6, 0.7, 0.6, 0.3), A(0.3, 0.3, 0.3, 210.0, 150.0)],
+        '.' => &[L(0.3, 0.0, 0.3, 0.0)],
+        ',' => &[L(0.3, 0.05, 0.2, -0.15)],
+        '!' => &[L(0.3, 1.0, 0.3, 0.3), L(0.3, 0.0, 0.3, 0.0)],
+        ':' => &[L(0.3, 0.2, 0.3, 0.2), L(0.3, 0.7, 0.3, 0.7)],
+        '\'' => &[L(0.3, 1.0, 0.3, 0.75)],
+        '-' => &[L(0.1, 0.5, 0.5, 0.5)],
+        '+' => &[L(0.1, 0.5, 0.5, 0.5), L(0.3, 0.3, 0.3, 0.7)],
+        '=' => &[L(0.1, 0.35, 0.5, 0.35), L(0.1, 0.65, 0.5, 0.65)],
+        '/' => &[L(0.0, 0.0, 0.6, 1.0)],
+        '(' => &[A(0.9, 0.5, 0.78, 140.1, 79.8)],
+        ')' => &[A(-0.3, 0.5, 0.78, -39.9, 79.8)],
+        _ => return None,
+    };
+    Some(strokes)
+}
+
+enum Stroke {
+    Segment(Segment),
+    Arc(Arc),
+}
+
+impl Stroke {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        match self {
+            Stroke::Segment(s) => s.signed_distance(p),
+            Stroke::Arc(a) => a.signed_distance(p),
+        }
+    }
+}
+
+struct Glyph {
+    min: Point2D, // Bounds of the stroke center lines
+    max: Point2D,
+    strokes: Vec<Stroke>,
+}
+
+// The laid out strokes of a string, centered on the origin. Lines are split on '\n' and
+// each one is centered on its own.
+pub struct TextProfile {
+    text: String,
+    size: f64,
+    weight: f64,
+    glyphs: Vec<Glyph>,
+    radius: f64,
+    extents: Point2D, // Half the width and height of the block of text
+}
+
+#[allow(dead_code)]
+impl TextProfile {
+    // `size` is the height of a capital letter, `weight` the stroke width relative to it
+    pub fn new(text: &str, size: f64, weight: f64) -> Self {
+        assert!(size > 0.0 && weight > 0.0, "Text size and weight must be positive");
+        let radius = weight * size / 2.0;
+        let lines: Vec<&str> = text.split('\n').collect();
+        let height = (lines.len() - 1) as f64 * LINE_HEIGHT + 1.0;
+        let mut width: f64 = 0.0;
+        let mut glyphs = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            let count = line.chars().count();
+            let line_width = if count > 0 { count as f64 * ADVANCE - (ADVANCE - GLYPH_WIDTH) } else { 0.0 };
+            width = width.max(line_width);
+            let baseline = height / 2.0 - 1.0 - row as f64 * LINE_HEIGHT;
+            for (column, c) in line.chars().enumerate() {
+                let origin = Point2D::new(column as f64 * ADVANCE - line_width / 2.0, baseline);
+                let place = |x: f64, y: f64| (origin + Point2D::new(x, y)) * size;
+                let mut min = Point2D::new(f64::INFINITY, f64::INFINITY);
+                let mut max = Point2D::new(f64::NEG_INFINITY, f64::NEG_INFINITY);
+                let mut grow = |p: Point2D| {
+                    min = Point2D::new(min.x.min(p.x), min.y.min(p.y));
+                    max = Point2D::new(max.x.max(p.x), max.y.max(p.y));
+                };
+                // Anything missing from the font is drawn as '?'
+                let strokes: Vec<Stroke> = font_strokes(c)
+                    .unwrap_or(QUESTION)
+                    .iter()
+                    .map(|stroke| match *stroke {
+                        FontStroke::Line(x0, y0, x1, y1) => {
+                            let (start, end) = (place(x0, y0), place(x1, y1));
+                            grow(start);
+                            grow(end);
+                            Stroke::Segment(Segment::new(start, end, radius))
+                        }
+                        FontStroke::Arc(x, y, r, start, sweep) => {
+                            // The whole circle is a loose but safe bound for the arc
+                            let center = place(x, y);
+                            grow(center - Point2D::new(r, r) * size);
+                            grow(center + Point2D::new(r, r) * size);
+                            Stroke::Arc(Arc::new(center, r * size, start.to_radians(), sweep.to_radians(), radius))
+                        }
+                    })
+                    .collect();
+                if !strokes.is_empty() {
+                    glyphs.push(Glyph { min, max, strokes });
+                }
+            }
+        }
+        TextProfile { text: text.to_string(), size, weight, glyphs, radius, extents: Point2D::new(width, height) * (size / 2.0) }
+    }
+
+    pub fn get_extents(&self) -> Point2D {
+        self.extents
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl Profile for TextProfile {
+    fn signed_distance(&self, p: &Point2D) -> f64 {
+        let mut best = f64::INFINITY;
+        for glyph in self.glyphs.iter() {
+            // Skip glyphs whose bounds are already further than the closest stroke
+            let outside = Point2D::new((glyph.min.x - p.x).max(p.x - glyph.max.x).max(0.0), (glyph.min.y - p.y).max(p.y - glyph.max.y).max(0.0));
+            if outside.length() - self.radius >= best {
+                continue;
+            }
+            for stroke in glyph.strokes.iter() {
+                best = best.min(stroke.signed_distance(p));
+            }
+        }
+        best
+    }
+
+    fn bounds(&self) -> Option<(Point2D, Point2D)> {
+        Some(bounds_around(self.glyphs.iter().flat_map(|g| [g.min, g.max]), self.radius))
+    }
+}
+
+// A string extruded `half_depth` to either side of `pos` along `axis`. The text reads
+// along the first of the axes perpendicular to `axis` and upwards along the second,
+// as with `Extrude`.
+pub struct Text {
+    extruded: Extrude<TextProfile>,
+}
+
+#[allow(dead_code)]
+impl Text {
+    pub fn new(text: &str, pos: Point3D, axis: Axis, size: f64, half_depth: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Text::from_profile(TextProfile::new(text, size, DEFAULT_WEIGHT), pos, axis, half_depth, sm)
+    }
+
+    pub fn from_profile(profile: TextProfile, pos: Point3D, axis: Axis, half_depth: f64, sm: Option<SurfaceMaterial>) -> Self {
+        Text { extruded: Extrude::new(profile, pos, axis, half_depth, sm) }
+    }
+}
+
+impl SceneObject for Text {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.extruded.signed_distance(p)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.extruded.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.extruded.get_surface_material()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.extruded.bounding_box()
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        let profile = self.extruded.get_profile();
+        Some(ObjectDescription::Text {
+            text: profile.text.clone(),
+            center: vec3(self.extruded.get_position()),
+            axis: self.extruded.get_axis(),
+            size: profile.size,
+            half_depth: self.extruded.get_half_depth(),
+            weight: profile.weight,
+            material: MaterialRef::describe(&self.extruded.get_surface_material()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+
+    fn brute_force(profile: &TextProfile, p: &Point2D) -> f64 {
+        profile.glyphs.iter().flat_map(|g| g.strokes.iter()).map(|s| s.signed_distance(p)).fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn test_font_covers_letters_and_digits() {
+        for c in ('A'..='Z').chain('0'..='9') {
+            assert!(font_strokes(c).is_some_and(|s| !s.is_empty()), "{} is missing", c);
+        }
+        assert!(font_strokes('~').is_none());
+        assert_eq!(font_strokes(' ').map(|s| s.len()), Some(0));
+    }
+
+    #[test]
+    fn test_text_layout() {
+        let i = TextProfile::new("I", 2.0, 0.1);
+        // The stem of the I runs through the middle
+        assert_close(i.signed_distance(&Point2D::new(0.0, 0.0)), -0.1);
+        assert_close(i.signed_distance(&Point2D::new(0.0, 3.0)), 1.9);
+        assert_close(i.get_extents().x, 0.6);
+        assert_close(i.get_extents().y, 1.0);
+
+        let two_lines = TextProfile::new("AB C\nD", 1.0, 0.1);
+        assert_close(two_lines.get_extents().x, (4.0 * ADVANCE - ADVANCE + GLYPH_WIDTH) / 2.0);
+        assert_close(two_lines.get_extents().y, (LINE_HEIGHT + 1.0) / 2.0);
+        // The space has no strokes
+        assert_eq!(two_lines.glyphs.len(), 4);
+    }
+
+    #[test]
+    fn test_lowercase_uses_capitals() {
+        let lower = TextProfile::new("hello", 1.0, 0.1);
+        let upper = TextProfile::new("HELLO", 1.0, 0.1);
+        for xi in -10..=10 {
+            let p = Point2D::new(xi as f64 * 0.3, 0.2);
+            assert_eq!(lower.signed_distance(&p), upper.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_text_matches_brute_force() {
+        let profile = TextProfile::new("Ray 42\nMARCHING!", 1.5, DEFAULT_WEIGHT);
+        for xi in -30..=30 {
+            for yi in -15..=15 {
+                let p = Point2D::new(xi as f64 * 0.23 + 0.01, yi as f64 * 0.19);
+                assert_close(profile.signed_distance(&p), brute_force(&profile, &p));
+            }
+        }
+    }
+
+    #[test]
+    fn test_extruded_text() {
+        let text = Text::new("T", Point3D::new(1.0, 2.0, 3.0), Axis::Z, 1.0, 0.25, None);
+        // The bar of the T is at the top of the glyph, half the height above the center
+        let top = Point3D::new(1.0, 2.5, 3.0);
+        assert_close(text.signed_distance(&top), -DEFAULT_WEIGHT / 2.0);
+        assert_close(text.signed_distance(&(top + Point3D::new(0.0, 0.0, 2.0))), 1.75);
+        assert!(text.signed_distance(&Point3D::new(1.0, 2.0, 3.0)) < 0.0);
+        assert_eq!(text.get_position(), &Point3D::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let profile = TextProfile::new("", 1.0, 0.1);
+        assert_eq!(profile.signed_distance(&Point2D::new(0.0, 0.0)), f64::INFINITY);
+    }
+}
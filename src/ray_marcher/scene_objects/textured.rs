@@ -0,0 +1,103 @@
+use super::super::noise::Noise3D;
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Varies a child's material across its surface with a noise. Where the noise is -1
+// the child's own material shows, where it is 1 the second material does. The shape
+// is left untouched.
+pub struct Textured<T> where T: SceneObject {
+    object: T,
+    noise: Box<dyn Noise3D>,
+    material: SurfaceMaterial,
+}
+
+#[allow(dead_code)]
+impl<T> Textured<T> where T: SceneObject {
+    pub fn new(object: T, noise: Box<dyn Noise3D>, sm: Option<SurfaceMaterial>) -> Self {
+        Textured { object, noise, material: sm.unwrap_or(DEFAULT_SURFACEMAT) }
+    }
+
+    pub fn get_object(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T> SceneObject for Textured<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.object.signed_distance(p)
+    }
+
+    fn get_position(&self) -> &Point3D {
+        self.object.get_position()
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        let ratio = (0.5 + 0.5 * self.noise.sample(p)).clamp(0.0, 1.0);
+        SurfaceMaterial::mix(&self.object.get_surface_material_at(p), &self.material, ratio)
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.object.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::noise::{Noise3D, Worley, WorleyFeature};
+    use super::super::objects::Sphere;
+    use super::*;
+    use crate::ray_marcher::color_data_types::Color;
+    use crate::ray_marcher::threed_data_types::test_helpers::{blue, red};
+
+    struct Constant(f64);
+
+    impl Noise3D for Constant {
+        fn sample(&self, _p: &Point3D) -> f64 {
+            self.0
+        }
+
+        fn lipschitz(&self) -> f64 {
+            0.0
+        }
+    }
+
+    fn red_sphere() -> Sphere {
+        Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, red())
+    }
+
+    #[test]
+    fn test_textured_keeps_shape() {
+        let t = Textured::new(red_sphere(), Box::new(Worley::new(1, WorleyFeature::F1)), blue());
+        let p = Point3D::new(0.3, 2.0, -0.4);
+        assert_eq!(t.signed_distance(&p), red_sphere().signed_distance(&p));
+    }
+
+    #[test]
+    fn test_textured_noise_extremes() {
+        let p = Point3D::new(1.0, 0.0, 0.0);
+        let low = Textured::new(red_sphere(), Box::new(Constant(-1.0)), blue());
+        let high = Textured::new(red_sphere(), Box::new(Constant(1.0)), blue());
+        assert_eq!(low.get_surface_material_at(&p), red().unwrap());
+        assert_eq!(high.get_surface_material_at(&p), blue().unwrap());
+    }
+
+    #[test]
+    fn test_textured_varies_over_surface() {
+        let t = Textured::new(red_sphere(), Box::new(Worley::new(1, WorleyFeature::F1)), blue());
+        let colors: Vec<Color> = (0..20)
+            .map(|i| {
+                let a = i as f64 * 0.3;
+                t.get_surface_material_at(&Point3D::new(a.cos(), a.sin(), 0.0)).albedo
+            })
+            .collect();
+        assert!(colors.iter().any(|c| *c != colors[0]));
+    }
+}
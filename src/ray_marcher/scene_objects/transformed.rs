@@ -0,0 +1,137 @@
+use super::super::scene_file::description::{ObjectDescription, TransformDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::super::threed_data_types::transform::Transform;
+use super::{Point3D, SceneObject, SurfaceMaterial};
+
+// Places a child object in the world through an affine transform. Query points are
+// mapped into the child's space, and the child's distance is scaled back by the
+// smallest stretch of the transform so it never overestimates.
+pub struct Transformed<T> where T: SceneObject {
+    object: T,
+    transform: Transform,
+    position: Point3D, // The child's position carried into world space
+}
+
+#[allow(dead_code)]
+impl<T> Transformed<T> where T: SceneObject {
+    pub fn new(object: T, transform: Transform) -> Self {
+        let position = transform.transform_point(object.get_position());
+        Transformed { object, transform, position }
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.position = transform.transform_point(self.object.get_position());
+        self.transform = transform;
+    }
+
+    pub fn get_object(&self) -> &T {
+        &self.object
+    }
+}
+
+impl<T> SceneObject for Transformed<T> where T: SceneObject {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let local = self.transform.inverse_transform_point(p);
+        self.object.signed_distance(&local) * self.transform.get_min_scale()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.object.get_surface_material()
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        self.object.get_surface_material_at(&self.transform.inverse_transform_point(p))
+    }
+
+    fn get_step_scale(&self) -> f64 {
+        self.object.get_step_scale()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let corners = self.object.bounding_box()?.corners().map(|c| self.transform.transform_point(&c));
+        Some(BoundingBox::from_points(corners.iter()))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::Transformed {
+            object: Box::new(self.object.describe()?),
+            transform: TransformDescription::from_transform(&self.transform),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{AxisAlignedBox, Sphere};
+    use super::super::Vector3D;
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::assert_close;
+
+    #[test]
+    fn test_translated_sphere_sdf() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let t = Transformed::new(s, Transform::from_translation(Point3D::new(5.0, 0.0, 0.0)));
+        assert_close(t.signed_distance(&Point3D::new(7.0, 0.0, 0.0)), 1.0);
+        assert_eq!(*t.get_position(), Point3D::new(5.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_rotated_box_sdf() {
+        // A long box along X turned to lie along Y
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(3.0, 1.0, 1.0), None);
+        let t = Transformed::new(b, Transform::from_rotation(Vector3D::new(0.0, 0.0, 1.0), 90_f64.to_radians()));
+        assert_close(t.signed_distance(&Point3D::new(0.0, 4.0, 0.0)), 1.0);
+        assert_close(t.signed_distance(&Point3D::new(4.0, 0.0, 0.0)), 3.0);
+    }
+
+    #[test]
+    fn test_uniform_scale_sdf_exact() {
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let t = Transformed::new(s, Transform::from_uniform_scale(2.0));
+        assert_close(t.signed_distance(&Point3D::new(0.0, 5.0, 0.0)), 3.0);
+        assert_close(t.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -2.0);
+    }
+
+    #[test]
+    fn test_non_uniform_scale_sdf_conservative() {
+        // Stretched into an ellipsoid with radii (4, 1, 1)
+        let s = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let t = Transformed::new(s, Transform::from_scale(Point3D::new(4.0, 1.0, 1.0)));
+        let along_long = t.signed_distance(&Point3D::new(6.0, 0.0, 0.0));
+        let along_short = t.signed_distance(&Point3D::new(0.0, 3.0, 0.0));
+        assert!(along_long > 0.0 && along_long <= 2.0);
+        assert_close(along_short, 2.0);
+    }
+
+    #[test]
+    fn test_transformed_surface_normal() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0), None);
+        let rotation = Transform::from_rotation(Vector3D::new(0.0, 0.0, 1.0), 45_f64.to_radians());
+        let on_top_face = rotation.transform_point(&Point3D::new(0.2, 1.0, 0.0));
+        let t = Transformed::new(b, rotation);
+        let n = t.get_surface_normal(&on_top_face, 0.0001);
+        assert!((Vector3D::new(-1.0, 1.0, 0.0).get_norm() - n).length() < 0.001);
+    }
+
+    #[test]
+    fn test_transformed_bounding_box() {
+        let b = AxisAlignedBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 1.0, 1.0), None);
+        let quarter_turn = Transform::from_rotation(Vector3D::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+        let t = Transformed::new(b, quarter_turn.then(&Transform::from_translation(Point3D::new(5.0, 0.0, 0.0))));
+        let bounds = t.bounding_box().unwrap();
+        // The long side now runs along y
+        assert_close(bounds.min.x, 4.0);
+        assert_close(bounds.max.x, 6.0);
+        assert_close(bounds.min.y, -2.0);
+        assert_close(bounds.max.y, 2.0);
+        assert_close(bounds.max.z, 1.0);
+    }
+}
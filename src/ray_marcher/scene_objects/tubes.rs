@@ -0,0 +1,312 @@
+use super::super::bvh::Bvh;
+use super::super::scene_file::description::{vec3, MaterialRef, ObjectDescription, TubePath};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Tubes swept along a polyline. Each piece between two points is a cone capped by the
+// spheres at its ends, so the radius can change along the tube and the joints stay
+// round. Splines are split into short pieces first. A BVH over the pieces finds the
+// closest one without checking all of them.
+
+pub struct Tube {
+    points: Vec<Point3D>,
+    radii: Vec<f64>,
+    lengths: Vec<f64>, // Distance along the polyline to each point
+    bvh: Bvh,
+    position: Point3D,
+    surface_material: SurfaceMaterial,
+    end_material: Option<SurfaceMaterial>, // Blended towards along the tube when set
+}
+
+#[allow(dead_code)]
+impl Tube {
+    // A tube through every point, with a radius for each point
+    pub fn new(points: Vec<Point3D>, radii: Vec<f64>, sm: Option<SurfaceMaterial>) -> Self {
+        assert!(points.len() >= 2, "a tube needs at least 2 points, got {}", points.len());
+        assert_eq!(points.len(), radii.len(), "a tube needs one radius per point");
+
+        let boxes: Vec<BoundingBox> = (0..points.len() - 1)
+            .map(|i| {
+                let start = BoundingBox::new(points[i] - radii[i], points[i] + radii[i]);
+                let end = BoundingBox::new(points[i + 1] - radii[i + 1], points[i + 1] + radii[i + 1]);
+                start.union(&end)
+            })
+            .collect();
+        let mut lengths = vec![0.0];
+        for i in 1..points.len() {
+            lengths.push(lengths[i - 1] + points[i].distance_to(&points[i - 1]));
+        }
+
+        Tube {
+            bvh: Bvh::new(&boxes),
+            position: points[0],
+            points,
+            radii,
+            lengths,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            end_material: None,
+        }
+    }
+
+    pub fn with_radius(points: Vec<Point3D>, radius: f64, sm: Option<SurfaceMaterial>) -> Self {
+        let radii = vec![radius; points.len()];
+        Tube::new(points, radii, sm)
+    }
+
+    // A smooth curve through every point. The curve leaves the first point towards the
+    // second and arrives at the last from the one before it.
+    pub fn catmull_rom(points: &[Point3D], radii: &[f64], segments_per_span: u32, sm: Option<SurfaceMaterial>) -> Self {
+        assert!(points.len() >= 2, "a spline needs at least 2 points, got {}", points.len());
+        assert_eq!(points.len(), radii.len(), "a spline needs one radius per point");
+        let n = points.len();
+        let at = |i: isize| points[i.clamp(0, n as isize - 1) as usize];
+        let mut curve = Vec::new();
+        let mut curve_radii = Vec::new();
+        for span in 0..n - 1 {
+            let (p0, p1, p2, p3) = (at(span as isize - 1), at(span as isize), at(span as isize + 1), at(span as isize + 2));
+            for step in 0..segments_per_span.max(1) {
+                let t = step as f64 / segments_per_span.max(1) as f64;
+                let t2 = t * t;
+                let t3 = t2 * t;
+                curve.push(
+                    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5,
+                );
+                curve_radii.push(radii[span] + (radii[span + 1] - radii[span]) * t);
+            }
+        }
+        curve.push(points[n - 1]);
+        curve_radii.push(radii[n - 1]);
+        Tube::new(curve, curve_radii, sm)
+    }
+
+    // Cubic Bezier spans sharing their end points, so `points` holds a start point and
+    // then two controls and an end point per span. Radii are given at the span ends.
+    pub fn cubic_bezier(points: &[Point3D], radii: &[f64], segments_per_span: u32, sm: Option<SurfaceMaterial>) -> Self {
+        assert!(points.len() >= 4 && points.len() % 3 == 1, "cubic Bezier spans need 3n + 1 points, got {}", points.len());
+        let spans = (points.len() - 1) / 3;
+        assert_eq!(radii.len(), spans + 1, "cubic Bezier spans need one radius per span end");
+        let mut curve = Vec::new();
+        let mut curve_radii = Vec::new();
+        for span in 0..spans {
+            let [p0, p1, p2, p3] = [0, 1, 2, 3].map(|i| points[span * 3 + i]);
+            for step in 0..segments_per_span.max(1) {
+                let t = step as f64 / segments_per_span.max(1) as f64;
+                let u = 1.0 - t;
+                curve.push(p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t));
+                curve_radii.push(radii[span] + (radii[span + 1] - radii[span]) * t);
+            }
+        }
+        curve.push(points[points.len() - 1]);
+        curve_radii.push(radii[spans]);
+        Tube::new(curve, curve_radii, sm)
+    }
+
+    // Materials fade from the tube's own at the start to this one at the end
+    pub fn with_end_material(mut self, sm: SurfaceMaterial) -> Self {
+        self.end_material = Some(sm);
+        self
+    }
+
+    pub fn get_points(&self) -> &Vec<Point3D> {
+        &self.points
+    }
+
+    pub fn length(&self) -> f64 {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    // Closest point on the center line and how far along the tube it is
+    pub fn closest_point(&self, p: &Point3D) -> (Point3D, f64) {
+        let (piece, _) = self
+            .bvh
+            .closest(p, |i| p.distance_to(&self.closest_on_piece(p, i).0))
+            .unwrap();
+        let (closest, t) = self.closest_on_piece(p, piece);
+        (closest, self.lengths[piece] + t * (self.lengths[piece + 1] - self.lengths[piece]))
+    }
+
+    fn closest_on_piece(&self, p: &Point3D, i: usize) -> (Point3D, f64) {
+        let (a, b) = (self.points[i], self.points[i + 1]);
+        let ab = b - a;
+        let len = ab.dot(&ab);
+        let t = if len > 0.0 { ((*p - a).dot(&ab) / len).clamp(0.0, 1.0) } else { 0.0 };
+        (a + ab * t, t)
+    }
+
+    fn piece_distance(&self, p: &Point3D, i: usize) -> f64 {
+        round_cone_distance(p, &self.points[i], &self.points[i + 1], self.radii[i], self.radii[i + 1])
+    }
+}
+
+// Exact distance to the hull of two spheres, from Inigo Quilez
+fn round_cone_distance(p: &Point3D, a: &Point3D, b: &Point3D, r1: f64, r2: f64) -> f64 {
+    let ba = *b - *a;
+    let l2 = ba.dot(&ba);
+    let rr = r1 - r2;
+    let a2 = l2 - rr * rr;
+    if a2 <= 0.0 {
+        // One sphere holds the other
+        return (p.distance_to(a) - r1).min(p.distance_to(b) - r2);
+    }
+    let il2 = 1.0 / l2;
+    let pa = *p - *a;
+    let y = pa.dot(&ba);
+    let z = y - l2;
+    let side = pa * l2 - ba * y;
+    let x2 = side.dot(&side);
+    let y2 = y * y * l2;
+    let z2 = z * z * l2;
+    let k = rr.signum() * rr * rr * x2;
+    if z.signum() * a2 * z2 > k {
+        return (x2 + z2).sqrt() * il2 - r2;
+    }
+    if y.signum() * a2 * y2 < k {
+        return (x2 + y2).sqrt() * il2 - r1;
+    }
+    ((x2 * a2 * il2).sqrt() + y * rr) * il2 - r1
+}
+
+impl SceneObject for Tube {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        self.bvh.closest(p, |i| self.piece_distance(p, i)).unwrap().1
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn get_surface_material_at(&self, p: &Point3D) -> SurfaceMaterial {
+        match &self.end_material {
+            Some(end) => {
+                // A tube whose points all coincide is a ball, it keeps the start material
+                let (_, along) = self.closest_point(p);
+                let t = if self.length() > 0.0 { along / self.length() } else { 0.0 };
+                SurfaceMaterial::mix(&self.surface_material, end, t)
+            }
+            None => self.surface_material,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.points.iter().zip(self.radii.iter()).fold(BoundingBox::empty(), |b, (p, r)| {
+            b.union(&BoundingBox::new(*p - *r, *p + *r))
+        }))
+    }
+
+    fn describe(&self) -> Option<ObjectDescription> {
+        // Splines were already split into pieces, so the tube is saved as a polyline
+        Some(ObjectDescription::Tube {
+            points: self.points.iter().map(vec3).collect(),
+            radii: self.radii.clone(),
+            path: TubePath::Polyline,
+            material: MaterialRef::describe(&self.surface_material),
+            end_material: self.end_material.as_ref().map(MaterialRef::inline),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::{Capsule, Sphere};
+    use super::*;
+    use crate::ray_marcher::threed_data_types::test_helpers::{assert_close, grid};
+    use crate::ray_marcher::color_data_types::Color;
+
+    fn sample_points() -> Vec<Point3D> {
+        grid((6, 6, 3), 0.5, Point3D::new(0.01, 0.0, -0.02))
+    }
+
+    #[test]
+    fn test_constant_radius_matches_capsules() {
+        let points = vec![Point3D::new(-2.0, 0.0, 0.0), Point3D::new(0.0, 1.0, 0.0), Point3D::new(2.0, -1.0, 0.5)];
+        let tube = Tube::with_radius(points.clone(), 0.3, None);
+        let capsules = [
+            Capsule::new(points[0], points[1], 0.3, None),
+            Capsule::new(points[1], points[2], 0.3, None),
+        ];
+        for p in sample_points() {
+            let expected = capsules[0].signed_distance(&p).min(capsules[1].signed_distance(&p));
+            assert_close(tube.signed_distance(&p), expected);
+        }
+    }
+
+    #[test]
+    fn test_round_cone_ends_are_spheres() {
+        let tube = Tube::new(vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(3.0, 0.0, 0.0)], vec![1.0, 0.25], None);
+        assert_close(tube.signed_distance(&Point3D::new(-2.0, 0.0, 0.0)), 1.0);
+        assert_close(tube.signed_distance(&Point3D::new(4.0, 0.0, 0.0)), 0.75);
+        // Tapers between the two radii
+        let middle = tube.signed_distance(&Point3D::new(1.5, 2.0, 0.0));
+        assert!(middle > 2.0 - 1.0 && middle < 2.0 - 0.25);
+    }
+
+    #[test]
+    fn test_round_cone_nested_spheres() {
+        let tube = Tube::new(vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.5, 0.0, 0.0)], vec![2.0, 0.5], None);
+        let big = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 2.0, None);
+        for p in sample_points() {
+            assert_close(tube.signed_distance(&p), big.signed_distance(&p));
+        }
+    }
+
+    #[test]
+    fn test_spline_tube_matches_brute_force() {
+        let tube = Tube::catmull_rom(
+            &[Point3D::new(-2.5, -1.0, 0.0), Point3D::new(-1.0, 1.5, 0.5), Point3D::new(1.0, -1.0, -0.5), Point3D::new(2.5, 1.0, 0.0)],
+            &[0.2, 0.5, 0.3, 0.1],
+            12,
+            None,
+        );
+        for p in sample_points() {
+            let brute = (0..tube.points.len() - 1).map(|i| tube.piece_distance(&p, i)).fold(f64::INFINITY, f64::min);
+            assert_close(tube.signed_distance(&p), brute);
+        }
+    }
+
+    #[test]
+    fn test_catmull_rom_passes_through_points() {
+        let points = [Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 2.0, 0.0), Point3D::new(3.0, 1.0, 1.0)];
+        let tube = Tube::catmull_rom(&points, &[0.1; 3], 8, None);
+        for p in points.iter() {
+            assert!(tube.get_points().contains(p));
+            assert_close(tube.signed_distance(p), -0.1);
+        }
+    }
+
+    #[test]
+    fn test_cubic_bezier() {
+        let points = [
+            Point3D::new(0.0, 0.0, 0.0), Point3D::new(0.0, 1.0, 0.0), Point3D::new(2.0, 1.0, 0.0), Point3D::new(2.0, 0.0, 0.0),
+            Point3D::new(2.0, -1.0, 0.0), Point3D::new(4.0, -1.0, 0.0), Point3D::new(4.0, 0.0, 0.0),
+        ];
+        let tube = Tube::cubic_bezier(&points, &[0.1, 0.2, 0.1], 16, None);
+        assert_eq!(tube.get_points().len(), 33);
+        // Apex of the first span at t = 0.5 is (1, 0.75, 0)
+        assert_close(tube.signed_distance(&Point3D::new(1.0, 0.75, 0.0)), -0.15);
+    }
+
+    #[test]
+    fn test_closest_point_along() {
+        let tube = Tube::with_radius(vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(2.0, 0.0, 0.0), Point3D::new(2.0, 2.0, 0.0)], 0.1, None);
+        let (closest, along) = tube.closest_point(&Point3D::new(3.0, 1.5, 0.0));
+        assert_eq!(closest, Point3D::new(2.0, 1.5, 0.0));
+        assert_close(along, 3.5);
+        assert_close(tube.length(), 4.0);
+    }
+
+    #[test]
+    fn test_end_material_gradient() {
+        let red = SurfaceMaterial::diffuse(Color::new(1.0, 0.0, 0.0));
+        let blue = SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0));
+        let tube = Tube::with_radius(vec![Point3D::new(0.0, 0.0, 0.0), Point3D::new(4.0, 0.0, 0.0)], 0.5, Some(red)).with_end_material(blue);
+        assert_eq!(tube.get_surface_material_at(&Point3D::new(-0.5, 0.0, 0.0)).albedo, red.albedo);
+        assert_eq!(tube.get_surface_material_at(&Point3D::new(4.5, 0.0, 0.0)).albedo, blue.albedo);
+
+        let ball = Tube::with_radius(vec![Point3D::new(1.0, 0.0, 0.0), Point3D::new(1.0, 0.0, 0.0)], 0.5, Some(red)).with_end_material(blue);
+        assert_eq!(ball.get_surface_material_at(&Point3D::new(2.0, 0.0, 0.0)), red);
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use super::super::scene_file::description::{MaterialRef, ObjectDescription};
+use super::super::threed_data_types::bounding_box::BoundingBox;
+use super::{Point3D, SceneObject, SurfaceMaterial, DEFAULT_SURFACEMAT};
+
+// Distances sampled on a regular grid spanning a world space box. Used to cache
+// expensive objects (fractals, meshes) so they can be baked once and loaded later.
+//
+// File layout, all little endian:
+//   b"SDFG", u32 version, u32 x/y/z sample counts, f64 min x/y/z, f64 max x/y/z,
+//   then one f32 per sample with x changing fastest and z slowest
+
+const MAGIC: &[u8; 4] = b"SDFG";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Trilinear,
+    Tricubic, // Catmull-Rom, smoother but reads 64 samples instead of 8
+}
+
+#[derive(Debug)]
+pub enum VoxelGridError {
+    Io(std::io::Error),
+    Format(String),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for VoxelGridError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxelGridError::Io(e) => write!(f, "could not read or write voxel grid: {}", e),
+            VoxelGridError::Format(message) => write!(f, "invalid voxel grid: {}", message),
+            VoxelGridError::UnsupportedVersion(v) => write!(f, "unsupported voxel grid version {}", v),
+        }
+    }
+}
+
+impl std::error::Error for VoxelGridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VoxelGridError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for VoxelGridError {
+    fn from(e: std::io::Error) -> Self {
+        VoxelGridError::Io(e)
+    }
+}
+
+pub struct VoxelGrid {
+    bounds: BoundingBox,
+    resolution: [usize; 3], // Samples along each axis, samples sit on the box faces too
+    data: Vec<f32>,
+    interpolation: Interpolation,
+    position: Point3D,
+    surface_material: SurfaceMaterial,
+    source: Option<PathBuf>, // The file it was loaded from, scene files refer to it
+}
+
+#[allow(dead_code)]
+impl VoxelGrid {
+    pub fn new(bounds: BoundingBox, resolution: [usize; 3], data: Vec<f32>, sm: Option<SurfaceMaterial>) -> Result<Self, VoxelGridError> {
+        if resolution.iter().any(|r| *r < 2) {
+            return Err(VoxelGridError::Format(format!("needs at least 2 samples per axis, got {:?}", resolution)));
+        }
+        let size = bounds.size();
+        if !(size.x > 0.0 && size.y > 0.0 && size.z > 0.0) {
+            return Err(VoxelGridError::Format("bounds have no volume".to_string()));
+        }
+        let expected = sample_count(resolution)?;
+        if data.len() != expected {
+            return Err(VoxelGridError::Format(format!("expected {} samples, got {}", expected, data.len())));
+        }
+        Ok(VoxelGrid {
+            position: bounds.center(),
+            bounds,
+            resolution,
+            data,
+            interpolation: Interpolation::Trilinear,
+            surface_material: sm.unwrap_or(DEFAULT_SURFACEMAT),
+            source: None,
+        })
+    }
+
+    // Samples the object at every grid point. Without a material the object's own is
+    // kept. The bounds should hold the whole object with some margin, distances
+    // outside them are only estimated from the box.
+    pub fn bake(object: &dyn SceneObject, bounds: BoundingBox, resolution: [usize; 3], sm: Option<SurfaceMaterial>) -> Result<Self, VoxelGridError> {
+        if resolution.iter().any(|r| *r < 2) {
+            return Err(VoxelGridError::Format(format!("needs at least 2 samples per axis, got {:?}", resolution)));
+        }
+        let count = sample_count(resolution)?;
+        let [nx, ny, nz] = resolution;
+        let step = bounds.size() / Point3D::new((nx - 1) as f64, (ny - 1) as f64, (nz - 1) as f64);
+        let data = (0..count)
+            .into_par_iter()
+            .map(|i| {
+                let cell = Point3D::new((i % nx) as f64, ((i / nx) % ny) as f64, (i / (nx * ny)) as f64);
+                object.signed_distance(&(bounds.min + cell * step)) as f32
+            })
+            .collect();
+        VoxelGrid::new(bounds, resolution, data, Some(sm.unwrap_or(object.get_surface_material())))
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn get_source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    pub fn get_bounds(&self) -> &BoundingBox {
+        &self.bounds
+    }
+
+    pub fn get_resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    pub fn get_sample(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.data[x + self.resolution[0] * (y + self.resolution[1] * z)]
+    }
+
+    // Reads a sample with the indices clamped into the grid
+    fn sample_clamped(&self, x: isize, y: isize, z: isize) -> f64 {
+        let clamp = |v: isize, n: usize| v.clamp(0, n as isize - 1) as usize;
+        self.get_sample(clamp(x, self.resolution[0]), clamp(y, self.resolution[1]), clamp(z, self.resolution[2])) as f64
+    }
+
+    // Interpolated distance at a point inside the bounds
+    fn interpolate(&self, p: &Point3D) -> f64 {
+        let [nx, ny, nz] = self.resolution;
+        let cells = Point3D::new((nx - 1) as f64, (ny - 1) as f64, (nz - 1) as f64);
+        let g = (*p - self.bounds.min) / self.bounds.size() * cells;
+        // Keep the base cell inside the grid so points on the max faces still interpolate
+        let base = |v: f64, n: usize| (v.floor() as isize).clamp(0, n as isize - 2);
+        let (ix, iy, iz) = (base(g.x, nx), base(g.y, ny), base(g.z, nz));
+        let (fx, fy, fz) = (g.x - ix as f64, g.y - iy as f64, g.z - iz as f64);
+
+        match self.interpolation {
+            Interpolation::Trilinear => {
+                let wx = [1.0 - fx, fx];
+                let wy = [1.0 - fy, fy];
+                let wz = [1.0 - fz, fz];
+                self.weighted_sum(ix, iy, iz, 0, &wx, &wy, &wz)
+            }
+            Interpolation::Tricubic => {
+                self.weighted_sum(ix, iy, iz, -1, &catmull_rom_weights(fx), &catmull_rom_weights(fy), &catmull_rom_weights(fz))
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn weighted_sum(&self, ix: isize, iy: isize, iz: isize, offset: isize, wx: &[f64], wy: &[f64], wz: &[f64]) -> f64 {
+        let mut total = 0.0;
+        for (k, z_weight) in wz.iter().enumerate() {
+            for (j, y_weight) in wy.iter().enumerate() {
+                for (i, x_weight) in wx.iter().enumerate() {
+                    let sample = self.sample_clamped(ix + offset + i as isize, iy + offset + j as isize, iz + offset + k as isize);
+                    total += sample * x_weight * y_weight * z_weight;
+                }
+            }
+        }
+        total
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), VoxelGridError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, sm: Option<SurfaceMaterial>) -> Result<Self, VoxelGridError> {
+        let grid = VoxelGrid::read_from(&mut BufReader::new(File::open(path.as_ref())?), sm)?;
+        Ok(VoxelGrid { source: Some(path.as_ref().to_path_buf()), ..grid })
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), VoxelGridError> {
+        // Checked before writing anything so a failed save leaves no partial header
+        let mut counts = [0_u32; 3];
+        for (count, r) in counts.iter_mut().zip(self.resolution.iter()) {
+            *count = u32::try_from(*r)
+                .map_err(|_| VoxelGridError::Format(format!("resolution {:?} doesn't fit in the file format", self.resolution)))?;
+        }
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        for count in counts.iter() {
+            writer.write_all(&count.to_le_bytes())?;
+        }
+        for p in [self.bounds.min, self.bounds.max] {
+            for v in [p.x, p.y, p.z] {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+        for v in self.data.iter() {
+            writer.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R, sm: Option<SurfaceMaterial>) -> Result<Self, VoxelGridError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(VoxelGridError::Format("not a voxel grid file".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(VoxelGridError::UnsupportedVersion(version));
+        }
+        let resolution = [read_u32(reader)? as usize, read_u32(reader)? as usize, read_u32(reader)? as usize];
+        let min = Point3D::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+        let max = Point3D::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+
+        let count = sample_count(resolution)?;
+        let length = count
+            .checked_mul(4)
+            .ok_or_else(|| VoxelGridError::Format(format!("resolution {:?} is too large", resolution)))?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() != length {
+            return Err(VoxelGridError::Format(format!("expected {} samples, found {} bytes of data", count, bytes.len())));
+        }
+        let data = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        VoxelGrid::new(BoundingBox::new(min, max), resolution, data, sm)
+    }
+}
+
+impl SceneObject for VoxelGrid {
+    fn signed_distance(&self, p: &Point3D) -> f64 {
+        let outside = self.bounds.distance_to(p);
+        if outside == 0.0 {
+            return self.interpolate(p);
+        }
+        // The object sits inside the box, so from a point outside it the path to the
+        // surface passes the closest box point at a right angle or wider
+        let clamped = Point3D::new(
+            p.x.clamp(self.bounds.min.x, self.bounds.max.x),
+            p.y.clamp(self.bounds.min.y, self.bounds.max.y),
+            p.z.clamp(self.bounds.min.z, self.bounds.max.z),
+        );
+        let edge = self.interpolate(&clamped).max(0.0);
+        (outside * outside + edge * edge).sqrt()
+    }
+
+    fn get_position(&self) -> &Point3D {
+        &self.position
+    }
+
+    fn get_surface_material(&self) -> SurfaceMaterial {
+        self.surface_material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.bounds)
+    }
+
+    // Only grids read from a file can be written, baking one doesn't save it
+    fn describe(&self) -> Option<ObjectDescription> {
+        Some(ObjectDescription::VoxelGrid {
+            path: self.source.as_ref()?.to_string_lossy().into_owned(),
+            interpolation: self.interpolation,
+            material: MaterialRef::describe(&self.surface_material),
+        })
+    }
+}
+
+fn catmull_rom_weights(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+fn sample_count(resolution: [usize; 3]) -> Result<usize, VoxelGridError> {
+    resolution
+        .iter()
+        .try_fold(1_usize, |n, r| n.checked_mul(*r))
+        .ok_or_else(|| VoxelGridError::Format(format!("resolution {:?} is too large", resolution)))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, VoxelGridError> {
+    let mut b = [0; 4];
+    reader.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> Result<f64, VoxelGridError> {
+    let mut b = [0; 8];
+    reader.read_exact(&mut b)?;
+    Ok(f64::from_le_bytes(b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::objects::Sphere;
+    use super::*;
+
+    fn baked_sphere(resolution: usize) -> VoxelGrid {
+        let sphere = Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, None);
+        let bounds = BoundingBox::new(Point3D::new(-1.5, -1.5, -1.5), Point3D::new(1.5, 1.5, 1.5));
+        VoxelGrid::bake(&sphere, bounds, [resolution; 3], None).unwrap()
+    }
+
+    // Worst error in a shell around the surface, away from the kink at the center
+    fn max_error(grid: &VoxelGrid) -> f64 {
+        let mut worst: f64 = 0.0;
+        for i in 0..=20 {
+            let r = 0.6 + 0.8 * i as f64 / 20.0;
+            for dir in [Point3D::new(1.0, 0.0, 0.0), Point3D::new(0.6, 0.48, -0.64), Point3D::new(-0.36, 0.8, 0.48)] {
+                let p = dir * r;
+                worst = worst.max((grid.signed_distance(&p) - (r - 1.0)).abs());
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn test_voxel_grid_matches_samples() {
+        let grid = baked_sphere(7);
+        // Grid points hold the baked values exactly
+        assert_eq!(grid.signed_distance(&Point3D::new(-1.5, -1.5, -1.5)), grid.get_sample(0, 0, 0) as f64);
+        assert_eq!(grid.signed_distance(&Point3D::new(0.0, 0.0, 0.0)), -1.0);
+        assert_eq!(grid.signed_distance(&Point3D::new(1.5, 1.5, 1.5)) as f32, grid.get_sample(6, 6, 6));
+    }
+
+    #[test]
+    fn test_voxel_grid_trilinear_accuracy() {
+        assert!(max_error(&baked_sphere(33)) < 0.01);
+    }
+
+    #[test]
+    fn test_voxel_grid_tricubic_more_accurate() {
+        let grid = baked_sphere(9);
+        let trilinear = max_error(&grid);
+        let tricubic = max_error(&grid.with_interpolation(Interpolation::Tricubic));
+        assert!(tricubic < trilinear);
+    }
+
+    #[test]
+    fn test_voxel_grid_outside_bounds() {
+        let grid = baked_sphere(17);
+        for p in [Point3D::new(4.0, 0.0, 0.0), Point3D::new(3.0, -3.0, 2.0)] {
+            let d = grid.signed_distance(&p);
+            assert!(d > 0.0 && d <= p.length() - 1.0 + 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_voxel_grid_round_trip() {
+        let grid = baked_sphere(5);
+        let mut bytes = Vec::new();
+        grid.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 + 4 + 12 + 48 + 125 * 4);
+        let loaded = VoxelGrid::read_from(&mut bytes.as_slice(), None).unwrap();
+        assert_eq!(loaded.get_bounds(), grid.get_bounds());
+        assert_eq!(loaded.data, grid.data);
+    }
+
+    #[test]
+    fn test_voxel_grid_save_load() {
+        let path = std::env::temp_dir().join(format!("ray_marcher_voxel_test_{}.sdf", std::process::id()));
+        let grid = baked_sphere(4);
+        grid.save(&path).unwrap();
+        let loaded = VoxelGrid::load(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().data, grid.data);
+    }
+
+    #[test]
+    fn test_voxel_grid_bad_files() {
+        assert!(matches!(VoxelGrid::read_from(&mut &b"NOPE0000"[..], None), Err(VoxelGridError::Format(_))));
+
+        let mut bytes = Vec::new();
+        baked_sphere(3).write_to(&mut bytes).unwrap();
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 9;
+        assert!(matches!(VoxelGrid::read_from(&mut wrong_version.as_slice(), None), Err(VoxelGridError::UnsupportedVersion(9))));
+
+        bytes.truncate(bytes.len() - 2);
+        assert!(matches!(VoxelGrid::read_from(&mut bytes.as_slice(), None), Err(VoxelGridError::Format(_))));
+        assert!(matches!(VoxelGrid::read_from(&mut &bytes[..10], None), Err(VoxelGridError::Io(_))));
+
+        // 2^63 samples fit in a usize but their bytes don't
+        let mut huge = bytes[..8].to_vec();
+        for r in [1_u32 << 31, 1 << 31, 2] {
+            huge.extend_from_slice(&r.to_le_bytes());
+        }
+        huge.extend_from_slice(&bytes[20..68]);
+        assert!(matches!(VoxelGrid::read_from(&mut huge.as_slice(), None), Err(VoxelGridError::Format(_))));
+    }
+
+    #[test]
+    fn test_voxel_grid_rejects_bad_data() {
+        let bounds = BoundingBox::new(Point3D::new(0.0, 0.0, 0.0), Point3D::new(1.0, 1.0, 1.0));
+        assert!(VoxelGrid::new(bounds, [2, 2, 2], vec![0.0; 7], None).is_err());
+        assert!(VoxelGrid::new(bounds, [1, 2, 2], vec![0.0; 4], None).is_err());
+        let huge = [usize::MAX / 2, 3, 2];
+        assert!(matches!(VoxelGrid::new(bounds, huge, Vec::new(), None), Err(VoxelGridError::Format(_))));
+        let sphere = Sphere::new(Point3D::new(0.5, 0.5, 0.5), 0.25, None);
+        assert!(matches!(VoxelGrid::bake(&sphere, bounds, huge, None), Err(VoxelGridError::Format(_))));
+    }
+
+    #[test]
+    fn test_voxel_grid_resolution_must_fit_file() {
+        // Too many samples to allocate, so the grid is faked with no data
+        let grid = VoxelGrid { resolution: [u32::MAX as usize + 1, 2, 2], data: Vec::new(), ..baked_sphere(2) };
+        let mut bytes = Vec::new();
+        assert!(matches!(grid.write_to(&mut bytes), Err(VoxelGridError::Format(_))));
+        assert!(bytes.is_empty());
+    }
+}
@@ -19,7 +19,7 @@ where
 {
     fn get_red_channel(&self, index: (u32, u32)) -> Self::Component {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get(linear_index as usize)
             .unwrap()
@@ -28,7 +28,7 @@ where
 
     fn get_green_channel(&self, index: (u32, u32)) -> Self::Component {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get(linear_index as usize)
             .unwrap()
@@ -37,7 +37,7 @@ where
 
     fn get_blue_channel(&self, index: (u32, u32)) -> Self::Component {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get(linear_index as usize)
             .unwrap()
@@ -46,7 +46,7 @@ where
 
     fn set_red_channel(&mut self, index: (u32, u32), new_r: Self::Component) {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get_mut(linear_index as usize)
             .unwrap()
@@ -55,7 +55,7 @@ where
 
     fn set_green_channel(&mut self, index: (u32, u32), new_g: Self::Component) {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get_mut(linear_index as usize)
             .unwrap()
@@ -64,7 +64,7 @@ where
 
     fn set_blue_channel(&mut self, index: (u32, u32), new_b: Self::Component) {
         let (num_rows, _num_cols) = self.get_resolution();
-        let linear_index = (num_rows * index.0) + index.1;
+        let linear_index = index.0 + (num_rows * index.1);
         self.pixels
             .get_mut(linear_index as usize)
             .unwrap()
@@ -1,5 +1,9 @@
 pub mod point_operations;
 pub mod direction_operations;
+pub mod transform;
+pub mod bounding_box;
+
+use serde::{Deserialize, Serialize};
 
 //-------- Point Vector -----------
 
@@ -25,6 +29,27 @@ impl Point {
         *self /= self.distance_to(&Point { x: 0.0, y: 0.0, z: 0.0 });
         *self
     }
+    pub fn length(&self) -> f64 {
+        (self.x*self.x + self.y*self.y + self.z*self.z).sqrt()
+    }
+    pub fn dot(&self, p: &Self) -> f64 {
+        self.x*p.x + self.y*p.y + self.z*p.z
+    }
+    pub fn abs(&self) -> Self {
+        Self { x: self.x.abs(), y: self.y.abs(), z: self.z.abs() }
+    }
+    pub fn max(&self, v: f64) -> Self { // Component-wise maximum against a scalar
+        Self { x: self.x.max(v), y: self.y.max(v), z: self.z.max(v) }
+    }
+    pub fn max_component(&self) -> f64 {
+        self.x.max(self.y.max(self.z))
+    }
+    pub fn min_component(&self) -> f64 {
+        self.x.min(self.y.min(self.z))
+    }
+    pub fn cross(&self, p: &Self) -> Self {
+        Self { x: self.y*p.z - self.z*p.y, y: self.z*p.x - self.x*p.z, z: self.x*p.y - self.y*p.x }
+    }
 }
 
 //------ Direction Vector ---------
@@ -90,6 +115,43 @@ impl Direction {
     }
 }
 
+//------------- Axis --------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    // The two axes spanning the plane perpendicular to this one, in right-handed order
+    pub fn perpendicular(&self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::Z, Axis::X),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+impl Point {
+    pub fn get_axis(&self, axis: Axis) -> f64 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+    pub fn set_axis(&mut self, axis: Axis, v: f64) {
+        match axis {
+            Axis::X => self.x = v,
+            Axis::Y => self.y = v,
+            Axis::Z => self.z = v,
+        }
+    }
+}
+
 //---------- Constants ------------
 
 pub mod constants {
@@ -100,6 +162,73 @@ pub mod constants {
     pub static Z_DIR: Direction = Direction{x: 0.0, y: 0.0, z: 1.0};
 }
 
+// Float comparisons, sample points and materials shared by the tests across the crate
+#[cfg(test)]
+pub mod test_helpers {
+    use super::Point;
+    use crate::ray_marcher::color_data_types::Color;
+    use crate::ray_marcher::scene_objects::{SceneObject, SurfaceMaterial};
+
+    pub fn assert_within(actual: f64, expected: f64, tolerance: f64) {
+        // Equal infinities compare as close too
+        assert!(actual == expected || (actual - expected).abs() <= tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    pub fn assert_close(actual: f64, expected: f64) {
+        assert_within(actual, expected, 1e-9);
+    }
+
+    pub fn red() -> Option<SurfaceMaterial> {
+        Some(SurfaceMaterial::diffuse(Color::new(1.0, 0.0, 0.0)))
+    }
+
+    pub fn blue() -> Option<SurfaceMaterial> {
+        Some(SurfaceMaterial::diffuse(Color::new(0.0, 0.0, 1.0)))
+    }
+
+    // A lattice of 2n + 1 points per axis around `offset`
+    pub fn grid(counts: (i32, i32, i32), spacing: f64, offset: Point) -> Vec<Point> {
+        let mut points = Vec::new();
+        for xi in -counts.0..=counts.0 {
+            for yi in -counts.1..=counts.1 {
+                for zi in -counts.2..=counts.2 {
+                    points.push(Point::new(xi as f64 * spacing, yi as f64 * spacing, zi as f64 * spacing) + offset);
+                }
+            }
+        }
+        points
+    }
+
+    // Evenly spread unit directions on a sphere
+    pub fn fibonacci_directions(n: usize) -> Vec<Point> {
+        let golden = std::f64::consts::PI * (3.0 - 5_f64.sqrt());
+        (0..n)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+                let r = (1.0 - y * y).sqrt();
+                let theta = golden * i as f64;
+                Point::new(r * theta.cos(), y, r * theta.sin())
+            })
+            .collect()
+    }
+
+    // A march step of `signed_distance * step_scale` from any of the points must stay on
+    // the same side of the surface in every direction, or the marcher could skip past it
+    pub fn assert_safe_steps(o: &dyn SceneObject, points: &[Point], directions: &[Point]) {
+        for p in points {
+            let step = o.signed_distance(p) * o.get_step_scale();
+            for dir in directions {
+                let d = o.signed_distance(&(*p + *dir * step.abs()));
+                if step > 0.0 {
+                    assert!(d > -1e-9, "overstep from {:?}", p);
+                } else if step < 0.0 {
+                    assert!(d < 1e-9, "overstep from {:?}", p);
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,163 @@
+use super::*;
+
+// An axis-aligned box, used to bound objects for faster distance queries
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point,
+    pub max: Point,
+}
+
+#[allow(dead_code)]
+impl BoundingBox {
+    pub fn new(min: Point, max: Point) -> Self {
+        BoundingBox { min, max }
+    }
+
+    // A box that contains nothing, growing it by any point gives that point
+    pub fn empty() -> Self {
+        BoundingBox {
+            min: Point::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<'a, I>(points: I) -> Self where I: IntoIterator<Item = &'a Point> {
+        let mut b = BoundingBox::empty();
+        for p in points {
+            b.grow(p);
+        }
+        b
+    }
+
+    pub fn grow(&mut self, p: &Point) {
+        self.min = Point::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+        self.max = Point::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut b = *self;
+        b.grow(&other.min);
+        b.grow(&other.max);
+        b
+    }
+
+    // Empty when the boxes don't overlap
+    pub fn intersection(&self, other: &Self) -> Self {
+        BoundingBox {
+            min: Point::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z)),
+            max: Point::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z)),
+        }
+    }
+
+    pub fn padded(&self, amount: f64) -> Self {
+        BoundingBox { min: self.min - amount, max: self.max + amount }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Point {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Point {
+        self.max - self.min
+    }
+
+    pub fn longest_axis(&self) -> Axis {
+        let s = self.size();
+        if s.x >= s.y && s.x >= s.z {
+            Axis::X
+        } else if s.y >= s.z {
+            Axis::Y
+        } else {
+            Axis::Z
+        }
+    }
+
+    pub fn corners(&self) -> [Point; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Point::new(a.x, a.y, a.z), Point::new(b.x, a.y, a.z), Point::new(a.x, b.y, a.z), Point::new(b.x, b.y, a.z),
+            Point::new(a.x, a.y, b.z), Point::new(b.x, a.y, b.z), Point::new(a.x, b.y, b.z), Point::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // Bounds everything the box can reach when spun around `axis` through `center`
+    pub fn swept_around(&self, center: &Point, axis: Axis) -> Self {
+        let (u_axis, v_axis) = axis.perpendicular();
+        let reach = self
+            .corners()
+            .iter()
+            .map(|c| (c.get_axis(u_axis) - center.get_axis(u_axis)).hypot(c.get_axis(v_axis) - center.get_axis(v_axis)))
+            .fold(0.0, f64::max);
+        let mut b = *self;
+        for a in [u_axis, v_axis] {
+            b.min.set_axis(a, center.get_axis(a) - reach);
+            b.max.set_axis(a, center.get_axis(a) + reach);
+        }
+        b
+    }
+
+    pub fn contains(&self, p: &Point) -> bool {
+        p.x >= self.min.x && p.x <= self.max.x
+            && p.y >= self.min.y && p.y <= self.max.y
+            && p.z >= self.min.z && p.z <= self.max.z
+    }
+
+    // Distance from the point to the box, zero anywhere inside it
+    pub fn distance_to(&self, p: &Point) -> f64 {
+        let dx = (self.min.x - p.x).max(p.x - self.max.x).max(0.0);
+        let dy = (self.min.y - p.y).max(p.y - self.max.y).max(0.0);
+        let dz = (self.min.z - p.z).max(p.z - self.max.z).max(0.0);
+        (dx * dx + dy * dy + dz * dz).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_points() {
+        let points = [Point::new(1.0, -2.0, 0.0), Point::new(-1.0, 3.0, 2.0)];
+        let b = BoundingBox::from_points(points.iter());
+        assert_eq!(b, BoundingBox::new(Point::new(-1.0, -2.0, 0.0), Point::new(1.0, 3.0, 2.0)));
+    }
+
+    #[test]
+    fn test_distance_to() {
+        let b = BoundingBox::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        assert_eq!(b.distance_to(&Point::new(0.0, 0.5, 0.0)), 0.0);
+        assert_eq!(b.distance_to(&Point::new(3.0, 0.0, 0.0)), 2.0);
+        assert_eq!(b.distance_to(&Point::new(2.0, 2.0, 1.0)), 2_f64.sqrt());
+    }
+
+    #[test]
+    fn test_intersection() {
+        let a = BoundingBox::new(Point::new(0.0, 0.0, 0.0), Point::new(2.0, 2.0, 2.0));
+        let b = BoundingBox::new(Point::new(1.0, -1.0, 1.0), Point::new(3.0, 1.0, 3.0));
+        assert_eq!(a.intersection(&b), BoundingBox::new(Point::new(1.0, 0.0, 1.0), Point::new(2.0, 1.0, 2.0)));
+        let far = BoundingBox::new(Point::new(5.0, 5.0, 5.0), Point::new(6.0, 6.0, 6.0));
+        assert!(a.intersection(&far).is_empty());
+    }
+
+    #[test]
+    fn test_swept_around() {
+        let b = BoundingBox::new(Point::new(1.0, 0.0, -1.0), Point::new(3.0, 5.0, 1.0));
+        let swept = b.swept_around(&Point::new(0.0, 0.0, 0.0), Axis::Y);
+        let reach = 10_f64.sqrt();
+        assert_eq!(swept, BoundingBox::new(Point::new(-reach, 0.0, -reach), Point::new(reach, 5.0, reach)));
+    }
+
+    #[test]
+    fn test_empty() {
+        assert!(BoundingBox::empty().is_empty());
+        let mut b = BoundingBox::empty();
+        b.grow(&Point::new(1.0, 2.0, 3.0));
+        assert!(!b.is_empty());
+        assert!(b.contains(&Point::new(1.0, 2.0, 3.0)));
+    }
+}
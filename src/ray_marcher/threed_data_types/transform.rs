@@ -0,0 +1,269 @@
+use super::*;
+
+type Matrix = [[f64; 3]; 3];
+
+static IDENTITY: Matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+// An affine transform: a linear part (rotation and scale) followed by a translation
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+    translation: Point,
+    min_scale: f64, // Smallest factor the linear part can shrink a length by
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 3]; 3];
+    for (r, row) in m.iter_mut().enumerate() {
+        for (c, v) in row.iter_mut().enumerate() {
+            *v = a[r][0] * b[0][c] + a[r][1] * b[1][c] + a[r][2] * b[2][c];
+        }
+    }
+    m
+}
+
+fn apply(m: &Matrix, p: &Point) -> Point {
+    Point::new(
+        m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z,
+        m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z,
+        m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z,
+    )
+}
+
+fn transpose(m: &Matrix) -> Matrix {
+    let mut t = [[0.0; 3]; 3];
+    for (r, row) in m.iter().enumerate() {
+        for (c, v) in row.iter().enumerate() {
+            t[c][r] = *v;
+        }
+    }
+    t
+}
+
+// None when the matrix collapses space, or so nearly that its inverse is meaningless
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+        [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+        [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+    ];
+    let det = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    if det.is_nan() || det.abs() <= 1e-12 {
+        return None;
+    }
+    let mut inv = adjugate;
+    inv.iter_mut().flatten().for_each(|v| *v /= det);
+    Some(inv)
+}
+
+// Smallest eigenvalue of a symmetric 3x3 matrix, using the closed form for its roots
+fn min_symmetric_eigenvalue(a: &Matrix) -> f64 {
+    let off = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+    if off < 1e-24 {
+        return a[0][0].min(a[1][1].min(a[2][2]));
+    }
+    let q = (a[0][0] + a[1][1] + a[2][2]) / 3.0;
+    let p2 = (a[0][0] - q).powi(2) + (a[1][1] - q).powi(2) + (a[2][2] - q).powi(2) + 2.0 * off;
+    let p = (p2 / 6.0).sqrt();
+    let mut b = *a;
+    for (i, row) in b.iter_mut().enumerate() {
+        row[i] -= q;
+        row.iter_mut().for_each(|v| *v /= p);
+    }
+    let det_b = b[0][0] * (b[1][1] * b[2][2] - b[1][2] * b[2][1])
+        - b[0][1] * (b[1][0] * b[2][2] - b[1][2] * b[2][0])
+        + b[0][2] * (b[1][0] * b[2][1] - b[1][1] * b[2][0]);
+    let phi = (det_b / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+    q + 2.0 * p * (phi + 2.0 * std::f64::consts::PI / 3.0).cos()
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub fn identity() -> Self {
+        Transform::from_matrix(IDENTITY, Point::new(0.0, 0.0, 0.0))
+    }
+
+    // Scales, then rotates around X, Y and Z (in radians), then translates
+    pub fn new(translation: Point, rotation: Point, scale: Point) -> Self {
+        Transform::from_scale(scale)
+            .then(&Transform::from_rotation(Direction::new(1.0, 0.0, 0.0), rotation.x))
+            .then(&Transform::from_rotation(Direction::new(0.0, 1.0, 0.0), rotation.y))
+            .then(&Transform::from_rotation(Direction::new(0.0, 0.0, 1.0), rotation.z))
+            .then(&Transform::from_translation(translation))
+    }
+
+    // Like `new`, but None instead of a panic for a scale that collapses space or values
+    // that aren't finite
+    pub fn try_new(translation: Point, rotation: Point, scale: Point) -> Option<Self> {
+        if ![rotation.x, rotation.y, rotation.z].iter().all(|v| v.is_finite()) {
+            return None;
+        }
+        let scaled = Transform::try_from_matrix([[scale.x, 0.0, 0.0], [0.0, scale.y, 0.0], [0.0, 0.0, scale.z]], Point::new(0.0, 0.0, 0.0))?;
+        let moved = Transform::try_from_matrix(IDENTITY, translation)?;
+        Some(scaled
+            .then(&Transform::from_rotation(Direction::new(1.0, 0.0, 0.0), rotation.x))
+            .then(&Transform::from_rotation(Direction::new(0.0, 1.0, 0.0), rotation.y))
+            .then(&Transform::from_rotation(Direction::new(0.0, 0.0, 1.0), rotation.z))
+            .then(&moved))
+    }
+
+    pub fn from_matrix(matrix: [[f64; 3]; 3], translation: Point) -> Self {
+        match Transform::try_from_matrix(matrix, translation) {
+            Some(t) => t,
+            None => panic!("Transform must not collapse space or hold values that aren't finite ({:?}, {:?})", matrix, translation),
+        }
+    }
+
+    // None when the matrix collapses space or any value isn't finite
+    pub fn try_from_matrix(matrix: [[f64; 3]; 3], translation: Point) -> Option<Self> {
+        if !matrix.iter().flatten().chain([translation.x, translation.y, translation.z].iter()).all(|v| v.is_finite()) {
+            return None;
+        }
+        let inverse = invert(&matrix)?;
+        let min_scale = min_symmetric_eigenvalue(&multiply(&transpose(&matrix), &matrix)).max(0.0).sqrt();
+        Some(Transform { matrix, inverse, translation, min_scale })
+    }
+
+    pub fn from_translation(translation: Point) -> Self {
+        Transform::from_matrix(IDENTITY, translation)
+    }
+
+    // Rotation by `angle` radians around `axis`, counter-clockwise when looking down the axis
+    pub fn from_rotation(axis: Direction, angle: f64) -> Self {
+        let a = axis.get_norm();
+        let (s, c) = angle.sin_cos();
+        let t = 1.0 - c;
+        let matrix = [
+            [t * a.x * a.x + c, t * a.x * a.y - s * a.z, t * a.x * a.z + s * a.y],
+            [t * a.x * a.y + s * a.z, t * a.y * a.y + c, t * a.y * a.z - s * a.x],
+            [t * a.x * a.z - s * a.y, t * a.y * a.z + s * a.x, t * a.z * a.z + c],
+        ];
+        Transform::from_matrix(matrix, Point::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn from_scale(scale: Point) -> Self {
+        let matrix = [[scale.x, 0.0, 0.0], [0.0, scale.y, 0.0], [0.0, 0.0, scale.z]];
+        Transform::from_matrix(matrix, Point::new(0.0, 0.0, 0.0))
+    }
+
+    pub fn from_uniform_scale(scale: f64) -> Self {
+        Transform::from_scale(Point::new(scale, scale, scale))
+    }
+
+    // The transform that applies `self` first and `next` afterwards
+    pub fn then(&self, next: &Transform) -> Self {
+        let matrix = multiply(&next.matrix, &self.matrix);
+        let translation = apply(&next.matrix, &self.translation) + next.translation;
+        Transform::from_matrix(matrix, translation)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let translation = apply(&self.inverse, &self.translation) * -1.0;
+        Transform::from_matrix(self.inverse, translation)
+    }
+
+    pub fn transform_point(&self, p: &Point) -> Point {
+        apply(&self.matrix, p) + self.translation
+    }
+
+    pub fn inverse_transform_point(&self, p: &Point) -> Point {
+        apply(&self.inverse, &(*p - self.translation))
+    }
+
+    pub fn transform_direction(&self, d: &Direction) -> Direction {
+        apply(&self.matrix, &d.to_point()).to_direction()
+    }
+
+    pub fn inverse_transform_direction(&self, d: &Direction) -> Direction {
+        apply(&self.inverse, &d.to_point()).to_direction()
+    }
+
+    pub fn get_matrix(&self) -> &[[f64; 3]; 3] {
+        &self.matrix
+    }
+
+    pub fn get_translation(&self) -> &Point {
+        &self.translation
+    }
+
+    pub fn get_min_scale(&self) -> f64 {
+        self.min_scale
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_points_close(a: Point, b: Point) {
+        assert!(a.distance_to(&b) < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_identity_point() {
+        let p = Point::new(1.0, 2.0, 3.0);
+        assert_eq!(Transform::identity().transform_point(&p), p);
+    }
+
+    #[test]
+    fn test_rotation_around_z() {
+        let t = Transform::from_rotation(Direction::new(0.0, 0.0, 1.0), 90_f64.to_radians());
+        assert_points_close(t.transform_point(&Point::new(1.0, 0.0, 0.0)), Point::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_inverse_round_trip() {
+        let t = Transform::new(Point::new(1.0, -2.0, 3.0), Point::new(0.3, 1.1, -0.7), Point::new(2.0, 0.5, 1.5));
+        let p = Point::new(-4.0, 0.25, 9.0);
+        assert_points_close(t.inverse_transform_point(&t.transform_point(&p)), p);
+        assert_points_close(t.inverse().transform_point(&t.transform_point(&p)), p);
+    }
+
+    #[test]
+    fn test_then_order() {
+        // Scaling before translating leaves the translation untouched
+        let t = Transform::from_uniform_scale(2.0).then(&Transform::from_translation(Point::new(1.0, 0.0, 0.0)));
+        assert_points_close(t.transform_point(&Point::new(1.0, 0.0, 0.0)), Point::new(3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_min_scale_uniform() {
+        let t = Transform::new(Point::new(5.0, 0.0, 0.0), Point::new(0.4, 0.2, 0.9), Point::new(3.0, 3.0, 3.0));
+        assert!((t.get_min_scale() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_min_scale_non_uniform() {
+        let t = Transform::new(Point::new(0.0, 0.0, 0.0), Point::new(0.4, 0.2, 0.9), Point::new(3.0, 0.5, 2.0));
+        assert!((t.get_min_scale() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_degenerate_scale() {
+        let _t = Transform::from_scale(Point::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_fallible_constructors() {
+        let origin = Point::new(0.0, 0.0, 0.0);
+        assert!(Transform::try_from_matrix([[1.0, 2.0, 0.0], [2.0, 4.0, 0.0], [0.0, 0.0, 1.0]], origin).is_none());
+        assert!(Transform::try_from_matrix(IDENTITY, Point::new(f64::NAN, 0.0, 0.0)).is_none());
+        assert_eq!(Transform::try_from_matrix(IDENTITY, origin), Some(Transform::identity()));
+        // A tiny scale collapses space just as surely as a zero one
+        assert!(Transform::try_new(origin, origin, Point::new(1e-4, 1e-4, 1e-4)).is_none());
+        assert!(Transform::try_new(origin, Point::new(f64::INFINITY, 0.0, 0.0), Point::new(1.0, 1.0, 1.0)).is_none());
+        let rotation = Point::new(0.3, 1.1, -0.7);
+        let scale = Point::new(2.0, 0.5, 1.5);
+        assert_eq!(Transform::try_new(origin, rotation, scale), Some(Transform::new(origin, rotation, scale)));
+    }
+}